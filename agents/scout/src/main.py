@@ -12,6 +12,7 @@ import asyncio
 import json
 import os
 import hashlib
+import time
 from typing import Optional, Dict, Any
 from dataclasses import dataclass
 from eth_account import Account
@@ -303,7 +304,7 @@ class ScoutAgent:
                         "source": source_url,
                         "data": data,
                         "hash": data_hash,
-                        "timestamp": int(asyncio.get_event_loop().time())
+                        "timestamp": int(time.time())
                     })
                     
                     # If payment was made, extract proof
@@ -0,0 +1,4357 @@
+//! Verifier agent: coordinator connection, task dispatch and verification
+
+use crate::audit_log::{AttestationLog, DEFAULT_ATTESTATION_LOG_FSYNC_EVERY};
+use crate::breaker::BreakerState;
+use crate::canonical_json::HashMode;
+use crate::cbor;
+use crate::clock::{Clock, SystemClock};
+use crate::consistency;
+use crate::decryption::{self, ChunkKey};
+use crate::eigencloud_sdk::{AttestationResponse, EigenCompute, EigenComputeConfig};
+use crate::error::VerifierError;
+use crate::hashing::HashAlgorithm;
+use crate::identity::AgentIdentity;
+use crate::merkle::{self, MerkleProof, MerkleTree};
+use crate::message_auth::{CoordinatorAuth, MessageAuth};
+use crate::metrics::METRICS;
+use crate::msgpack;
+use crate::onchain::{self, SettlementKey};
+use crate::outbound::{OutboundQueue, OverflowPolicy};
+use crate::outbox::Outbox;
+use crate::protocol::{
+    self, AgentMessage, BatchEntry, BatchInclusion, ChunkFailure, ChunkResult, ChunkStatus,
+    ConsistencyCheck, ConsistencyReport, CoordinatorMessage, DataChunk, DataRef, Encoding,
+    FailureReason, Freshness, HealthSummary, Settlement, Severity, SignatureStatus,
+    SourceBreakdown, StatusReport, StatusUpdate, TaskChunk, TaskFailure, TeeAttestation,
+    VerificationResult, VerifyBatch, VerifyTask,
+};
+use crate::quote::{self, ParsedQuote};
+use crate::rate_limit::TokenBucket;
+use crate::recent_results::RecentResults;
+use crate::rules::RuleSet;
+use crate::source_stats::SourceStats;
+use crate::tee_backend::TeeBackend;
+use crate::tls::{CoordinatorStream, CoordinatorTls};
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch, Notify, Semaphore};
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+use tokio::time::MissedTickBehavior;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+/// Default number of verification tasks processed concurrently
+const DEFAULT_MAX_CONCURRENT_TASKS: usize = 4;
+
+/// Default number of TEE attestations allowed back to back when
+/// `TASKS_PER_SEC` paces them
+const DEFAULT_TASK_BURST: u32 = 5;
+
+/// Default number of tasks kept waiting for a concurrency slot before new
+/// ones are refused as `busy`
+const DEFAULT_MAX_QUEUED_TASKS: usize = 256;
+
+/// Default number of messages waiting for the coordinator connection
+/// before `OUTBOUND_OVERFLOW` applies
+const DEFAULT_OUTBOUND_QUEUE_CAPACITY: usize = 1024;
+
+/// Default time given to in-flight tasks on shutdown
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 30;
+
+/// Default time from the shutdown signal until the process exits regardless
+const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 45;
+
+/// Default confidence (percent of expected chunks verified) needed for a
+/// `verified` status rather than `partial`
+const DEFAULT_CONFIDENCE_THRESHOLD: u8 = 95;
+
+/// Default confidence below which a result is `failed` rather than `partial`
+const DEFAULT_CONFIDENCE_FLOOR: u8 = 50;
+
+/// Default share of confidence, in percent, given to source agreement
+const DEFAULT_CONSISTENCY_WEIGHT: u8 = 25;
+
+/// Default share of a chunk's weight, in percent, lost by breaking a `warn`
+/// rule
+const DEFAULT_RULE_WARNING_PENALTY: u8 = 50;
+
+/// Default oldest chunk timestamp accepted, in seconds before now
+const DEFAULT_MAX_CHUNK_AGE_SECS: u64 = 3600;
+
+/// Default tolerance for chunk timestamps ahead of the local clock
+const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 30;
+
+/// Default chunk count from which hashing moves to the worker pool
+const DEFAULT_PARALLEL_THRESHOLD: usize = 64;
+
+/// Default limit on a single chunk's serialized `data`
+const DEFAULT_MAX_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Default limit on chunks in one task
+const DEFAULT_MAX_CHUNKS_PER_TASK: usize = 100_000;
+
+/// Default limit on a whole coordinator message
+const DEFAULT_MAX_TASK_BYTES: usize = 32 * 1024 * 1024;
+
+/// Default size limit for chunks fetched by URL
+const DEFAULT_MAX_FETCH_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Default time allowed for one referenced chunk to download
+const DEFAULT_FETCH_TIMEOUT_SECS: u64 = 300;
+
+/// Time allowed to connect to the host of a referenced chunk
+const FETCH_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default number of referenced chunks downloaded at once
+const DEFAULT_MAX_CONCURRENT_FETCHES: usize = 4;
+
+/// Default limit on tasks in one `verify_batch`
+const DEFAULT_MAX_BATCH_TASKS: usize = 64;
+
+/// Default time a task may take from receipt to result
+const DEFAULT_TASK_TIMEOUT_SECS: u64 = 900;
+
+/// Status of a task that ran out of time
+const TIMEOUT_STATUS: &str = "timeout";
+
+/// Status that replaces `verified` when the attestation was simulated
+const SIMULATED_VERIFIED_STATUS: &str = "verified_simulated";
+
+/// Default interval between outbound WebSocket pings
+const DEFAULT_HEARTBEAT_INTERVAL_MS: u64 = 15_000;
+
+/// Default silence after which the coordinator connection is presumed dead
+const DEFAULT_HEARTBEAT_TIMEOUT_MS: u64 = 45_000;
+
+/// Default interval between `status` reports to the coordinator
+const DEFAULT_STATUS_INTERVAL_MS: u64 = 15_000;
+
+/// Default wait for the coordinator to acknowledge a registration
+const DEFAULT_REGISTER_TIMEOUT_MS: u64 = 10_000;
+
+/// Fresh agent IDs tried on one connection attempt when the coordinator
+/// reports ours as a duplicate
+const MAX_AGENT_ID_RENAMES: u32 = 3;
+
+/// Default directory for state kept across restarts
+const DEFAULT_DATA_DIR: &str = "verifier-data";
+
+/// Default age after which an unacknowledged result is no longer replayed
+const DEFAULT_OUTBOX_RETENTION_SECS: u64 = 24 * 60 * 60;
+
+/// Default cap on unacknowledged results kept
+const DEFAULT_OUTBOX_MAX_ENTRIES: usize = 1000;
+
+/// Default number of completed quests whose reports answer repeated tasks
+const DEFAULT_DEDUP_WINDOW: usize = 1024;
+
+/// Default time a completed quest's report answers repeated tasks
+const DEFAULT_DEDUP_TTL_SECS: u64 = 600;
+
+/// Delay before the first reconnect attempt, doubled up to the maximum
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Encoded chunk bytes gathered before each hasher update
+const HASH_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Capabilities every agent advertises on registration; the rest depend on
+/// configuration, see [`VerifierAgent::capabilities`]
+const BASE_CAPABILITIES: [&str; 3] = [
+    "hash_verification",
+    "data_integrity",
+    "signature_verification",
+];
+
+/// Capability a hash-only agent leaves out, so it isn't sent work that
+/// needs a real attestation
+const ATTESTATION_CAPABILITY: &str = "tee_attestation";
+
+/// Capability for `verify_batch`; refused from a coordinator that didn't
+/// accept it
+const BATCH_CAPABILITY: &str = "batch_verification";
+
+/// Capability for MessagePack framing, which is only used if the
+/// coordinator both accepts it and picks it as the encoding
+const MSGPACK_CAPABILITY: &str = "msgpack";
+
+/// Capability for CBOR framing, on the same terms as MessagePack
+const CBOR_CAPABILITY: &str = "cbor";
+
+/// Write half of the coordinator connection
+type WsSink = SplitSink<CoordinatorStream, Message>;
+
+/// Read half of the coordinator connection
+type WsStream = SplitStream<CoordinatorStream>;
+
+/// Whether tasks are attested or only hash-checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerifierMode {
+    /// Every result carries a TEE attestation
+    #[default]
+    Attested,
+    /// Chunks are verified but EigenCloud is never called; results carry a
+    /// placeholder attestation. For local development and CI.
+    HashOnly,
+}
+
+impl VerifierMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerifierMode::Attested => "attested",
+            VerifierMode::HashOnly => "hash-only",
+        }
+    }
+}
+
+impl FromStr for VerifierMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "attested" => Ok(VerifierMode::Attested),
+            "hash-only" | "hash_only" => Ok(VerifierMode::HashOnly),
+            other => Err(format!("unsupported verifier mode: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for VerifierMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How the agent uses its coordinator URLs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoordinatorMode {
+    /// One connection at a time; a dropped connection moves the agent on
+    /// to the next URL, round-robin
+    #[default]
+    Failover,
+    /// A connection to every URL at once. Tasks are only taken from the
+    /// coordinator that has flagged the agent `primary`, and results go to
+    /// whichever coordinator is primary when they are ready.
+    ActiveActive,
+}
+
+impl CoordinatorMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoordinatorMode::Failover => "failover",
+            CoordinatorMode::ActiveActive => "active-active",
+        }
+    }
+}
+
+impl FromStr for CoordinatorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "failover" => Ok(CoordinatorMode::Failover),
+            "active-active" | "active_active" => Ok(CoordinatorMode::ActiveActive),
+            other => Err(format!("unsupported coordinator mode: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for CoordinatorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Agent settings; `from_env` reads them the way the binary does
+#[derive(Debug, Clone)]
+pub struct VerifierConfig {
+    pub agent_id: String,
+    /// Attest results, or only check hashes
+    pub mode: VerifierMode,
+    /// Coordinator WebSocket URLs. The agent starts with the first and, in
+    /// failover mode, moves on to the next whenever a connection fails.
+    pub coordinator_urls: Vec<String>,
+    /// Failover between the URLs, or stay connected to all of them
+    pub coordinator_mode: CoordinatorMode,
+    /// PEM bundle trusted for `wss://` in addition to the system roots
+    pub coordinator_ca_cert: Option<PathBuf>,
+    /// Required SHA-256 fingerprint of the coordinator's certificate
+    pub coordinator_pin_sha256: Option<String>,
+    /// Shared secret presented to the coordinator on connecting
+    pub auth_token: Option<String>,
+    /// Whether outbound messages carry an HMAC under `auth_token`
+    pub message_auth: MessageAuth,
+    /// Upper bound on verifications running at the same time
+    pub max_concurrent_tasks: usize,
+    /// TEE attestations started per second; None leaves them unpaced
+    pub tasks_per_sec: Option<f64>,
+    /// Attestations that may start back to back before pacing kicks in
+    pub task_burst: u32,
+    /// Tasks waiting for a concurrency slot beyond which new ones are
+    /// rejected as `busy`
+    pub max_queued_tasks: usize,
+    /// Messages waiting for the coordinator connection before
+    /// `outbound_overflow` applies
+    pub outbound_queue_capacity: usize,
+    /// Whether a full outbound queue holds results back or drops the oldest
+    pub outbound_overflow: OverflowPolicy,
+    /// Threads used for chunk hashing
+    pub parallelism: usize,
+    /// Tasks with fewer distinct chunks are hashed inline
+    pub parallel_threshold: usize,
+    /// How long shutdown waits for in-flight tasks before aborting them
+    pub drain_timeout: Duration,
+    /// Hard deadline for the whole shutdown sequence
+    pub shutdown_grace: Duration,
+    /// Interval between outbound pings
+    pub heartbeat_interval: Duration,
+    /// Inbound silence after which the connection is torn down
+    pub heartbeat_timeout: Duration,
+    /// Interval between `status` reports of health and task counts
+    pub status_interval: Duration,
+    /// How long a new connection waits for `register_ack` before it is
+    /// dropped
+    pub register_timeout: Duration,
+    /// Confidence needed for `verified`, 0-100; a task's `minConfidence`
+    /// takes precedence
+    pub confidence_threshold: u8,
+    /// Confidence below which the result is `failed`, 0-100; a task's
+    /// `confidenceFloor` takes precedence
+    pub confidence_floor: u8,
+    /// Share of the confidence score, 0-100, given to source agreement in
+    /// tasks with a consistency check; the check's `weight` takes
+    /// precedence
+    pub consistency_weight: u8,
+    /// Share of its weight, 0-100, a verified chunk loses in the confidence
+    /// score by breaking any of the task's `warn` rules
+    pub rule_warning_penalty: u8,
+    /// Chunks timestamped further in the past fail as stale; None disables
+    /// the check
+    pub max_chunk_age: Option<Duration>,
+    /// How far ahead of the local clock a chunk timestamp may be
+    pub max_clock_skew: Duration,
+    /// Chunks whose serialized data is larger fail as `too_large`
+    pub max_chunk_bytes: usize,
+    /// Tasks with more chunks are rejected outright
+    pub max_chunks_per_task: usize,
+    /// Coordinator messages longer than this are rejected unparsed
+    pub max_task_bytes: usize,
+    /// Referenced chunks larger than this fail as `too_large`
+    pub max_fetch_bytes: u64,
+    /// Deadline for downloading one referenced chunk
+    pub fetch_timeout: Duration,
+    /// Referenced chunks downloaded at the same time, across all tasks
+    pub max_concurrent_fetches: usize,
+    /// Batches with more tasks are rejected outright
+    pub max_batch_tasks: usize,
+    /// Time a task may take before it is reported as `timeout`; a task's
+    /// `timeoutSecs` takes precedence. None lifts the limit.
+    pub task_timeout: Option<Duration>,
+    /// Directory for state kept across restarts; an empty path keeps
+    /// everything in memory
+    pub data_dir: PathBuf,
+    /// Directory for unacknowledged results instead of `<data_dir>/outbox`;
+    /// an empty path keeps them in memory only
+    pub pending_dir: Option<PathBuf>,
+    /// Unacknowledged results older than this are dropped, not replayed
+    pub outbox_retention: Duration,
+    /// Most unacknowledged results kept; zero disables the outbox
+    pub outbox_max_entries: usize,
+    /// Completed quests whose reports are resent, instead of verifying
+    /// again, when their task arrives again; zero re-runs every task
+    pub dedup_window: usize,
+    /// How long a completed quest's report is resent for
+    pub dedup_ttl: Duration,
+    /// Hex secp256k1 secret that signs EVM settlements; without it they
+    /// go unsigned
+    pub settlement_key: Option<String>,
+    /// Secret from which the key for encrypted chunks is derived; a task's
+    /// `decryptionKey` takes precedence. Without either, encrypted chunks
+    /// fail as `decrypt_failed`.
+    pub chunk_decryption_key: Option<String>,
+    /// JSON-lines file every signed result is appended to; None keeps no
+    /// audit log
+    pub attestation_log_path: Option<PathBuf>,
+    /// Audit log records written per fsync; zero leaves syncing to the OS
+    pub attestation_log_fsync_every: u32,
+    /// Size at which the audit log is rotated; None lets it grow
+    pub attestation_log_max_bytes: Option<u64>,
+}
+
+impl Default for VerifierConfig {
+    fn default() -> Self {
+        Self {
+            agent_id: "verifier-001".to_string(),
+            mode: VerifierMode::default(),
+            coordinator_urls: vec!["ws://localhost:8080".to_string()],
+            coordinator_mode: CoordinatorMode::default(),
+            coordinator_ca_cert: None,
+            coordinator_pin_sha256: None,
+            auth_token: None,
+            message_auth: MessageAuth::default(),
+            max_concurrent_tasks: DEFAULT_MAX_CONCURRENT_TASKS,
+            tasks_per_sec: None,
+            task_burst: DEFAULT_TASK_BURST,
+            max_queued_tasks: DEFAULT_MAX_QUEUED_TASKS,
+            outbound_queue_capacity: DEFAULT_OUTBOUND_QUEUE_CAPACITY,
+            outbound_overflow: OverflowPolicy::default(),
+            parallelism: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            drain_timeout: Duration::from_secs(DEFAULT_DRAIN_TIMEOUT_SECS),
+            shutdown_grace: Duration::from_secs(DEFAULT_SHUTDOWN_GRACE_SECS),
+            heartbeat_interval: Duration::from_millis(DEFAULT_HEARTBEAT_INTERVAL_MS),
+            heartbeat_timeout: Duration::from_millis(DEFAULT_HEARTBEAT_TIMEOUT_MS),
+            status_interval: Duration::from_millis(DEFAULT_STATUS_INTERVAL_MS),
+            register_timeout: Duration::from_millis(DEFAULT_REGISTER_TIMEOUT_MS),
+            confidence_threshold: DEFAULT_CONFIDENCE_THRESHOLD,
+            confidence_floor: DEFAULT_CONFIDENCE_FLOOR,
+            consistency_weight: DEFAULT_CONSISTENCY_WEIGHT,
+            rule_warning_penalty: DEFAULT_RULE_WARNING_PENALTY,
+            max_chunk_age: Some(Duration::from_secs(DEFAULT_MAX_CHUNK_AGE_SECS)),
+            max_clock_skew: Duration::from_secs(DEFAULT_MAX_CLOCK_SKEW_SECS),
+            max_chunk_bytes: DEFAULT_MAX_CHUNK_BYTES,
+            max_chunks_per_task: DEFAULT_MAX_CHUNKS_PER_TASK,
+            max_task_bytes: DEFAULT_MAX_TASK_BYTES,
+            max_fetch_bytes: DEFAULT_MAX_FETCH_BYTES,
+            fetch_timeout: Duration::from_secs(DEFAULT_FETCH_TIMEOUT_SECS),
+            max_concurrent_fetches: DEFAULT_MAX_CONCURRENT_FETCHES,
+            max_batch_tasks: DEFAULT_MAX_BATCH_TASKS,
+            task_timeout: Some(Duration::from_secs(DEFAULT_TASK_TIMEOUT_SECS)),
+            data_dir: PathBuf::from(DEFAULT_DATA_DIR),
+            pending_dir: None,
+            outbox_retention: Duration::from_secs(DEFAULT_OUTBOX_RETENTION_SECS),
+            outbox_max_entries: DEFAULT_OUTBOX_MAX_ENTRIES,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            dedup_ttl: Duration::from_secs(DEFAULT_DEDUP_TTL_SECS),
+            settlement_key: None,
+            chunk_decryption_key: None,
+            attestation_log_path: None,
+            attestation_log_fsync_every: DEFAULT_ATTESTATION_LOG_FSYNC_EVERY,
+            attestation_log_max_bytes: None,
+        }
+    }
+}
+
+impl VerifierConfig {
+    /// Read settings from the environment, keeping defaults for unset or
+    /// invalid values
+    pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    /// Overlay environment variables onto these settings; unset or invalid
+    /// variables leave the current value in place
+    pub fn with_env(self) -> Self {
+        Self {
+            agent_id: env::var("AGENT_ID").unwrap_or(self.agent_id),
+            mode: env_parse("VERIFIER_MODE").unwrap_or(self.mode),
+            // Comma-separated COORDINATOR_WS_URLS wins over the single
+            // COORDINATOR_WS_URL
+            coordinator_urls: match env::var("COORDINATOR_WS_URLS") {
+                Ok(list) => list
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|url| !url.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                Err(_) => env::var("COORDINATOR_WS_URL")
+                    .map(|url| vec![url])
+                    .unwrap_or(self.coordinator_urls),
+            },
+            coordinator_mode: env_parse("COORDINATOR_MODE").unwrap_or(self.coordinator_mode),
+            // Set but empty clears a value from the config file.
+            // COORDINATOR_CA_FILE and COORDINATOR_CERT_PIN are read when the
+            // older names are unset.
+            coordinator_ca_cert: match env::var("COORDINATOR_CA_CERT")
+                .or_else(|_| env::var("COORDINATOR_CA_FILE"))
+            {
+                Ok(path) if path.is_empty() => None,
+                Ok(path) => Some(PathBuf::from(path)),
+                Err(_) => self.coordinator_ca_cert,
+            },
+            coordinator_pin_sha256: match env::var("COORDINATOR_PIN_SHA256")
+                .or_else(|_| env::var("COORDINATOR_CERT_PIN"))
+            {
+                Ok(pin) if pin.is_empty() => None,
+                Ok(pin) => Some(pin),
+                Err(_) => self.coordinator_pin_sha256,
+            },
+            auth_token: env::var("AGENT_AUTH_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty())
+                .or(self.auth_token),
+            message_auth: env_parse("MESSAGE_AUTH").unwrap_or(self.message_auth),
+            max_concurrent_tasks: env_positive("MAX_CONCURRENT_TASKS")
+                .unwrap_or(self.max_concurrent_tasks),
+            // Zero lifts the limit
+            tasks_per_sec: match env_parse::<f64>("TASKS_PER_SEC") {
+                Some(0.0) => None,
+                Some(rate) if rate > 0.0 && rate.is_finite() => Some(rate),
+                _ => self.tasks_per_sec,
+            },
+            task_burst: env_parse::<u32>("TASK_BURST")
+                .filter(|n| *n > 0)
+                .unwrap_or(self.task_burst),
+            // TASK_QUEUE_CAPACITY is read when MAX_QUEUED_TASKS is unset
+            max_queued_tasks: env_parse("MAX_QUEUED_TASKS")
+                .or_else(|| env_parse("TASK_QUEUE_CAPACITY"))
+                .unwrap_or(self.max_queued_tasks),
+            outbound_queue_capacity: env_positive("OUTBOUND_QUEUE_CAPACITY")
+                .unwrap_or(self.outbound_queue_capacity),
+            outbound_overflow: env_parse("OUTBOUND_OVERFLOW").unwrap_or(self.outbound_overflow),
+            parallelism: env_positive("VERIFIER_PARALLELISM").unwrap_or(self.parallelism),
+            parallel_threshold: env_parse("VERIFIER_PARALLEL_THRESHOLD")
+                .unwrap_or(self.parallel_threshold),
+            drain_timeout: env_parse("SHUTDOWN_DRAIN_TIMEOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(self.drain_timeout),
+            shutdown_grace: env_parse("SHUTDOWN_GRACE_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(self.shutdown_grace),
+            heartbeat_interval: env_duration("HEARTBEAT_INTERVAL_MS", "HEARTBEAT_INTERVAL_SECS")
+                .unwrap_or(self.heartbeat_interval),
+            heartbeat_timeout: env_duration("HEARTBEAT_TIMEOUT_MS", "HEARTBEAT_TIMEOUT_SECS")
+                .unwrap_or(self.heartbeat_timeout),
+            status_interval: env_duration("STATUS_INTERVAL_MS", "STATUS_INTERVAL_SECS")
+                .unwrap_or(self.status_interval),
+            register_timeout: env_duration("REGISTER_TIMEOUT_MS", "REGISTER_TIMEOUT_SECS")
+                .unwrap_or(self.register_timeout),
+            confidence_threshold: env_confidence("VERIFIER_CONFIDENCE_THRESHOLD")
+                .unwrap_or(self.confidence_threshold),
+            confidence_floor: env_confidence("VERIFIER_CONFIDENCE_FLOOR")
+                .unwrap_or(self.confidence_floor),
+            consistency_weight: env_confidence("CONSISTENCY_WEIGHT")
+                .unwrap_or(self.consistency_weight),
+            rule_warning_penalty: env_confidence("RULE_WARNING_PENALTY")
+                .unwrap_or(self.rule_warning_penalty),
+            // Zero turns the age check off
+            max_chunk_age: match env_parse::<u64>("MAX_CHUNK_AGE_SECS") {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => self.max_chunk_age,
+            },
+            max_clock_skew: env_parse("MAX_CLOCK_SKEW_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(self.max_clock_skew),
+            max_chunk_bytes: env_positive("MAX_CHUNK_BYTES").unwrap_or(self.max_chunk_bytes),
+            max_chunks_per_task: env_positive("MAX_CHUNKS_PER_TASK")
+                .unwrap_or(self.max_chunks_per_task),
+            max_task_bytes: env_positive("MAX_TASK_BYTES").unwrap_or(self.max_task_bytes),
+            max_fetch_bytes: env_parse::<u64>("MAX_FETCH_BYTES")
+                .filter(|n| *n > 0)
+                .unwrap_or(self.max_fetch_bytes),
+            fetch_timeout: env_parse::<u64>("FETCH_TIMEOUT_SECS")
+                .filter(|n| *n > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(self.fetch_timeout),
+            max_concurrent_fetches: env_positive("MAX_CONCURRENT_FETCHES")
+                .unwrap_or(self.max_concurrent_fetches),
+            max_batch_tasks: env_positive("MAX_BATCH_TASKS").unwrap_or(self.max_batch_tasks),
+            // Zero lifts the limit; TASK_TIMEOUT_MS wins over the seconds
+            task_timeout: match env_parse::<u64>("TASK_TIMEOUT_MS")
+                .map(Duration::from_millis)
+                .or_else(|| env_parse::<u64>("TASK_TIMEOUT_SECS").map(Duration::from_secs))
+            {
+                Some(timeout) if timeout.is_zero() => None,
+                Some(timeout) => Some(timeout),
+                None => self.task_timeout,
+            },
+            data_dir: env::var("VERIFIER_DATA_DIR")
+                .map(PathBuf::from)
+                .unwrap_or(self.data_dir),
+            // Set but empty keeps results in memory only
+            pending_dir: env::var("PENDING_DIR")
+                .map(PathBuf::from)
+                .ok()
+                .or(self.pending_dir),
+            outbox_retention: env_parse("OUTBOX_RETENTION_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(self.outbox_retention),
+            outbox_max_entries: env_parse("OUTBOX_MAX_ENTRIES")
+                .unwrap_or(self.outbox_max_entries),
+            dedup_window: env_parse("DEDUP_WINDOW").unwrap_or(self.dedup_window),
+            dedup_ttl: env_parse("DEDUP_TTL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(self.dedup_ttl),
+            settlement_key: env::var("SETTLEMENT_KEY")
+                .ok()
+                .filter(|key| !key.is_empty())
+                .or(self.settlement_key),
+            chunk_decryption_key: env::var("CHUNK_DECRYPTION_KEY")
+                .ok()
+                .filter(|key| !key.is_empty())
+                .or(self.chunk_decryption_key),
+            // Set but empty turns the audit log off
+            attestation_log_path: match env::var("ATTESTATION_LOG_PATH") {
+                Ok(path) if path.is_empty() => None,
+                Ok(path) => Some(PathBuf::from(path)),
+                Err(_) => self.attestation_log_path,
+            },
+            attestation_log_fsync_every: env_parse("ATTESTATION_LOG_FSYNC_EVERY")
+                .unwrap_or(self.attestation_log_fsync_every),
+            // Zero turns rotation off
+            attestation_log_max_bytes: match env_parse::<u64>("ATTESTATION_LOG_MAX_BYTES") {
+                Some(0) => None,
+                Some(bytes) => Some(bytes),
+                None => self.attestation_log_max_bytes,
+            },
+        }
+    }
+}
+
+/// Step-by-step construction of a [`VerifierAgent`]
+///
+/// Anything not set explicitly is read from the environment by `build`,
+/// exactly as `VerifierAgent::from_env` would, so an embedding binary only
+/// has to name what it wants to pin down.
+#[derive(Default)]
+pub struct VerifierAgentBuilder {
+    config: Option<VerifierConfig>,
+    agent_id: Option<String>,
+    coordinator_url: Option<String>,
+    confidence_threshold: Option<u8>,
+    max_concurrent_tasks: Option<usize>,
+    eigen_compute: Option<EigenCompute>,
+    tee_backend: Option<Box<dyn TeeBackend>>,
+    identity: Option<AgentIdentity>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl VerifierAgentBuilder {
+    /// Base settings used instead of `VerifierConfig::from_env`; the
+    /// individual setters still take precedence
+    pub fn config(mut self, config: VerifierConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn agent_id(mut self, agent_id: impl Into<String>) -> Self {
+        self.agent_id = Some(agent_id.into());
+        self
+    }
+
+    /// Single coordinator WebSocket URL, replacing any failover list
+    pub fn coordinator_url(mut self, url: impl Into<String>) -> Self {
+        self.coordinator_url = Some(url.into());
+        self
+    }
+
+    /// Confidence needed for `verified`, 0-100
+    pub fn confidence_threshold(mut self, threshold: u8) -> Self {
+        self.confidence_threshold = Some(threshold);
+        self
+    }
+
+    pub fn max_concurrent_tasks(mut self, max: usize) -> Self {
+        self.max_concurrent_tasks = Some(max);
+        self
+    }
+
+    /// EigenCloud client, instead of `EigenCompute::from_env`
+    pub fn eigen_compute(mut self, eigen_compute: EigenCompute) -> Self {
+        self.eigen_compute = Some(eigen_compute);
+        self
+    }
+
+    /// Another TEE provider, used instead of any EigenCloud client
+    pub fn tee_backend(mut self, tee_backend: Box<dyn TeeBackend>) -> Self {
+        self.tee_backend = Some(tee_backend);
+        self
+    }
+
+    /// Signing key, instead of the one at `AGENT_KEY_PATH`
+    pub fn identity(mut self, identity: AgentIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Time source for the agent and its EigenCloud client, instead of the
+    /// system clock
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Fails if the identity has to be loaded and can't be, the
+    /// coordinator or TEE TLS settings are unusable, or the attestation log
+    /// can't be opened
+    pub fn build(self) -> Result<VerifierAgent, VerifierError> {
+        let mut config = self.config.unwrap_or_else(VerifierConfig::from_env);
+        if let Some(agent_id) = self.agent_id {
+            config.agent_id = agent_id;
+        }
+        if let Some(url) = self.coordinator_url {
+            config.coordinator_urls = vec![url];
+        }
+        if let Some(threshold) = self.confidence_threshold {
+            config.confidence_threshold = threshold;
+        }
+        if let Some(max) = self.max_concurrent_tasks {
+            config.max_concurrent_tasks = max;
+        }
+
+        let tls = CoordinatorTls::new(
+            config.coordinator_ca_cert.as_deref(),
+            config.coordinator_pin_sha256.as_deref(),
+        )?;
+        if config.coordinator_urls.is_empty() {
+            return Err(VerifierError::InvalidConfig(
+                "COORDINATOR_WS_URLS names no coordinator".into(),
+            ));
+        }
+        if let Some(url) = config
+            .coordinator_urls
+            .iter()
+            .find(|url| tls.is_customised() && !url.starts_with("wss://"))
+        {
+            return Err(VerifierError::InvalidConfig(format!(
+                "COORDINATOR_CA_CERT and COORDINATOR_PIN_SHA256 need a wss:// coordinator URL, got {}",
+                url
+            )));
+        }
+        let auth = CoordinatorAuth::new(config.auth_token.clone(), config.message_auth)?;
+        let tee: Box<dyn TeeBackend> = match (self.tee_backend, self.eigen_compute) {
+            (Some(tee_backend), _) => tee_backend,
+            (None, eigen_compute) => {
+                let eigen_compute = match eigen_compute {
+                    Some(eigen_compute) => eigen_compute,
+                    None => EigenCompute::try_new(EigenComputeConfig::from_env())?,
+                };
+                // A client passed in keeps its own clock unless one is set here
+                match &self.clock {
+                    Some(clock) => Box::new(eigen_compute.with_clock(Arc::clone(clock))),
+                    None => Box::new(eigen_compute),
+                }
+            }
+        };
+        let identity = match self.identity {
+            Some(identity) => identity,
+            None => AgentIdentity::from_env()?,
+        };
+        let settlement_key = config
+            .settlement_key
+            .as_deref()
+            .map(|key| {
+                SettlementKey::from_hex(key).ok_or_else(|| {
+                    VerifierError::InvalidConfig(
+                        "SETTLEMENT_KEY is not a hex-encoded secp256k1 secret key".into(),
+                    )
+                })
+            })
+            .transpose()?;
+        let attestation_log = config
+            .attestation_log_path
+            .clone()
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| {
+                AttestationLog::open(
+                    path,
+                    config.attestation_log_fsync_every,
+                    config.attestation_log_max_bytes,
+                )
+            })
+            .transpose()?;
+        let mut agent = VerifierAgent::from_parts(
+            config,
+            tee,
+            identity,
+            tls,
+            auth,
+            settlement_key,
+            attestation_log,
+        );
+        if let Some(clock) = self.clock {
+            agent.clock = clock;
+        }
+        Ok(agent)
+    }
+}
+
+/// Verifier Agent implementation
+pub struct VerifierAgent {
+    /// Replaced with a fresh one if the coordinator reports it taken
+    agent_id: RwLock<String>,
+    /// ID from the configuration, which fresh ones are derived from
+    configured_agent_id: String,
+    /// Hash-only agents never call EigenCloud
+    mode: VerifierMode,
+    /// Tried round-robin in failover mode, all connected in active-active
+    coordinator_urls: Vec<String>,
+    coordinator_mode: CoordinatorMode,
+    /// Custom CA and certificate pin for `wss://`
+    coordinator_tls: CoordinatorTls,
+    /// Token and MACs the coordinator authenticates the agent by
+    coordinator_auth: CoordinatorAuth,
+    /// Provider attestations come from
+    tee: Box<dyn TeeBackend>,
+    /// Key that signs results sent to the coordinator
+    identity: AgentIdentity,
+    /// Upper bound on verifications running at the same time
+    max_concurrent_tasks: usize,
+    /// Paces TEE attestation calls when `TASKS_PER_SEC` is set
+    attestation_limiter: Option<TokenBucket>,
+    /// Tasks allowed to wait for a concurrency slot
+    max_queued_tasks: usize,
+    outbound_queue_capacity: usize,
+    outbound_overflow: OverflowPolicy,
+    /// Worker pool for CPU-bound chunk hashing
+    hash_pool: rayon::ThreadPool,
+    /// Tasks with fewer distinct chunks skip the pool
+    parallel_threshold: usize,
+    /// How long shutdown waits for in-flight tasks before aborting them
+    drain_timeout: Duration,
+    /// Hard deadline for the whole shutdown sequence
+    shutdown_grace: Duration,
+    /// Interval between outbound pings
+    heartbeat_interval: Duration,
+    /// Inbound silence after which the connection is torn down
+    heartbeat_timeout: Duration,
+    /// Interval between `status` reports
+    status_interval: Duration,
+    /// Wait for `register_ack` on a new connection
+    register_timeout: Duration,
+    /// Confidence needed for `verified` when the task doesn't say
+    confidence_threshold: u8,
+    /// Confidence below which results are `failed` when the task doesn't say
+    confidence_floor: u8,
+    /// Share of confidence given to source agreement when the check doesn't
+    /// say
+    consistency_weight: u8,
+    /// Share of its weight a verified chunk loses by breaking a `warn` rule
+    rule_warning_penalty: u8,
+    /// Oldest acceptable chunk timestamp relative to now, if limited
+    max_chunk_age: Option<Duration>,
+    /// Tolerance for chunk timestamps ahead of the local clock
+    max_clock_skew: Duration,
+    /// Size limits applied before any hashing
+    max_chunk_bytes: usize,
+    max_chunks_per_task: usize,
+    max_task_bytes: usize,
+    /// Client for chunks referenced by URL
+    http: reqwest::Client,
+    max_fetch_bytes: u64,
+    fetch_timeout: Duration,
+    /// Bounds downloads of referenced chunks across all tasks
+    fetch_slots: Semaphore,
+    max_batch_tasks: usize,
+    /// Default limit on a task's time from receipt to result
+    task_timeout: Option<Duration>,
+    /// Verification tasks not yet reported, keyed by dispatch sequence so a
+    /// resent quest doesn't clobber the original's entry
+    in_flight: Mutex<HashMap<u64, InFlightTask>>,
+    next_task_seq: AtomicU64,
+    /// Reports kept until the coordinator acknowledges them
+    outbox: Outbox,
+    /// Reports of recently completed quests, resent for repeated tasks
+    recent_results: RecentResults,
+    /// Verified and failed chunk counts per source
+    source_stats: SourceStats,
+    /// Signs EVM settlements when set
+    settlement_key: Option<SettlementKey>,
+    /// Decrypts encrypted chunks of tasks that bring no key of their own
+    chunk_key: Option<ChunkKey>,
+    /// Keeps every signed result for audit when set
+    attestation_log: Option<AttestationLog>,
+    /// Signalled by [`VerifierAgent::shutdown`] and the coordinator's
+    /// `shutdown` message
+    shutdown_requested: Notify,
+    started_at: Instant,
+    /// Time for attestations, registrations and freshness checks
+    clock: Arc<dyn Clock>,
+}
+
+impl VerifierAgent {
+    /// Agent with fully explicit settings; equivalent to a builder given
+    /// every part. Panics if the coordinator TLS or auth settings or the
+    /// settlement key are unusable.
+    pub fn new(config: VerifierConfig, eigen_compute: EigenCompute, identity: AgentIdentity) -> Self {
+        Self::builder()
+            .config(config)
+            .eigen_compute(eigen_compute)
+            .identity(identity)
+            .build()
+            .expect("invalid coordinator TLS or auth settings or settlement key")
+    }
+
+    /// Builder whose unset fields fall back to the environment
+    pub fn builder() -> VerifierAgentBuilder {
+        VerifierAgentBuilder::default()
+    }
+
+    fn from_parts(
+        config: VerifierConfig,
+        tee: Box<dyn TeeBackend>,
+        identity: AgentIdentity,
+        coordinator_tls: CoordinatorTls,
+        coordinator_auth: CoordinatorAuth,
+        settlement_key: Option<SettlementKey>,
+        attestation_log: Option<AttestationLog>,
+    ) -> Self {
+        if let Some(key) = &settlement_key {
+            info!(signer = %key.address(), "Signing EVM settlements");
+        }
+        if let Some(rate) = config.tasks_per_sec {
+            info!(
+                tasks_per_sec = rate,
+                burst = config.task_burst,
+                "Pacing TEE attestations"
+            );
+        }
+        let hash_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.parallelism.max(1))
+            .thread_name(|i| format!("verifier-hash-{}", i))
+            .build()
+            .expect("failed to build hashing thread pool");
+        // An unreachable host fails within the connect timeout instead of
+        // holding a fetch slot for the whole download time
+        let http = reqwest::Client::builder()
+            .connect_timeout(FETCH_CONNECT_TIMEOUT.min(config.fetch_timeout))
+            .timeout(config.fetch_timeout)
+            .build()
+            .expect("failed to build HTTP client for referenced chunks");
+
+        Self {
+            agent_id: RwLock::new(config.agent_id.clone()),
+            configured_agent_id: config.agent_id,
+            mode: config.mode,
+            coordinator_urls: config.coordinator_urls,
+            coordinator_mode: config.coordinator_mode,
+            coordinator_tls,
+            coordinator_auth,
+            tee,
+            identity,
+            max_concurrent_tasks: config.max_concurrent_tasks.max(1),
+            attestation_limiter: config
+                .tasks_per_sec
+                .map(|rate| TokenBucket::new(rate, config.task_burst)),
+            max_queued_tasks: config.max_queued_tasks,
+            outbound_queue_capacity: config.outbound_queue_capacity,
+            outbound_overflow: config.outbound_overflow,
+            hash_pool,
+            parallel_threshold: config.parallel_threshold,
+            drain_timeout: config.drain_timeout,
+            shutdown_grace: config.shutdown_grace,
+            heartbeat_interval: config.heartbeat_interval,
+            heartbeat_timeout: config.heartbeat_timeout,
+            // A zero period would make the timer panic
+            status_interval: config.status_interval.max(Duration::from_millis(1)),
+            register_timeout: config.register_timeout,
+            confidence_threshold: config.confidence_threshold.min(100),
+            confidence_floor: config.confidence_floor.min(100),
+            consistency_weight: config.consistency_weight.min(100),
+            rule_warning_penalty: config.rule_warning_penalty.min(100),
+            max_chunk_age: config.max_chunk_age,
+            max_clock_skew: config.max_clock_skew,
+            max_chunk_bytes: config.max_chunk_bytes,
+            max_chunks_per_task: config.max_chunks_per_task,
+            max_task_bytes: config.max_task_bytes,
+            http,
+            max_fetch_bytes: config.max_fetch_bytes,
+            fetch_timeout: config.fetch_timeout,
+            fetch_slots: Semaphore::new(config.max_concurrent_fetches.max(1)),
+            max_batch_tasks: config.max_batch_tasks,
+            task_timeout: config.task_timeout,
+            in_flight: Mutex::new(HashMap::new()),
+            next_task_seq: AtomicU64::new(0),
+            outbox: Outbox::new(
+                match config.pending_dir {
+                    Some(dir) if dir.as_os_str().is_empty() => None,
+                    Some(dir) => Some(dir),
+                    None if config.data_dir.as_os_str().is_empty() => None,
+                    None => Some(config.data_dir.join("outbox")),
+                },
+                config.outbox_retention,
+                config.outbox_max_entries,
+            ),
+            recent_results: RecentResults::new(config.dedup_window, config.dedup_ttl),
+            source_stats: SourceStats::new(
+                Some(config.data_dir.join("source_stats.json"))
+                    .filter(|_| !config.data_dir.as_os_str().is_empty()),
+            ),
+            settlement_key,
+            chunk_key: config.chunk_decryption_key.as_deref().map(ChunkKey::derive),
+            attestation_log,
+            shutdown_requested: Notify::new(),
+            started_at: Instant::now(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Agent configured entirely from the environment, as the binary runs it
+    pub fn from_env() -> Result<Self, VerifierError> {
+        Self::builder().build()
+    }
+
+    /// TEE provider used for attestations
+    pub fn tee(&self) -> &dyn TeeBackend {
+        self.tee.as_ref()
+    }
+
+    /// Whether results are attested or only hash-checked
+    pub fn mode(&self) -> VerifierMode {
+        self.mode
+    }
+
+    /// Hash a chunk's data with its declared algorithm. The encoding is
+    /// streamed into the hasher, so a large chunk is never held twice.
+    fn compute_hash(
+        data: &Value,
+        mode: HashMode,
+        algorithm: HashAlgorithm,
+    ) -> Result<String, String> {
+        // Batches the encoder's many small writes into updates large
+        // enough for the hasher's SIMD paths
+        let mut writer = BufWriter::with_capacity(HASH_BUFFER_BYTES, algorithm.hasher());
+        mode.encode_to(data, &mut writer)?;
+        let hasher = writer.into_inner().map_err(|e| e.to_string())?;
+        Ok(hasher.finalize_hex())
+    }
+
+    /// Stream a referenced chunk through `algorithm`, enforcing the size
+    /// limit, the declared size and the timeout. The data is only held in
+    /// memory, and returned, when `keep` is set for a signature check.
+    async fn fetch_digest(
+        &self,
+        data_ref: &DataRef,
+        algorithm: HashAlgorithm,
+        keep: bool,
+    ) -> Result<(String, Option<Vec<u8>>), (FailureReason, String)> {
+        let max = self.max_fetch_bytes;
+        let too_large = |size: u64| {
+            (FailureReason::TooLarge, format!("data is {} bytes (max {})", size, max))
+        };
+        if let Some(size) = data_ref.size.filter(|&size| size > max) {
+            return Err(too_large(size));
+        }
+        let fetch_failed = |e: reqwest::Error| (FailureReason::FetchFailed, e.to_string());
+
+        let _slot = self.fetch_slots.acquire().await.expect("fetch semaphore is never closed");
+        let download = async {
+            let mut response = self
+                .http
+                .get(&data_ref.url)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(fetch_failed)?;
+            if let Some(length) = response.content_length().filter(|&length| length > max) {
+                return Err(too_large(length));
+            }
+
+            let mut hasher = algorithm.hasher();
+            let mut kept = keep.then(Vec::new);
+            let mut received = 0u64;
+            while let Some(bytes) = response.chunk().await.map_err(fetch_failed)? {
+                received += bytes.len() as u64;
+                if received > max {
+                    return Err((
+                        FailureReason::TooLarge,
+                        format!("data exceeds {} bytes", max),
+                    ));
+                }
+                hasher.update(&bytes);
+                if let Some(kept) = &mut kept {
+                    kept.extend_from_slice(&bytes);
+                }
+            }
+            if let Some(size) = data_ref.size.filter(|&size| size != received) {
+                return Err((
+                    FailureReason::FetchFailed,
+                    format!("received {} bytes, expected {}", received, size),
+                ));
+            }
+            // The digest of nothing is public, so it proves nothing
+            if received == 0 {
+                return Err((
+                    FailureReason::MalformedData,
+                    "referenced data is empty".to_string(),
+                ));
+            }
+            Ok((hasher.finalize_hex(), kept))
+        };
+
+        match tokio::time::timeout(self.fetch_timeout, download).await {
+            Ok(result) => result,
+            Err(_) => Err((
+                FailureReason::FetchFailed,
+                format!("download took longer than {}s", self.fetch_timeout.as_secs()),
+            )),
+        }
+    }
+
+    /// Digest and signature check of every URL-referenced chunk among
+    /// `unique`, keyed by index. Chunks that would fail anyway are not
+    /// downloaded.
+    async fn fetch_referenced(
+        &self,
+        unique: &[(&TaskChunk, usize)],
+        rules: &TaskRules,
+    ) -> HashMap<usize, Fetched> {
+        let fetches = unique.iter().enumerate().filter_map(|(i, (entry, _))| {
+            let TaskChunk::Valid(chunk) = entry else { return None };
+            let data_ref = chunk.data_ref.as_ref()?;
+            if chunk.encryption.is_some() {
+                return None;
+            }
+            Some(async move {
+                let downloaded = match chunk_algorithm(chunk, rules.hash_algo) {
+                    Err(e) => Err((FailureReason::UnsupportedAlgorithm, e)),
+                    Ok(algorithm) => match rules.freshness_violation(chunk.timestamp) {
+                        Some(violation) => Err(violation),
+                        None => {
+                            debug!(source = %chunk.source, url = %data_ref.url, "Fetching chunk");
+                            let signed = chunk.signature.is_some();
+                            self.fetch_digest(data_ref, algorithm, signed).await
+                        }
+                    },
+                };
+                let fetched = match downloaded {
+                    Ok((digest, bytes)) => Fetched {
+                        digest: Ok(digest),
+                        signature: rules.signature_check(chunk, || bytes),
+                    },
+                    Err(failure) => Fetched {
+                        digest: Err(failure),
+                        signature: rules.signature_check(chunk, || None::<Vec<u8>>),
+                    },
+                };
+                (i, fetched)
+            })
+        });
+        futures_util::future::join_all(fetches).await.into_iter().collect()
+    }
+
+    /// Freshness window for a task's chunks: the agent's, with the task's
+    /// overrides applied, or none if the task opts out
+    fn freshness_window(&self, task: &VerifyTask) -> Option<FreshnessWindow> {
+        if task.freshness == Freshness::Ignore {
+            return None;
+        }
+        Some(FreshnessWindow {
+            // Zero turns the age check off, as with MAX_CHUNK_AGE_SECS
+            max_age: match task.max_chunk_age_secs {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => self.max_chunk_age,
+            },
+            max_skew: task
+                .max_clock_skew_secs
+                .map(Duration::from_secs)
+                .unwrap_or(self.max_clock_skew),
+        })
+    }
+
+    /// Check a single chunk against its own claim, the freshness window and
+    /// the expected set. `fetched` is the outcome of downloading a
+    /// referenced chunk, which replaces hashing its inline data. Encrypted
+    /// data is hashed as decrypted, and the plaintext is zeroed on return;
+    /// it never reaches the result or the logs.
+    fn check_chunk(chunk: &DataChunk, rules: &TaskRules, fetched: Option<&Fetched>) -> ChunkResult {
+        let size = json_len(&chunk.data);
+        let mut computed_hash = None;
+        let algorithm = chunk_algorithm(chunk, rules.hash_algo);
+        // Decrypted once for both the hash and the signature check
+        let plaintext = chunk
+            .encryption
+            .as_ref()
+            .filter(|_| size <= rules.max_chunk_bytes)
+            .map(|encryption| {
+                let key = rules
+                    .decryption_key
+                    .as_ref()
+                    .ok_or_else(|| "no decryption key for encrypted chunk".to_string())?;
+                if chunk.data_ref.is_some() {
+                    return Err("referenced data cannot be encrypted".to_string());
+                }
+                decryption::decrypt(encryption, &chunk.data, key)
+            });
+        let failure = match &algorithm {
+            _ if size > rules.max_chunk_bytes => Some((
+                FailureReason::TooLarge,
+                format!("data is {} bytes (max {})", size, rules.max_chunk_bytes),
+            )),
+            Err(e) => Some((FailureReason::UnsupportedAlgorithm, e.clone())),
+            Ok(algorithm) => match match (&plaintext, fetched) {
+                (Some(Err(e)), _) => Err((FailureReason::DecryptFailed, e.clone())),
+                (Some(Ok(plaintext)), _) => Ok(algorithm.digest_hex(plaintext)),
+                (None, Some(fetched)) => fetched.digest.clone(),
+                (None, None) => Self::compute_hash(&chunk.data, rules.mode, *algorithm)
+                    .map_err(|e| (FailureReason::MalformedData, e)),
+            } {
+                Err(failure) => Some(failure),
+                Ok(computed) if computed != chunk.hash => {
+                    computed_hash = Some(computed);
+                    Some((
+                        FailureReason::HashMismatch,
+                        format!("data does not match claimed {} hash", algorithm),
+                    ))
+                }
+                Ok(_) => rules.freshness_violation(chunk.timestamp).or_else(|| {
+                    (!rules.expected.contains(chunk.hash.as_str())).then(|| {
+                        (FailureReason::MissingExpectedHash, "hash not in expectedHashes".to_string())
+                    })
+                }),
+            },
+        };
+        let status = match &failure {
+            None => ChunkStatus::Verified,
+            Some((FailureReason::MissingExpectedHash, _)) => ChunkStatus::Unexpected,
+            Some(_) => ChunkStatus::Failed,
+        };
+        let (reason, detail) = failure.unzip();
+        // Checked over its own encoding of the data, so a chunk is never
+        // held in memory for the signature unless it is signed
+        let signature = match (&plaintext, fetched) {
+            (Some(plaintext), _) => {
+                rules.signature_check(chunk, || plaintext.as_ref().ok().map(|p| p.as_slice()))
+            }
+            (None, Some(fetched)) => fetched.signature.clone(),
+            (None, None) => rules.signature_check(chunk, || {
+                (size <= rules.max_chunk_bytes)
+                    .then(|| rules.mode.encode(&chunk.data).ok())
+                    .flatten()
+            }),
+        };
+        let (signature_status, signature_detail) = signature.unzip();
+
+        ChunkResult {
+            hash: chunk.hash.clone(),
+            source: chunk.source.clone(),
+            algorithm: match algorithm {
+                Ok(algorithm) => algorithm.to_string(),
+                Err(_) => chunk.algorithm.clone().unwrap_or_default(),
+            },
+            status,
+            computed_hash,
+            reason,
+            detail,
+            duplicates: 0,
+            signature_status,
+            signature_detail: signature_detail.flatten(),
+            consistency: None,
+            violations: Vec::new(),
+        }
+    }
+
+    /// Check one entry of a task's `data`, or `None` once `deadline` has
+    /// passed. A chunk must hash to its own claim, be recent, AND be one
+    /// the coordinator expects, otherwise a producer could simply vouch for
+    /// itself or replay old data.
+    fn check_entry(
+        entry: &TaskChunk,
+        duplicates: usize,
+        rules: &TaskRules,
+        fetched: Option<&Fetched>,
+        deadline: Option<Instant>,
+    ) -> Option<ChunkResult> {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None;
+        }
+        Some(match entry {
+            TaskChunk::Valid(chunk) => ChunkResult {
+                duplicates,
+                ..Self::check_chunk(chunk, rules, fetched)
+            },
+            TaskChunk::Invalid { source, hash, error } => ChunkResult {
+                hash: hash.clone(),
+                source: source.clone(),
+                algorithm: String::new(),
+                status: ChunkStatus::Failed,
+                computed_hash: None,
+                reason: Some(FailureReason::DeserializeError),
+                detail: Some(error.clone()),
+                duplicates,
+                signature_status: None,
+                signature_detail: None,
+                consistency: None,
+                violations: Vec::new(),
+            },
+        })
+    }
+
+    /// Check and attest one task, exactly as a `verify_task` from the
+    /// coordinator would be, and return the unsigned result. Needs no
+    /// coordinator connection, so other services and tests can call it
+    /// directly.
+    ///
+    /// A task that runs past its deadline is cancelled: downloads and the
+    /// TEE call are dropped and no further chunks are hashed. It comes back
+    /// as a `timeout` result carrying whatever checks had finished.
+    ///
+    /// Must be awaited inside a Tokio runtime with the time and I/O drivers
+    /// enabled, as for the TEE call, downloads and deadlines. Either runtime
+    /// flavor works: large tasks are hashed on the agent's own thread pool
+    /// and awaited, without blocking the runtime's threads.
+    pub async fn verify(&self, task: VerifyTask) -> Result<VerificationResult, VerifierError> {
+        let deadline = self.task_deadline(&task);
+        let checked = match within(deadline, self.check_task(&task, deadline)).await {
+            None | Some(Err(VerifierError::TaskTimeout(_))) => {
+                return Ok(self.timed_out(&task, None));
+            }
+            Some(checked) => checked?,
+        };
+        let attestation = within(
+            deadline,
+            self.attest(
+                &checked.aggregate_hash,
+                &merkle::algorithm_name(checked.algorithm),
+                &checked.verified_chunks,
+                &task.quest_id,
+                task.nonce.as_deref(),
+            ),
+        )
+        .await;
+        let Some(attestation) = attestation else {
+            return Ok(self.timed_out(&task, Some(checked)));
+        };
+        let (attestation, parsed_quote) = attestation?;
+        let data_hash = checked.aggregate_hash.clone();
+        Ok(self.finish_task(&task, checked, attestation, parsed_quote, data_hash, None))
+    }
+
+    /// When a task has to be finished: its `timeoutSecs`, or else the agent
+    /// default, from now, and no later than its `deadline`. `None` means no
+    /// limit.
+    fn task_deadline(&self, task: &VerifyTask) -> Option<Instant> {
+        let now = Instant::now();
+        let timeout = match task.timeout_secs {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => self.task_timeout,
+        };
+        let by_deadline = task.deadline.map(|deadline| {
+            now + Duration::from_secs(deadline.saturating_sub(self.clock.now_unix()))
+        });
+        match (timeout.map(|t| now + t), by_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Check every chunk of a task and commit to the verified ones, without
+    /// attesting anything yet. Hashing runs outside the async runtime, where
+    /// a timeout can't interrupt it, so chunks not started by `deadline` are
+    /// skipped and the task fails with [`VerifierError::TaskTimeout`].
+    async fn check_task(
+        &self,
+        task: &VerifyTask,
+        deadline: Option<Instant>,
+    ) -> Result<CheckedTask, VerifierError> {
+        if task.data.is_empty() {
+            return Err(VerifierError::EmptyTask(
+                "task has no data chunks".to_string(),
+            ));
+        }
+        if task.data.len() > self.max_chunks_per_task {
+            return Err(VerifierError::TaskTooLarge(format!(
+                "{} chunks (max {})",
+                task.data.len(),
+                self.max_chunks_per_task
+            )));
+        }
+
+        let hash_algo = task
+            .hash_algo
+            .as_deref()
+            .map(str::parse::<HashAlgorithm>)
+            .transpose()
+            .map_err(|e| VerifierError::MalformedMessage(format!("hashAlgo: {}", e)))?;
+        if let Some(check) = &task.consistency {
+            consistency::validate(check)
+                .map_err(|e| VerifierError::MalformedMessage(format!("consistency: {}", e)))?;
+        }
+        let data_rules = RuleSet::compile(&task.rules)
+            .map_err(|e| VerifierError::MalformedMessage(format!("rules: {}", e)))?;
+        // The Merkle root goes on chain as a bytes32
+        let tree_algorithm = hash_algo.unwrap_or_default();
+        if task.settlement == Some(Settlement::Evm) && tree_algorithm.output_len() != 32 {
+            return Err(VerifierError::MalformedMessage(format!(
+                "settlement evm needs a 32-byte hashAlgo, not {}",
+                tree_algorithm
+            )));
+        }
+        let rules = Arc::new(TaskRules {
+            mode: task.hash_mode,
+            hash_algo,
+            freshness: self.freshness_window(task),
+            expected: task.expected_hashes.iter().cloned().collect(),
+            source_keys: task.source_keys.clone(),
+            decryption_key: task
+                .decryption_key
+                .clone()
+                .or_else(|| self.chunk_key.clone()),
+            max_chunk_bytes: self.max_chunk_bytes,
+            now: self.clock.now_unix(),
+        });
+
+        let unique = dedup_chunks(&task.data);
+        if unique.len() < task.data.len() {
+            debug!(
+                submitted = task.data.len(),
+                unique = unique.len(),
+                "Dropped duplicate chunks"
+            );
+        }
+        let fetched = self.fetch_referenced(&unique, &rules).await;
+
+        // Reported whether or not the chunks pass, so the coordinator can
+        // judge the recency of what it was sent
+        let timestamps = unique.iter().filter_map(|(entry, _)| match entry {
+            TaskChunk::Valid(chunk) => Some(chunk.timestamp),
+            TaskChunk::Invalid { .. } => None,
+        });
+        let min_chunk_timestamp = timestamps.clone().min();
+        let max_chunk_timestamp = timestamps.max();
+
+        // Hashing is CPU-bound, so large tasks run on the rayon pool;
+        // collecting an indexed parallel iterator keeps results in input
+        // order. Small ones are cheaper to hash than to hand off.
+        let chunk_results: Option<Vec<ChunkResult>> = if unique.len() < self.parallel_threshold {
+            unique
+                .iter()
+                .enumerate()
+                .map(|(i, &(entry, duplicates))| {
+                    Self::check_entry(entry, duplicates, &rules, fetched.get(&i), deadline)
+                })
+                .collect()
+        } else {
+            // The pool gets its own copy of the chunks, and the runtime
+            // awaits the results instead of blocking a worker on them
+            let owned: Vec<(TaskChunk, usize)> = unique
+                .iter()
+                .map(|&(entry, duplicates)| (entry.clone(), duplicates))
+                .collect();
+            let rules = Arc::clone(&rules);
+            let (tx, rx) = oneshot::channel();
+            self.hash_pool.spawn(move || {
+                let results = owned
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, (entry, duplicates))| {
+                        Self::check_entry(entry, *duplicates, &rules, fetched.get(&i), deadline)
+                    })
+                    .collect();
+                let _ = tx.send(results);
+            });
+            // A panic on the pool aborts the process, so it always answers
+            rx.await.expect("hash pool dropped a task")
+        };
+        let mut chunk_results = chunk_results.ok_or_else(|| {
+            VerifierError::TaskTimeout(format!(
+                "deadline passed while hashing {} chunks",
+                unique.len()
+            ))
+        })?;
+        // Before the consistency check, so chunks failing a rule take no
+        // part in it
+        if !data_rules.is_empty() {
+            check_rules(&data_rules, &unique, &mut chunk_results);
+        }
+        let consistency = task
+            .consistency
+            .as_ref()
+            .map(|check| self.check_consistency(check, &unique, &mut chunk_results));
+
+        let mut verified_chunks = Vec::new();
+        let mut failed_chunks = Vec::new();
+        let mut unexpected_chunks = Vec::new();
+
+        for result in &chunk_results {
+            debug!(
+                source = %result.source,
+                hash = %result.hash,
+                algorithm = %result.algorithm,
+                status = ?result.status,
+                reason = ?result.reason,
+                detail = result.detail.as_deref(),
+                signature = ?result.signature_status,
+                "Chunk checked"
+            );
+            match result.status {
+                ChunkStatus::Verified => verified_chunks.push(result.hash.clone()),
+                ChunkStatus::Failed => failed_chunks.push(result.hash.clone()),
+                ChunkStatus::Unexpected => unexpected_chunks.push(result.hash.clone()),
+            }
+        }
+        let failure_details: Vec<ChunkFailure> = chunk_results
+            .iter()
+            .filter_map(|result| {
+                Some(ChunkFailure {
+                    hash: result.hash.clone(),
+                    source: result.source.clone(),
+                    reason: result.reason?,
+                    detail: result.detail.clone().unwrap_or_default(),
+                    rule: result
+                        .violations
+                        .iter()
+                        .find(|violation| violation.severity == Severity::Fail)
+                        .map(|violation| violation.rule),
+                })
+            })
+            .collect();
+
+        METRICS
+            .chunks_verified
+            .fetch_add(verified_chunks.len() as u64, Ordering::Relaxed);
+        METRICS.chunks_failed.fetch_add(
+            (failed_chunks.len() + unexpected_chunks.len()) as u64,
+            Ordering::Relaxed,
+        );
+        self.source_stats.record(&chunk_results);
+
+        // The attested data hash is the Merkle root, so any single chunk can
+        // later be proven part of the attestation
+        let warned_share = f64::from(100 - self.rule_warning_penalty) / 100.0;
+        let (covered_weight, expected_weight) =
+            weigh_coverage(task, &rules.expected, &chunk_results, warned_share);
+        let source_breakdown = source_breakdown(task, &chunk_results);
+        let algorithm = hash_algo.unwrap_or_default();
+        let tree = MerkleTree::from_hashes(algorithm, &verified_chunks);
+        let aggregate_hash = tree.root_hex();
+        let proofs = task.include_proofs.then(|| {
+            let mut seen = HashSet::new();
+            verified_chunks
+                .iter()
+                .filter(|hash| seen.insert(hash.as_str()))
+                .filter_map(|hash| tree.proof(hash))
+                .collect()
+        });
+
+        Ok(CheckedTask {
+            expected_count: rules.expected.len(),
+            covered_weight,
+            expected_weight,
+            source_breakdown,
+            algorithm,
+            aggregate_hash,
+            verified_chunks,
+            failed_chunks,
+            unexpected_chunks,
+            chunk_results,
+            failure_details,
+            proofs,
+            min_chunk_timestamp,
+            max_chunk_timestamp,
+            consistency,
+        })
+    }
+
+    /// Hold the verified chunks among `results` to each other's values as
+    /// `check` asks, flagging each one; `results` line up with `unique`
+    fn check_consistency(
+        &self,
+        check: &ConsistencyCheck,
+        unique: &[(&TaskChunk, usize)],
+        results: &mut [ChunkResult],
+    ) -> ConsistencyReport {
+        let verified: Vec<usize> = (0..results.len())
+            .filter(|&i| results[i].status == ChunkStatus::Verified)
+            .collect();
+        let samples: Vec<_> = verified
+            .iter()
+            .map(|&i| {
+                // Referenced data is raw bytes and encrypted data is not
+                // kept once hashed, so only inline plaintext can be read
+                let data = match unique[i].0 {
+                    TaskChunk::Valid(chunk)
+                        if chunk.data_ref.is_none() && chunk.encryption.is_none() =>
+                    {
+                        Some(&chunk.data)
+                    }
+                    _ => None,
+                };
+                (
+                    results[i].source.as_str(),
+                    consistency::read(data, &check.pointer),
+                )
+            })
+            .collect();
+        let weight = check.weight.unwrap_or(self.consistency_weight);
+        let (findings, report) = consistency::evaluate(check, weight, &samples);
+        for (i, finding) in verified.into_iter().zip(findings) {
+            results[i].consistency = Some(finding);
+        }
+        if report.outliers > 0 || report.unreadable > 0 {
+            info!(
+                pointer = %report.pointer,
+                aggregate = report.aggregate,
+                outliers = report.outliers,
+                unreadable = report.unreadable,
+                "Sources disagree"
+            );
+        }
+        report
+    }
+
+    /// Get a TEE attestation over `data_hash` from EigenCloud, and never
+    /// forward one we can't verify ourselves. It is bound to `nonce`, or to
+    /// a fresh one when the coordinator sent none, so a captured
+    /// attestation can't be replayed. Calls are paced to `TASKS_PER_SEC`
+    /// when it is set. In hash-only mode the result gets a placeholder
+    /// instead and EigenCloud is not called.
+    async fn attest(
+        &self,
+        data_hash: &str,
+        data_hash_algorithm: &str,
+        leaves: &[String],
+        request_id: &str,
+        nonce: Option<&str>,
+    ) -> Result<(AttestationResponse, Option<ParsedQuote>), VerifierError> {
+        if self.mode == VerifierMode::HashOnly {
+            let placeholder = AttestationResponse {
+                quote: quote::PLACEHOLDER_QUOTE.to_string(),
+                tee_type: None,
+                validator_pubkey: String::new(),
+                signature: String::new(),
+                nonce: None,
+                timestamp: None,
+                success: true,
+                error: None,
+                cached: false,
+                simulated: true,
+            };
+            return Ok((placeholder, None));
+        }
+        if let Some(limiter) = &self.attestation_limiter {
+            let waited = limiter.acquire().await;
+            if !waited.is_zero() {
+                debug!(
+                    waited_ms = waited.as_millis() as u64,
+                    "Paced TEE attestation"
+                );
+            }
+        }
+        let nonce = self.tee.challenge_nonce(nonce);
+        let nonce = nonce.as_deref();
+        let attestation = self
+            .tee
+            .execute_verification(data_hash, data_hash_algorithm, leaves, request_id, nonce)
+            .await
+            .and_then(|attestation| {
+                let parsed = self
+                    .tee
+                    .verify_attestation(&attestation, data_hash, nonce)?;
+                Ok((attestation, parsed))
+            });
+        if attestation.is_err() {
+            METRICS.attestation_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        attestation
+    }
+
+    /// Score a checked task and assemble its result around an attestation
+    /// of `data_hash`, which is the task's own aggregate hash unless the
+    /// task was attested as part of a batch
+    fn finish_task(
+        &self,
+        task: &VerifyTask,
+        checked: CheckedTask,
+        attestation: AttestationResponse,
+        parsed_quote: Option<ParsedQuote>,
+        data_hash: String,
+        batch: Option<BatchInclusion>,
+    ) -> VerificationResult {
+        // Confidence is measured against what the coordinator expected,
+        // so missing chunks count against it just like bad ones
+        let covered = checked
+            .verified_chunks
+            .iter()
+            .map(String::as_str)
+            .collect::<HashSet<_>>()
+            .len();
+        let raw_confidence = to_confidence(percent(covered as f64, checked.expected_count as f64));
+        // Without `sourceWeights` every hash weighs the same and this
+        // matches the raw score
+        let coverage = percent(checked.covered_weight, checked.expected_weight);
+        // Agreement between the sources takes its share when asked for
+        let confidence = to_confidence(match &checked.consistency {
+            Some(report) => consistency::blend(coverage, report),
+            None => coverage,
+        });
+
+        let (threshold, floor) = self.thresholds(task);
+        // A simulated attestation never passes for a hardware-backed one
+        let simulated = attestation.simulated || quote::is_simulated(&attestation.quote);
+        let status = if confidence >= threshold && simulated {
+            SIMULATED_VERIFIED_STATUS
+        } else if confidence >= threshold {
+            "verified"
+        } else if confidence >= floor {
+            "partial"
+        } else {
+            "failed"
+        };
+
+        // The attestation only carries a nonce once it has been checked
+        // against the one it was challenged with
+        let replay_protected = attestation.nonce.is_some();
+        let idempotency_key = protocol::idempotency_key(&task.quest_id, &data_hash);
+        let received_at = self.clock.now_unix();
+
+        let attestation = TeeAttestation {
+            simulated,
+            cached: attestation.cached,
+            mrtd: parsed_quote.as_ref().map(|q| q.mrtd.clone()),
+            quote: attestation.quote,
+            // What the quote itself says beats what the container claims
+            tee_type: parsed_quote
+                .map(|q| q.tee_type)
+                .or(attestation.tee_type)
+                .unwrap_or_else(|| self.tee.tee_type()),
+            data_hash,
+            // A batch attests the Blake3 tree of the quests' aggregates
+            data_hash_algorithm: match &batch {
+                Some(_) => merkle::ALGORITHM.to_string(),
+                None => merkle::algorithm_name(checked.algorithm),
+            },
+            timestamp: attestation.timestamp.unwrap_or(received_at),
+            received_at,
+            validator_pubkey: attestation.validator_pubkey,
+            signature: attestation.signature,
+            nonce: attestation.nonce,
+            confidence_score: confidence,
+        };
+        // check_task has made sure the data hash fits a bytes32
+        let onchain = match task.settlement {
+            Some(Settlement::Evm) => {
+                onchain::settle(&task.quest_id, &attestation, self.settlement_key.as_ref())
+                    .map_err(|e| warn!(error = %e, "Failed to encode settlement"))
+                    .ok()
+            }
+            None => None,
+        };
+
+        VerificationResult {
+            quest_id: task.quest_id.clone(),
+            idempotency_key,
+            agent_id: self.agent_id(),
+            status: status.to_string(),
+            min_confidence: threshold,
+            confidence_floor: floor,
+            raw_confidence: Some(raw_confidence),
+            attestation: Some(attestation),
+            replay_protected,
+            verified_chunks: checked.verified_chunks,
+            failed_chunks: checked.failed_chunks,
+            unexpected_chunks: checked.unexpected_chunks,
+            chunk_results: checked.chunk_results,
+            failure_details: checked.failure_details,
+            source_breakdown: checked.source_breakdown,
+            consistency: checked.consistency,
+            proofs: checked.proofs,
+            min_chunk_timestamp: checked.min_chunk_timestamp,
+            max_chunk_timestamp: checked.max_chunk_timestamp,
+            batch,
+            onchain,
+        }
+    }
+
+    /// Result for a task that ran out of time, reporting the chunks that
+    /// were checked before it did, if any. Nothing is attested.
+    fn timed_out(&self, task: &VerifyTask, checked: Option<CheckedTask>) -> VerificationResult {
+        METRICS.tasks_timed_out.fetch_add(1, Ordering::Relaxed);
+        warn!(checked = checked.is_some(), "Task ran out of time");
+
+        let (threshold, floor) = self.thresholds(task);
+        let checked = checked.unwrap_or_default();
+        VerificationResult {
+            quest_id: task.quest_id.clone(),
+            idempotency_key: protocol::idempotency_key(&task.quest_id, TIMEOUT_STATUS),
+            agent_id: self.agent_id(),
+            status: TIMEOUT_STATUS.to_string(),
+            min_confidence: threshold,
+            confidence_floor: floor,
+            raw_confidence: None,
+            attestation: None,
+            replay_protected: false,
+            verified_chunks: checked.verified_chunks,
+            failed_chunks: checked.failed_chunks,
+            unexpected_chunks: checked.unexpected_chunks,
+            chunk_results: checked.chunk_results,
+            failure_details: checked.failure_details,
+            source_breakdown: checked.source_breakdown,
+            consistency: checked.consistency,
+            // Proofs lead to an attested root, and there is none
+            proofs: None,
+            min_chunk_timestamp: checked.min_chunk_timestamp,
+            max_chunk_timestamp: checked.max_chunk_timestamp,
+            batch: None,
+            onchain: None,
+        }
+    }
+
+    /// Confidence needed for `verified` and below which a result is
+    /// `failed`. The task's own bars win over the agent defaults; a floor
+    /// above the threshold would leave no room for `partial`, so it is
+    /// capped.
+    fn thresholds(&self, task: &VerifyTask) -> (u8, u8) {
+        let threshold = task.min_confidence.unwrap_or(self.confidence_threshold);
+        let floor = task
+            .confidence_floor
+            .unwrap_or(self.confidence_floor)
+            .min(threshold);
+        (threshold, floor)
+    }
+
+    /// Serialize a result as a `task_result` signed by this agent, so the
+    /// coordinator can attribute it
+    fn sign_result(&self, result: VerificationResult) -> Result<String, VerifierError> {
+        let mut body = serde_json::to_value(AgentMessage::TaskResult(Box::new(result)))
+            .map_err(|e| VerifierError::SerializationFailed(e.to_string()))?;
+        let signature = self.identity.sign_hex(&protocol::signing_payload(&body));
+        body[protocol::SIGNATURE_FIELD] = json!(signature);
+        Ok(body.to_string())
+    }
+
+    /// What this agent can take on, as configured. Attested agents name
+    /// their TEE hardware too (`tdx_attestation` or `sgx_attestation`)
+    /// unless attestations are simulated; every supported hash algorithm is
+    /// listed by name.
+    fn capabilities(&self) -> Vec<String> {
+        let mut capabilities = Vec::new();
+        if self.mode == VerifierMode::Attested {
+            capabilities.push(ATTESTATION_CAPABILITY.to_string());
+            if !self.tee.is_dev_mode() {
+                let tee = self.tee.tee_type().as_str().to_ascii_lowercase();
+                capabilities.push(format!("{}_attestation", tee));
+            }
+        }
+        capabilities.extend(BASE_CAPABILITIES.iter().map(|c| c.to_string()));
+        capabilities.push(BATCH_CAPABILITY.to_string());
+        capabilities.push(MSGPACK_CAPABILITY.to_string());
+        capabilities.push(CBOR_CAPABILITY.to_string());
+        capabilities.extend(supported_hash_algos());
+        capabilities
+    }
+
+    /// ID the agent is currently registered under
+    fn agent_id(&self) -> String {
+        self.agent_id.read().unwrap().clone()
+    }
+
+    /// Register under a fresh ID derived from the configured one
+    fn rename(&self) -> String {
+        let suffix: [u8; 3] = rand::random();
+        let fresh = format!("{}-{}", self.configured_agent_id, hex::encode(suffix));
+        *self.agent_id.write().unwrap() = fresh.clone();
+        fresh
+    }
+
+    /// Registration message signed with the agent key. Every call picks a
+    /// new nonce and timestamp, so each connection registers afresh.
+    pub fn sign_registration(&self) -> Value {
+        let nonce: [u8; 16] = rand::random();
+        let registration = AgentMessage::Register {
+            role: "verifier".to_string(),
+            agent_id: self.agent_id(),
+            pubkey: self.identity.public_key_hex(),
+            capabilities: self.capabilities(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            supported_hash_algos: supported_hash_algos(),
+            encodings: Encoding::ALL.to_vec(),
+            protocol_version: protocol::PROTOCOL_VERSION,
+            nonce: hex::encode(nonce),
+            timestamp: self.clock.now_unix(),
+            auth_token: self.coordinator_auth.token().map(str::to_string),
+        };
+
+        let mut body = serde_json::to_value(registration).expect("registration is plain JSON");
+        let signature = self.identity.sign_hex(&protocol::signing_payload(&body));
+        body[protocol::SIGNATURE_FIELD] = json!(signature);
+        body
+    }
+
+    /// Handle a raw coordinator message, returning the replies in order; a
+    /// batch gets one per task. A thin adapter over [`Self::verify`] for
+    /// callers that speak the wire protocol.
+    pub async fn handle_task(&self, text: &str) -> Vec<String> {
+        let message = match self.parse_message(text) {
+            Ok(message) => message,
+            Err(reply) => return reply.to_json().ok().into_iter().collect(),
+        };
+        let reports = match message {
+            CoordinatorMessage::VerifyTask(task) => vec![self.run_verify_task(*task).await],
+            CoordinatorMessage::VerifyBatch(batch) => self.run_verify_batch(batch).await,
+            other => {
+                return self
+                    .handle_control(other)
+                    .and_then(|reply| reply.to_json().ok())
+                    .into_iter()
+                    .collect()
+            }
+        };
+        reports
+            .into_iter()
+            .map(|report| self.settle(report))
+            .collect()
+    }
+
+    /// Verify a task and produce its signed result or failure report
+    async fn run_verify_task(&self, task: VerifyTask) -> TaskReport {
+        METRICS.tasks_received.fetch_add(1, Ordering::Relaxed);
+
+        info!(chunks = task.data.len(), "Received verification task");
+
+        let quest_id = task.quest_id.clone();
+        let started = Instant::now();
+        let outcome = self.verify(task).await;
+        METRICS.verification_latency.observe(started.elapsed());
+
+        self.report(&quest_id, outcome)
+    }
+
+    /// Verify the tasks of a batch under one shared attestation, producing
+    /// a signed result or failure report per task, in order. A task that
+    /// fails its checks is reported on its own and left out of the batch
+    /// root.
+    async fn run_verify_batch(&self, batch: VerifyBatch) -> Vec<TaskReport> {
+        METRICS
+            .tasks_received
+            .fetch_add(batch.tasks.len() as u64, Ordering::Relaxed);
+
+        info!(tasks = batch.tasks.len(), "Received verification batch");
+
+        if batch.tasks.len() > self.max_batch_tasks {
+            let e = VerifierError::TaskTooLarge(format!(
+                "batch of {} tasks (max {})",
+                batch.tasks.len(),
+                self.max_batch_tasks
+            ));
+            return batch
+                .tasks
+                .iter()
+                .map(|entry| self.report(entry.quest_id(), Err(e.clone())))
+                .collect();
+        }
+
+        let started = Instant::now();
+        let checks = batch.tasks.iter().map(|entry| {
+            let span = info_span!("task", quest_id = %entry.quest_id());
+            async move {
+                match entry {
+                    BatchEntry::Invalid { quest_id, error } => Err((
+                        quest_id.as_str(),
+                        VerifierError::MalformedMessage(error.clone()),
+                    )),
+                    BatchEntry::Valid(task) if task.nonce.is_some() => Err((
+                        task.quest_id.as_str(),
+                        VerifierError::MalformedMessage(
+                            "tasks in a batch share the batch nonce".to_string(),
+                        ),
+                    )),
+                    BatchEntry::Valid(task) => match self.check_task(task, None).await {
+                        Ok(checked) => Ok((task, checked)),
+                        Err(e) => Err((task.quest_id.as_str(), e)),
+                    },
+                }
+            }
+            .instrument(span)
+        });
+        let checked = futures_util::future::join_all(checks).await;
+
+        // One attestation covers every task that got this far, through the
+        // Merkle root of their aggregate hashes
+        let aggregates: Vec<String> = checked
+            .iter()
+            .filter_map(|outcome| outcome.as_ref().ok())
+            .map(|(_, checked)| checked.aggregate_hash.clone())
+            .collect();
+        let tree = MerkleTree::from_hashes(HashAlgorithm::default(), &aggregates);
+        let root = tree.root_hex();
+        let attested = if aggregates.is_empty() {
+            Err(VerifierError::HashMismatch(
+                "no task in the batch passed its checks".to_string(),
+            ))
+        } else {
+            self.attest(
+                &root,
+                merkle::ALGORITHM,
+                &aggregates,
+                &batch.batch_id,
+                batch.nonce.as_deref(),
+            )
+            .await
+        };
+        METRICS.verification_latency.observe(started.elapsed());
+
+        checked
+            .into_iter()
+            .map(|outcome| match outcome {
+                Err((quest_id, e)) => {
+                    let _span = info_span!("task", quest_id = %quest_id).entered();
+                    self.report(quest_id, Err(e))
+                }
+                Ok((task, checked)) => {
+                    let _span = info_span!("task", quest_id = %task.quest_id).entered();
+                    let outcome = match &attested {
+                        Ok((attestation, parsed_quote)) => {
+                            let inclusion = BatchInclusion {
+                                batch_id: batch.batch_id.clone(),
+                                size: aggregates.len(),
+                                proof: tree
+                                    .proof(&checked.aggregate_hash)
+                                    .expect("every aggregate hash is a leaf of the batch tree"),
+                                aggregate_algorithm: merkle::algorithm_name(checked.algorithm),
+                            };
+                            Ok(self.finish_task(
+                                task,
+                                checked,
+                                attestation.clone(),
+                                parsed_quote.clone(),
+                                root.clone(),
+                                Some(inclusion),
+                            ))
+                        }
+                        Err(e) => Err(e.clone()),
+                    };
+                    self.report(&task.quest_id, outcome)
+                }
+            })
+            .collect()
+    }
+
+    /// Sign a finished verification, or turn its error into a failure
+    /// report
+    fn report(
+        &self,
+        quest_id: &str,
+        outcome: Result<VerificationResult, VerifierError>,
+    ) -> TaskReport {
+        METRICS.tasks_processed.fetch_add(1, Ordering::Relaxed);
+        let outcome = outcome.and_then(|result| {
+            if result.status == "failed" || result.status == TIMEOUT_STATUS {
+                METRICS.tasks_failed.fetch_add(1, Ordering::Relaxed);
+            }
+            info!(
+                verified = result.verified_chunks.len(),
+                failed = result.failed_chunks.len(),
+                unexpected = result.unexpected_chunks.len(),
+                confidence = result.attestation.as_ref().map(|a| a.confidence_score),
+                status = %result.status,
+                "Verification complete"
+            );
+            // A task that ran out of time may well finish if sent again
+            let settled = result.status != TIMEOUT_STATUS;
+            let signed = self.sign_result(result)?;
+            if let Some(log) = &self.attestation_log {
+                log.append(quest_id, &signed);
+            }
+            Ok((signed, settled))
+        });
+
+        let (report, settled) = outcome.unwrap_or_else(|e| {
+            METRICS.tasks_failed.fetch_add(1, Ordering::Relaxed);
+            error!(error = %e, code = e.code(), retryable = e.is_retryable(), "TEE verification failed");
+            // A disallowed measurement is a verdict on the container, not a
+            // fault, so it gets its own status
+            let (status, measurement) = match &e {
+                VerifierError::MeasurementNotAllowed(mrtd) => ("attestation_rejected", Some(mrtd.clone())),
+                _ => ("error", None),
+            };
+            let failure = AgentMessage::TaskFailed(TaskFailure {
+                quest_id: quest_id.to_string(),
+                agent_id: self.agent_id(),
+                status: status.to_string(),
+                error: e.to_string(),
+                code: Some(e.code().to_string()),
+                retryable: Some(e.is_retryable()),
+                measurement,
+            });
+            // Only plain strings and bools, so encoding cannot fail
+            (failure.to_json().unwrap_or_default(), !e.is_retryable())
+        });
+        TaskReport {
+            quest_id: quest_id.to_string(),
+            text: report,
+            settled,
+        }
+    }
+
+    /// Keep a report to answer repeats of its task with, if it settles the
+    /// task, and hand back its text. Only settled outcomes answer a repeated
+    /// task; retryable failures and timeouts run again.
+    fn settle(&self, report: TaskReport) -> String {
+        if report.settled {
+            self.recent_results.insert(&report.quest_id, &report.text);
+        }
+        report.text
+    }
+
+    /// Answer a message that needs no verification work
+    fn handle_control(&self, message: CoordinatorMessage) -> Option<AgentMessage> {
+        match message {
+            // Routed by `dispatch`
+            CoordinatorMessage::VerifyTask(_)
+            | CoordinatorMessage::VerifyBatch(_)
+            | CoordinatorMessage::Registered { .. }
+            | CoordinatorMessage::Primary { .. } => None,
+            // Only meaningful while registering, see `await_ack`
+            CoordinatorMessage::RegisterRejected { reason } => {
+                warn!(reason = %reason, "Ignoring registration rejection on a registered connection");
+                None
+            }
+            CoordinatorMessage::Ping => Some(self.pong()),
+            CoordinatorMessage::StatusRequest => {
+                Some(AgentMessage::StatusResponse(self.status_report()))
+            }
+            CoordinatorMessage::CancelTask { quest_id } => self.cancel_task(quest_id),
+            CoordinatorMessage::ResultAck { quest_id } => {
+                self.outbox.ack(&quest_id);
+                None
+            }
+            CoordinatorMessage::ConfigUpdate { allowed_measurements } => {
+                if let Some(measurements) = allowed_measurements {
+                    self.tee.set_allowed_measurements(&measurements);
+                }
+                None
+            }
+            CoordinatorMessage::Shutdown { reason } => {
+                info!(
+                    reason = reason.as_deref(),
+                    "Coordinator asked the agent to shut down"
+                );
+                self.shutdown();
+                None
+            }
+            CoordinatorMessage::Unknown { raw } => {
+                let message_type = raw.get("type").and_then(Value::as_str).map(str::to_string);
+                warn!(message_type = message_type.as_deref(), "Unsupported message type");
+                let e = VerifierError::UnsupportedMessage(message_type.clone().unwrap_or_default());
+                Some(self.error_reply(&e, message_type))
+            }
+        }
+    }
+
+    /// Abort a queued or running task. Whoever removes the in-flight entry
+    /// reports the task, so a result racing the cancellation is either sent
+    /// in full or replaced by `cancelled`, never both.
+    fn cancel_task(&self, quest_id: String) -> Option<AgentMessage> {
+        let cancelled = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            let before = in_flight.len();
+            in_flight.retain(|_, task| {
+                let matches = task.quest_id == quest_id;
+                if matches && !task.in_batch {
+                    task.abort.abort();
+                }
+                !matches
+            });
+            before - in_flight.len()
+        };
+
+        if cancelled == 0 {
+            debug!(quest_id = %quest_id, "Cancellation for unknown or finished task");
+            return None;
+        }
+        info!(quest_id = %quest_id, "Task cancelled by coordinator");
+        Some(AgentMessage::TaskFailed(TaskFailure {
+            quest_id,
+            agent_id: self.agent_id(),
+            status: "cancelled".to_string(),
+            error: "cancelled by coordinator".to_string(),
+            code: None,
+            retryable: None,
+            measurement: None,
+        }))
+    }
+
+    /// Health fields shared by pongs and status messages
+    fn health_summary(&self) -> HealthSummary {
+        let breaker = self.tee.breaker_state();
+        let queue_depth = self.queue_depth();
+        HealthSummary {
+            agent_id: self.agent_id(),
+            degraded: breaker != BreakerState::Closed,
+            tee_breaker: breaker.as_str().to_string(),
+            in_flight: self.in_flight.lock().unwrap().len(),
+            queue_depth,
+            uptime_secs: self.started_at.elapsed().as_secs(),
+        }
+    }
+
+    /// Periodic `status` report: health and load for the coordinator's
+    /// scheduling, plus the task counters `/metrics` exports
+    fn status_update(&self) -> StatusUpdate {
+        let stats = METRICS.stats();
+        StatusUpdate {
+            health: self.health_summary(),
+            tasks_completed: stats.tasks_processed,
+            tasks_failed: stats.tasks_failed,
+        }
+    }
+
+    /// Ticks every `status_interval`, first one interval from now
+    async fn status_timer(&self) -> tokio::time::Interval {
+        let mut timer = tokio::time::interval(self.status_interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        timer.tick().await;
+        timer
+    }
+
+    /// Tasks received but still waiting for a concurrency slot
+    fn queue_depth(&self) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|task| !task.started.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// Reply to a coordinator `ping`
+    fn pong(&self) -> AgentMessage {
+        AgentMessage::Pong(self.health_summary())
+    }
+
+    /// Reply to a coordinator `status_request`
+    fn status_report(&self) -> StatusReport {
+        let in_flight_quests = {
+            let in_flight = self.in_flight.lock().unwrap();
+            let mut tasks: Vec<_> = in_flight.iter().collect();
+            tasks.sort_by_key(|(seq, _)| **seq);
+            tasks
+                .iter()
+                .map(|(_, task)| task.quest_id.clone())
+                .collect()
+        };
+        StatusReport {
+            health: self.health_summary(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            dev_mode: self.tee.is_dev_mode(),
+            mode: self.mode.as_str().to_string(),
+            in_flight_quests,
+            stats: METRICS.stats(),
+            source_reliability: self.source_stats.snapshot(),
+        }
+    }
+
+    /// Tell the coordinator a message it sent could not be acted on
+    fn error_reply(&self, e: &VerifierError, message_type: Option<String>) -> AgentMessage {
+        AgentMessage::Error {
+            agent_id: self.agent_id(),
+            code: e.code().to_string(),
+            error: e.to_string(),
+            message_type,
+        }
+    }
+
+    /// Route an incoming message. Verification tasks wait for a concurrency
+    /// slot, or are rejected as `busy` when too many already do, or as
+    /// `not_primary` unless `peer` is primary; batches are refused unless
+    /// `peer` accepted them. Everything else is answered straight from the
+    /// read loop, so liveness checks never queue behind a TEE call. Replies
+    /// go to `replies` and task results to `results`, which are the same
+    /// queue outside active-active mode.
+    ///
+    /// `register_ack` and `primary` concern the connection rather than the
+    /// agent, so they are handed back for the caller to act on, with an
+    /// encoding `peer` didn't accept dropped.
+    fn dispatch(
+        self: &Arc<Self>,
+        frame: &Frame,
+        replies: &Arc<OutboundQueue>,
+        results: &Arc<OutboundQueue>,
+        peer: Peer<'_>,
+        task_slots: &Arc<Semaphore>,
+        tasks: &mut JoinSet<()>,
+    ) -> Option<CoordinatorMessage> {
+        let parsed = match frame {
+            Frame::Text(text) => self.parse_message(text),
+            Frame::Binary(bytes) => self.parse_binary(bytes),
+        };
+        let message = match parsed {
+            Ok(message) => message,
+            Err(reply) => {
+                send(replies, &reply);
+                return None;
+            }
+        };
+
+        match message {
+            CoordinatorMessage::VerifyTask(task) if self.is_duplicate(&task.quest_id, results) => {}
+            CoordinatorMessage::VerifyTask(task) => {
+                if let Err(e) = self.admit(1, peer.primary, results, task_slots) {
+                    send(replies, &self.reject_task(task.quest_id, &e));
+                    return None;
+                }
+                let span =
+                    info_span!("task", quest_id = %task.quest_id, agent_id = %self.agent_id());
+                let quest_ids = vec![task.quest_id.clone()];
+                self.spawn_verification(
+                    quest_ids,
+                    span,
+                    results,
+                    task_slots,
+                    tasks,
+                    |agent| async move { vec![agent.run_verify_task(*task).await] },
+                );
+            }
+            CoordinatorMessage::VerifyBatch(batch)
+                if !peer.negotiated.accepts(BATCH_CAPABILITY) =>
+            {
+                warn!(batch_id = %batch.batch_id, "Refusing batch the coordinator did not negotiate");
+                let e = VerifierError::UnsupportedMessage(format!(
+                    "verify_batch needs the {} capability",
+                    BATCH_CAPABILITY
+                ));
+                let reply = self.error_reply(&e, Some("verify_batch".to_string()));
+                send(replies, &reply);
+            }
+            CoordinatorMessage::VerifyBatch(batch) => {
+                let span =
+                    info_span!("batch", batch_id = %batch.batch_id, agent_id = %self.agent_id());
+                let quest_ids = batch
+                    .tasks
+                    .iter()
+                    .map(|entry| entry.quest_id().to_string())
+                    .collect::<Vec<_>>();
+                if let Err(e) = self.admit(quest_ids.len(), peer.primary, results, task_slots) {
+                    for quest_id in quest_ids {
+                        send(replies, &self.reject_task(quest_id, &e));
+                    }
+                    return None;
+                }
+                self.spawn_verification(
+                    quest_ids,
+                    span,
+                    results,
+                    task_slots,
+                    tasks,
+                    |agent| async move { agent.run_verify_batch(batch).await },
+                );
+            }
+            CoordinatorMessage::Registered {
+                primary,
+                encoding,
+                accepted_capabilities,
+                protocol_version,
+            } => {
+                let picked = encoding.map(Encoding::as_str);
+                debug!(primary, encoding = picked, "Registration acknowledged");
+                let encoding = encoding.filter(|encoding| {
+                    let accepted = match encoding {
+                        Encoding::Json => true,
+                        Encoding::Msgpack => peer.negotiated.accepts(MSGPACK_CAPABILITY),
+                        Encoding::Cbor => peer.negotiated.accepts(CBOR_CAPABILITY),
+                    };
+                    if !accepted {
+                        warn!(
+                            encoding = encoding.as_str(),
+                            "Ignoring encoding the coordinator did not accept"
+                        );
+                    }
+                    accepted
+                });
+                return Some(CoordinatorMessage::Registered {
+                    primary,
+                    encoding,
+                    accepted_capabilities,
+                    protocol_version,
+                });
+            }
+            message @ CoordinatorMessage::Primary { .. } => return Some(message),
+            other => {
+                if let Some(reply) = self.handle_control(other) {
+                    send(replies, &reply);
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether `quest_id` has already been taken on. A completed quest's
+    /// report goes to `results` again, flagged `duplicate`; a repeat of a
+    /// quest still in flight is dropped, as its result is on the way.
+    fn is_duplicate(&self, quest_id: &str, results: &OutboundQueue) -> bool {
+        if let Some(report) = self.recent_results.get(quest_id) {
+            info!(quest_id, "Resending result for repeated task");
+            METRICS.tasks_duplicate.fetch_add(1, Ordering::Relaxed);
+            results.push(Message::Text(mark_duplicate(&report)));
+            return true;
+        }
+        let in_flight = self.in_flight.lock().unwrap();
+        if in_flight.values().any(|task| task.quest_id == quest_id) {
+            info!(quest_id, "Ignoring repeat of a task in flight");
+            METRICS.tasks_duplicate.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// Refuse `count` new tasks from a coordinator that isn't `primary`,
+    /// while `results` is full because the writer is stalled, or if they
+    /// would push the number waiting for a concurrency slot past
+    /// `max_queued_tasks`. Tasks that can start at once don't count as
+    /// waiting.
+    fn admit(
+        &self,
+        count: usize,
+        primary: bool,
+        results: &OutboundQueue,
+        task_slots: &Semaphore,
+    ) -> Result<(), VerifierError> {
+        if !primary {
+            return Err(VerifierError::NotPrimary(
+                "tasks are only taken from the primary coordinator".into(),
+            ));
+        }
+        if results.is_full() {
+            return Err(VerifierError::Busy(format!(
+                "outbound queue is full ({} messages), results are not being sent",
+                self.outbound_queue_capacity
+            )));
+        }
+        let queued = self.queue_depth();
+        let waiting = (queued + count).saturating_sub(task_slots.available_permits());
+        if waiting > self.max_queued_tasks {
+            return Err(VerifierError::Busy(format!(
+                "{} tasks already queued (max {})",
+                queued, self.max_queued_tasks
+            )));
+        }
+        Ok(())
+    }
+
+    /// Nack a task refused by `admit`, so the coordinator can reschedule it
+    fn reject_task(&self, quest_id: String, e: &VerifierError) -> AgentMessage {
+        warn!(quest_id = %quest_id, reason = e.code(), error = %e, "Rejecting task");
+        if matches!(e, VerifierError::Busy(_)) {
+            METRICS.tasks_rejected_busy.fetch_add(1, Ordering::Relaxed);
+        }
+        AgentMessage::TaskRejected {
+            quest_id,
+            agent_id: self.agent_id(),
+            reason: e.code().to_string(),
+            queue_depth: self.queue_depth(),
+        }
+    }
+
+    /// Parse a coordinator message, or produce the error reply for one that
+    /// is oversized or malformed
+    fn parse_message(&self, text: &str) -> Result<CoordinatorMessage, Box<AgentMessage>> {
+        self.check_message_size(text.len())?;
+        if let Ok(message) = serde_json::from_str::<Value>(text) {
+            if let Err(e) = self.coordinator_auth.check(&message) {
+                warn!(error = %e, "Rejecting unauthenticated message");
+                return Err(Box::new(self.auth_failure(&message, &e)));
+            }
+        }
+
+        serde_json::from_str::<CoordinatorMessage>(text).map_err(|e| {
+            warn!(error = %e, "Rejecting malformed message");
+            let message_type = serde_json::from_str::<Value>(text)
+                .ok()
+                .and_then(|v| v.get("type").and_then(Value::as_str).map(str::to_string));
+            let e = VerifierError::MalformedMessage(e.to_string());
+            Box::new(self.error_reply(&e, message_type))
+        })
+    }
+
+    /// Parse a MessagePack- or CBOR-encoded coordinator message, or produce
+    /// the error reply, as [`parse_message`](Self::parse_message) does for
+    /// JSON
+    fn parse_binary(&self, bytes: &[u8]) -> Result<CoordinatorMessage, Box<AgentMessage>> {
+        self.check_message_size(bytes.len())?;
+        let message = decode_binary(bytes).map_err(|e| {
+            warn!(error = %e, "Rejecting malformed message");
+            let e = VerifierError::MalformedMessage(e);
+            Box::new(self.error_reply(&e, None))
+        })?;
+        if let Err(e) = self.coordinator_auth.check(&message) {
+            warn!(error = %e, "Rejecting unauthenticated message");
+            return Err(Box::new(self.auth_failure(&message, &e)));
+        }
+
+        let message_type = message
+            .get("type")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        serde_json::from_value::<CoordinatorMessage>(message).map_err(|e| {
+            warn!(error = %e, "Rejecting malformed message");
+            let e = VerifierError::MalformedMessage(e.to_string());
+            Box::new(self.error_reply(&e, message_type))
+        })
+    }
+
+    /// Refuse a message before parsing; the parsed form is several times
+    /// larger
+    fn check_message_size(&self, len: usize) -> Result<(), Box<AgentMessage>> {
+        if len <= self.max_task_bytes {
+            return Ok(());
+        }
+        warn!(
+            bytes = len,
+            max = self.max_task_bytes,
+            "Rejecting oversized message"
+        );
+        let e = VerifierError::TaskTooLarge(format!(
+            "message is {} bytes (max {})",
+            len, self.max_task_bytes
+        ));
+        Err(Box::new(self.error_reply(&e, None)))
+    }
+
+    /// Reply to a message whose MAC failed: a failed result for a task, so
+    /// the coordinator stops waiting on it, else an error reply
+    fn auth_failure(&self, message: &Value, e: &VerifierError) -> AgentMessage {
+        let field = |name: &str| {
+            message
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let message_type = field("type");
+        match (message_type.as_deref(), field("questId")) {
+            (Some("verify_task"), Some(quest_id)) => AgentMessage::TaskFailed(TaskFailure {
+                quest_id,
+                agent_id: self.agent_id(),
+                status: "error".to_string(),
+                error: e.to_string(),
+                code: Some(e.code().to_string()),
+                retryable: Some(e.is_retryable()),
+                measurement: None,
+            }),
+            _ => self.error_reply(e, message_type),
+        }
+    }
+
+    /// Run verification work once a concurrency slot is free. `work`
+    /// yields one report per entry of `quest_ids`, in order; each quest is
+    /// tracked so shutdown and cancellation can account for it.
+    fn spawn_verification<F, Fut>(
+        self: &Arc<Self>,
+        quest_ids: Vec<String>,
+        span: Span,
+        responses: &Arc<OutboundQueue>,
+        task_slots: &Arc<Semaphore>,
+        tasks: &mut JoinSet<()>,
+        work: F,
+    ) where
+        F: FnOnce(Arc<Self>) -> Fut + Send + 'static,
+        Fut: Future<Output = Vec<TaskReport>> + Send,
+    {
+        let seqs: Vec<u64> = quest_ids
+            .iter()
+            .map(|_| self.next_task_seq.fetch_add(1, Ordering::Relaxed))
+            .collect();
+        let in_batch = quest_ids.len() > 1;
+
+        let agent = Arc::clone(self);
+        let responses = Arc::clone(responses);
+        let task_slots = Arc::clone(task_slots);
+        let report_seqs = seqs.clone();
+        let started = Arc::new(AtomicBool::new(false));
+        let job_started = Arc::clone(&started);
+        let job = async move {
+            let Ok(_permit) = task_slots.acquire_owned().await else {
+                return;
+            };
+            job_started.store(true, Ordering::Relaxed);
+            let in_flight_guard = METRICS.track_in_flight();
+            let reports = work(Arc::clone(&agent)).await;
+            drop(in_flight_guard);
+
+            // Only report, or keep for repeats, tasks that shutdown or a
+            // cancellation hasn't already written off; removal and send
+            // happen without an await in between
+            let owned: Vec<(String, String)> = {
+                let mut in_flight = agent.in_flight.lock().unwrap();
+                report_seqs
+                    .iter()
+                    .zip(reports)
+                    .filter_map(|(seq, report)| {
+                        let quest_id = in_flight.remove(seq)?.quest_id;
+                        Some((quest_id, agent.settle(report)))
+                    })
+                    .collect()
+            };
+            for (quest_id, report) in owned {
+                agent.outbox.store(&quest_id, &report);
+                responses.send(Message::Text(report)).await;
+            }
+        };
+
+        // Hold the lock until the entries exist, so the job can't finish
+        // and look for them first
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let abort = tasks.spawn(job.instrument(span));
+        for (seq, quest_id) in seqs.into_iter().zip(quest_ids) {
+            in_flight.insert(
+                seq,
+                InFlightTask {
+                    quest_id,
+                    abort: abort.clone(),
+                    in_batch,
+                    started: Arc::clone(&started),
+                },
+            );
+        }
+    }
+
+    /// Give in-flight tasks up to the drain timeout to finish, then abort the
+    /// rest and report them as `aborted` so the coordinator can reschedule
+    async fn drain_tasks(&self, tasks: &mut JoinSet<()>, responses: &Arc<OutboundQueue>) {
+        let in_flight = self.in_flight.lock().unwrap().len();
+        if in_flight > 0 {
+            info!(
+                in_flight,
+                drain_timeout_secs = self.drain_timeout.as_secs(),
+                "Waiting for in-flight tasks"
+            );
+        }
+
+        let drained = tokio::time::timeout(self.drain_timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await
+        .is_ok();
+
+        if !drained {
+            let abandoned: Vec<String> = {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                tasks.abort_all();
+                in_flight.drain().map(|(_, task)| task.quest_id).collect()
+            };
+            for quest_id in abandoned {
+                warn!(quest_id = %quest_id, "Aborting unfinished task");
+                let aborted = AgentMessage::TaskFailed(TaskFailure {
+                    quest_id,
+                    agent_id: self.agent_id(),
+                    status: "aborted".to_string(),
+                    error: "agent shutting down".to_string(),
+                    code: None,
+                    retryable: None,
+                    measurement: None,
+                });
+                send(responses, &aborted);
+            }
+            while tasks.join_next().await.is_some() {}
+        }
+    }
+
+    /// Open a connection to `url` and register on it. Every new
+    /// connection registers afresh. If the coordinator reports our agent ID
+    /// as taken, a fresh one is tried on a new connection; any other
+    /// rejection fails with [`VerifierError::RegistrationRejected`].
+    async fn connect(
+        &self,
+        url: &str,
+    ) -> Result<(WsSink, WsStream, Registration), Box<dyn std::error::Error>> {
+        let mut renames = 0;
+        loop {
+            info!(url = %url, "Connecting to coordinator");
+
+            let mut request = url.into_client_request()?;
+            self.coordinator_auth.authorize(&mut request);
+            let ws_stream = self.coordinator_tls.connect(request).await?;
+            let (mut write, mut read) = ws_stream.split();
+
+            let registration = self
+                .coordinator_auth
+                .seal(self.sign_registration().to_string());
+            write.send(Message::Text(registration)).await?;
+            match self.await_ack(&mut write, &mut read).await {
+                Ok(registration) => {
+                    let negotiated = &registration.negotiated;
+                    info!(
+                        agent_id = %self.agent_id(),
+                        mode = %self.mode,
+                        url = %url,
+                        protocol_version = negotiated.protocol_version,
+                        accepted_capabilities = ?negotiated.accepted_capabilities,
+                        "Registered with coordinator"
+                    );
+                    if negotiated
+                        .protocol_version
+                        .is_some_and(|version| version > protocol::PROTOCOL_VERSION)
+                    {
+                        warn!(
+                            url = %url,
+                            ours = protocol::PROTOCOL_VERSION,
+                            "Coordinator speaks a newer protocol version"
+                        );
+                    }
+                    return Ok((write, read, registration));
+                }
+                Err(e)
+                    if rejection_reason(e.as_ref()) == Some(protocol::DUPLICATE_AGENT_ID)
+                        && renames < MAX_AGENT_ID_RENAMES => {}
+                Err(e) => return Err(e),
+            }
+            renames += 1;
+            let taken = self.agent_id();
+            let fresh = self.rename();
+            warn!(url = %url, taken = %taken, agent_id = %fresh, "Agent ID already registered, retrying under a fresh one");
+            let _ = write.close().await;
+        }
+    }
+
+    /// Wait up to `register_timeout` for the coordinator to acknowledge the
+    /// registration just sent. Pings are answered meanwhile; other frames
+    /// are kept to be dispatched after the acknowledgement.
+    async fn await_ack(
+        &self,
+        write: &mut WsSink,
+        read: &mut WsStream,
+    ) -> Result<Registration, Box<dyn std::error::Error>> {
+        let deadline = tokio::time::sleep(self.register_timeout);
+        tokio::pin!(deadline);
+        let mut early = Vec::new();
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                _ = &mut deadline => {
+                    return Err(format!(
+                        "registration not acknowledged within {}ms",
+                        self.register_timeout.as_millis()
+                    )
+                    .into());
+                }
+            };
+            let frame = match msg {
+                Some(Ok(Message::Text(text))) => Frame::Text(text),
+                Some(Ok(Message::Binary(bytes))) => Frame::Binary(bytes),
+                Some(Ok(Message::Ping(payload))) => {
+                    write.send(Message::Pong(payload)).await?;
+                    continue;
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err("connection closed before registration was acknowledged".into());
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+            };
+            let message_type = frame_type(&frame);
+            if !matches!(message_type.as_deref(), Some("register_ack" | "registered")) {
+                early.push(frame);
+                continue;
+            }
+            let parsed = match &frame {
+                Frame::Text(text) => self.parse_message(text),
+                Frame::Binary(bytes) => self.parse_binary(bytes),
+            };
+            match parsed {
+                Ok(CoordinatorMessage::Registered {
+                    accepted_capabilities,
+                    protocol_version,
+                    ..
+                }) => {
+                    let negotiated = Negotiated {
+                        protocol_version,
+                        accepted_capabilities: accepted_capabilities
+                            .map(|capabilities| capabilities.into_iter().collect()),
+                    };
+                    // The acknowledgement first, so its encoding and primary
+                    // flag apply to whatever came before it
+                    let mut frames = vec![frame];
+                    frames.append(&mut early);
+                    return Ok(Registration { negotiated, frames });
+                }
+                Ok(CoordinatorMessage::RegisterRejected { reason }) => {
+                    return Err(Box::new(VerifierError::RegistrationRejected(reason)));
+                }
+                // Left for dispatch to report
+                _ => early.push(frame),
+            }
+        }
+    }
+
+    /// Connect, register and serve one coordinator connection until it
+    /// drops, goes silent, or shutdown is requested
+    async fn session(
+        self: &Arc<Self>,
+        url: &str,
+        responses: &Arc<OutboundQueue>,
+        task_slots: &Arc<Semaphore>,
+        tasks: &mut JoinSet<()>,
+        mut shutdown: Pin<&mut impl Future<Output = ()>>,
+    ) -> Result<SessionEnd, Box<dyn std::error::Error>> {
+        let (mut write, mut read, registration) = self.connect(url).await?;
+        let negotiated = registration.negotiated;
+        let auth = &self.coordinator_auth;
+
+        // Results the coordinator never acknowledged, possibly from before a
+        // restart; the coordinator drops repeats by their `idempotencyKey`
+        let pending = self.outbox.pending();
+        if !pending.is_empty() {
+            info!(count = pending.len(), "Replaying unacknowledged results");
+        }
+        for report in pending {
+            debug!(quest_id = %report.quest_id, "Replaying result");
+            write.send(Message::Text(auth.seal(report.message))).await?;
+        }
+        // Only now is the connection known to carry messages both ways
+        METRICS.set_connected(true);
+
+        // The writer drains the queue for the life of this connection and
+        // hands the sink back once the queue is closed. Messages are framed
+        // as they are written, in whatever encoding is in use by then.
+        let encoding = Arc::new(FrameEncoding::default());
+        let outgoing = Arc::clone(responses);
+        let auth = auth.clone();
+        let writer_encoding = Arc::clone(&encoding);
+        let mut writer = tokio::spawn(async move {
+            while let Some(message) = outgoing.recv().await {
+                let message = match message {
+                    Message::Text(text) => encode_frame(auth.seal(text), writer_encoding.get()),
+                    other => other,
+                };
+                write.send(message).await?;
+            }
+            Ok::<_, tokio_tungstenite::tungstenite::Error>(write)
+        });
+        let mut writer_done = false;
+        let mut shutting_down = false;
+
+        let peer = Peer {
+            primary: true,
+            negotiated: &negotiated,
+        };
+        for frame in registration.frames {
+            follow_frame(&encoding, &frame, url);
+            let update = self.dispatch(&frame, responses, responses, peer, task_slots, tasks);
+            if let Some(CoordinatorMessage::Registered {
+                encoding: Some(picked),
+                ..
+            }) = update
+            {
+                use_encoding(&encoding, picked, url);
+            }
+        }
+
+        // Any inbound frame counts as a sign of life, pongs included; pings
+        // keep idle connections from being reaped by load balancers
+        let mut heartbeat = tokio::time::interval(self.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.tick().await;
+        let mut last_seen = Instant::now();
+        // When the unanswered heartbeat ping went out
+        let mut ping_sent: Option<Instant> = None;
+        let mut status = self.status_timer().await;
+
+        // Listen for tasks
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                result = &mut writer => {
+                    writer_done = true;
+                    match result {
+                        Ok(Err(e)) => error!(error = %e, "WebSocket write error"),
+                        Err(e) => error!(error = %e, "Writer task failed"),
+                        Ok(Ok(_)) => {}
+                    }
+                    break;
+                }
+                Some(_) = tasks.join_next(), if !tasks.is_empty() => continue,
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() >= self.heartbeat_timeout {
+                        warn!(
+                            silent_ms = last_seen.elapsed().as_millis() as u64,
+                            "No frames from coordinator, dropping connection"
+                        );
+                        break;
+                    }
+                    responses.push(Message::Ping(Vec::new()));
+                    ping_sent.get_or_insert_with(Instant::now);
+                    continue;
+                }
+                _ = status.tick() => {
+                    send(responses, &AgentMessage::Status(self.status_update()));
+                    continue;
+                }
+                _ = &mut shutdown => {
+                    info!("Shutdown requested, no longer accepting tasks");
+                    shutting_down = true;
+                    break;
+                }
+            };
+            let Some(msg) = msg else { break };
+            last_seen = Instant::now();
+
+            let frame = match msg {
+                Ok(Message::Text(text)) => Frame::Text(text),
+                Ok(Message::Binary(bytes)) => Frame::Binary(bytes),
+                Ok(Message::Close(_)) => {
+                    info!("Connection closed by coordinator");
+                    break;
+                }
+                // Answered through the writer rather than left to
+                // tungstenite, which only flushes its own pong when this
+                // half of the stream next writes
+                Ok(Message::Ping(payload)) => {
+                    responses.push(Message::Pong(payload));
+                    continue;
+                }
+                Ok(Message::Pong(_)) => {
+                    if let Some(sent) = ping_sent.take() {
+                        let rtt_ms = sent.elapsed().as_millis() as u64;
+                        debug!(rtt_ms, "Heartbeat answered");
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    error!(error = %e, "WebSocket error");
+                    break;
+                }
+                _ => continue,
+            };
+            follow_frame(&encoding, &frame, url);
+            let update = self.dispatch(&frame, responses, responses, peer, task_slots, tasks);
+            if let Some(CoordinatorMessage::Registered {
+                encoding: Some(picked),
+                ..
+            }) = update
+            {
+                use_encoding(&encoding, picked, url);
+            }
+        }
+
+        METRICS.set_connected(false);
+
+        if writer_done {
+            return Ok(if shutting_down { SessionEnd::Shutdown(None) } else { SessionEnd::Disconnected });
+        }
+        if shutting_down {
+            return Ok(SessionEnd::Shutdown(Some(writer)));
+        }
+        writer.abort();
+        Ok(SessionEnd::Disconnected)
+    }
+
+    /// Exit the process if shutdown outlives the grace period or a second
+    /// signal arrives, so a stuck flush or close can't block termination
+    fn arm_force_exit(&self) {
+        let grace = self.shutdown_grace;
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(grace) => {
+                    error!(grace_secs = grace.as_secs(), "Shutdown did not finish in time, forcing exit");
+                }
+                _ = shutdown_signal() => {
+                    warn!("Second shutdown signal received, forcing exit");
+                }
+            }
+            std::process::exit(1);
+        });
+    }
+
+    /// Stop taking tasks, finish the ones in flight, deregister and let
+    /// [`run`](Self::run) return, as on SIGTERM. Takes effect even if
+    /// called before `run`.
+    pub fn shutdown(&self) {
+        self.shutdown_requested.notify_one();
+    }
+
+    /// Resolves on a shutdown signal or [`shutdown`](Self::shutdown)
+    async fn until_shutdown(&self) {
+        tokio::select! {
+            _ = shutdown_signal() => {}
+            _ = self.shutdown_requested.notified() => {}
+        }
+    }
+
+    /// Main agent loop
+    ///
+    /// Runs inside an `agent` span so connection events carry `agent_id`;
+    /// per-task spans nest beneath it.
+    pub async fn run(self: Arc<Self>) -> Result<(), Box<dyn std::error::Error>> {
+        let span = info_span!("agent", agent_id = %self.agent_id());
+        let outcome = match self.coordinator_mode {
+            CoordinatorMode::Failover => Arc::clone(&self).run_sessions().instrument(span).await,
+            CoordinatorMode::ActiveActive => {
+                Arc::clone(&self).run_active_active().instrument(span).await
+            }
+        };
+        // Records written since the last fsync batch
+        if let Some(log) = &self.attestation_log {
+            log.sync();
+        }
+        outcome
+    }
+
+    async fn run_sessions(self: Arc<Self>) -> Result<(), Box<dyn std::error::Error>> {
+        // Responses from concurrent tasks are funnelled to a single writer.
+        // Ordering is not preserved; every result carries its own questId.
+        let responses = Arc::new(OutboundQueue::new(
+            self.outbound_queue_capacity,
+            self.outbound_overflow,
+        ));
+        let mut tasks = JoinSet::new();
+        let shutdown = self.until_shutdown();
+        tokio::pin!(shutdown);
+
+        let task_slots = Arc::new(Semaphore::new(self.max_concurrent_tasks));
+        info!(
+            max_concurrent_tasks = self.max_concurrent_tasks,
+            "Processing tasks concurrently"
+        );
+
+        let mut connected_once = false;
+        let mut reconnect_delay = RECONNECT_BASE_DELAY;
+        // Round-robin: a round starts with the coordinator after the one
+        // that last dropped, so a flapping coordinator isn't retried first
+        let count = self.coordinator_urls.len();
+        let mut next = 0;
+        loop {
+            let mut end = Err("no coordinator URL configured".into());
+            for offset in 0..count {
+                let index = (next + offset) % count;
+                let url = &self.coordinator_urls[index];
+                end = self
+                    .session(url, &responses, &task_slots, &mut tasks, shutdown.as_mut())
+                    .await;
+                match &end {
+                    Ok(_) => {
+                        next = (index + 1) % count;
+                        break;
+                    }
+                    Err(e) if rejection_reason(e.as_ref()).is_some() => break,
+                    Err(e) => warn!(url = %url, error = %e, "Connection failed"),
+                }
+            }
+            match end {
+                Ok(SessionEnd::Shutdown(Some(writer))) => {
+                    self.arm_force_exit();
+
+                    // Deregister first so the coordinator stops dispatching
+                    // while in-flight work finishes, then flush its results
+                    // and leave cleanly
+                    let deregister = AgentMessage::Deregister { agent_id: self.agent_id() };
+                    send(&responses, &deregister);
+                    self.drain_tasks(&mut tasks, &responses).await;
+                    responses.close();
+
+                    let mut write = writer.await??;
+                    write.close().await?;
+                    info!("Deregistered and closed connection");
+                    return Ok(());
+                }
+                Ok(SessionEnd::Shutdown(None)) => return Ok(()),
+                Ok(SessionEnd::Disconnected) => reconnect_delay = RECONNECT_BASE_DELAY,
+                // Registering again would only be refused again
+                Err(e) if rejection_reason(e.as_ref()).is_some() => return Err(e),
+                // A coordinator that was never reachable is a configuration
+                // problem, not an outage to wait out
+                Err(e) if !connected_once => return Err(e),
+                // Each failure was logged as its coordinator was tried
+                Err(_) => {}
+            }
+            connected_once = true;
+
+            info!(delay_ms = reconnect_delay.as_millis() as u64, "Reconnecting");
+            tokio::select! {
+                _ = tokio::time::sleep(reconnect_delay) => {}
+                _ = &mut shutdown => {
+                    info!("Shutdown requested while disconnected");
+                    return Ok(());
+                }
+            }
+            reconnect_delay = (reconnect_delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+    }
+
+    /// Active-active mode: one link per coordinator, all connected at once.
+    /// Every link answers pings and status requests, but tasks are only
+    /// taken from the primary, and results flow to whichever link is
+    /// primary when they are written.
+    async fn run_active_active(self: Arc<Self>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.coordinator_urls.is_empty() {
+            return Err("no coordinator URL configured".into());
+        }
+        let results = Arc::new(OutboundQueue::new(
+            self.outbound_queue_capacity,
+            self.outbound_overflow,
+        ));
+        let (primary_tx, primary_rx) = watch::channel(None::<usize>);
+        let (closing_tx, closing_rx) = watch::channel(false);
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+
+        let mut links = JoinSet::new();
+        for (link, url) in self.coordinator_urls.iter().enumerate() {
+            let link = Arc::clone(&self).link(
+                link,
+                url.clone(),
+                Arc::clone(&results),
+                primary_rx.clone(),
+                closing_rx.clone(),
+                events_tx.clone(),
+            );
+            links.spawn(link.in_current_span());
+        }
+
+        let mut tasks = JoinSet::new();
+        let task_slots = Arc::new(Semaphore::new(self.max_concurrent_tasks));
+        info!(
+            coordinators = self.coordinator_urls.len(),
+            max_concurrent_tasks = self.max_concurrent_tasks,
+            "Connecting to all coordinators"
+        );
+        let shutdown = self.until_shutdown();
+        tokio::pin!(shutdown);
+
+        // Control queues, encodings and negotiated settings of the links
+        // that are up
+        let mut replies: HashMap<usize, (Arc<OutboundQueue>, Arc<FrameEncoding>, Negotiated)> =
+            HashMap::new();
+        loop {
+            tokio::select! {
+                Some(event) = events.recv() => match event {
+                    LinkEvent::Up { link, replies: queue, encoding, negotiated } => {
+                        replies.insert(link, (queue, encoding, negotiated));
+                    }
+                    LinkEvent::Message { link, frame } => {
+                        let Some((queue, encoding, negotiated)) = replies.get(&link) else { continue };
+                        follow_frame(encoding, &frame, &self.coordinator_urls[link]);
+                        let is_primary = *primary_tx.borrow() == Some(link);
+                        let peer = Peer {
+                            primary: is_primary,
+                            negotiated,
+                        };
+                        let update = self.dispatch(
+                            &frame,
+                            queue,
+                            &results,
+                            peer,
+                            &task_slots,
+                            &mut tasks,
+                        );
+                        let flag = match update {
+                            Some(CoordinatorMessage::Registered { primary, encoding: picked, .. }) => {
+                                if let Some(picked) = picked {
+                                    use_encoding(encoding, picked, &self.coordinator_urls[link]);
+                                }
+                                primary
+                            }
+                            Some(CoordinatorMessage::Primary { primary }) => Some(primary),
+                            _ => None,
+                        };
+                        match flag {
+                            Some(true) if !is_primary => {
+                                info!(url = %self.coordinator_urls[link], "Coordinator is now primary");
+                                primary_tx.send_replace(Some(link));
+                                // Whatever the last primary never acknowledged;
+                                // repeats are dropped by `idempotencyKey`
+                                for report in self.outbox.pending() {
+                                    debug!(quest_id = %report.quest_id, "Replaying result");
+                                    queue.push(Message::Text(report.message));
+                                }
+                                METRICS.set_connected(true);
+                            }
+                            Some(false) if is_primary => {
+                                info!(url = %self.coordinator_urls[link], "Coordinator is no longer primary");
+                                primary_tx.send_replace(None);
+                                METRICS.set_connected(false);
+                            }
+                            _ => {}
+                        }
+                    }
+                    LinkEvent::Down { link } => {
+                        replies.remove(&link);
+                        if *primary_tx.borrow() == Some(link) {
+                            warn!(url = %self.coordinator_urls[link], "Lost the primary coordinator");
+                            primary_tx.send_replace(None);
+                            METRICS.set_connected(false);
+                        }
+                    }
+                },
+                Some(_) = tasks.join_next(), if !tasks.is_empty() => {}
+                _ = &mut shutdown => break,
+            }
+        }
+
+        info!("Shutdown requested, no longer accepting tasks");
+        self.arm_force_exit();
+        // Every link deregisters at once; the primary stays open until the
+        // results of in-flight work are flushed
+        closing_tx.send_replace(true);
+        self.drain_tasks(&mut tasks, &results).await;
+        results.close();
+        while links.join_next().await.is_some() {}
+        METRICS.set_connected(false);
+        Ok(())
+    }
+
+    /// Keep one active-active link connected until shutdown, reconnecting
+    /// with backoff
+    async fn link(
+        self: Arc<Self>,
+        link: usize,
+        url: String,
+        results: Arc<OutboundQueue>,
+        primary: watch::Receiver<Option<usize>>,
+        mut closing: watch::Receiver<bool>,
+        events: mpsc::UnboundedSender<LinkEvent>,
+    ) {
+        let mut reconnect_delay = RECONNECT_BASE_DELAY;
+        loop {
+            let session = self.link_session(
+                link,
+                &url,
+                &results,
+                primary.clone(),
+                closing.clone(),
+                &events,
+            );
+            match session.await {
+                Ok(()) => reconnect_delay = RECONNECT_BASE_DELAY,
+                // The other coordinators may still take the agent
+                Err(e) if rejection_reason(e.as_ref()).is_some() => {
+                    error!(url = %url, error = %e, "Giving up on coordinator");
+                    return;
+                }
+                Err(e) => warn!(url = %url, error = %e, "Connection failed"),
+            }
+            if *closing.borrow() {
+                return;
+            }
+
+            info!(url = %url, delay_ms = reconnect_delay.as_millis() as u64, "Reconnecting");
+            tokio::select! {
+                _ = tokio::time::sleep(reconnect_delay) => {}
+                _ = closing.wait_for(|closing| *closing) => return,
+            }
+            reconnect_delay = (reconnect_delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+    }
+
+    /// Serve one active-active connection until it drops, goes silent, or
+    /// the agent shuts down. Inbound text goes to the executor as
+    /// [`LinkEvent::Message`]; replies come back on the link's own queue.
+    async fn link_session(
+        &self,
+        link: usize,
+        url: &str,
+        results: &Arc<OutboundQueue>,
+        primary: watch::Receiver<Option<usize>>,
+        mut closing: watch::Receiver<bool>,
+        events: &mpsc::UnboundedSender<LinkEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (write, mut read, registration) = self.connect(url).await?;
+        let replies = Arc::new(OutboundQueue::new(
+            self.outbound_queue_capacity,
+            self.outbound_overflow,
+        ));
+        let encoding = Arc::new(FrameEncoding::default());
+        let _ = events.send(LinkEvent::Up {
+            link,
+            replies: Arc::clone(&replies),
+            encoding: Arc::clone(&encoding),
+            negotiated: registration.negotiated,
+        });
+        for frame in registration.frames {
+            let _ = events.send(LinkEvent::Message { link, frame });
+        }
+        let mut writer = tokio::spawn(link_writer(
+            link,
+            write,
+            Arc::clone(&replies),
+            Arc::clone(results),
+            primary,
+            encoding,
+            self.coordinator_auth.clone(),
+        ));
+        let mut writer_done = false;
+        let mut shutting_down = false;
+
+        let mut heartbeat = tokio::time::interval(self.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.tick().await;
+        let mut last_seen = Instant::now();
+        let mut ping_sent: Option<Instant> = None;
+        let mut status = self.status_timer().await;
+
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                result = &mut writer => {
+                    writer_done = true;
+                    match result {
+                        Ok(Err(e)) => error!(url = %url, error = %e, "WebSocket write error"),
+                        Err(e) => error!(url = %url, error = %e, "Writer task failed"),
+                        Ok(Ok(_)) => {}
+                    }
+                    break;
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() >= self.heartbeat_timeout {
+                        warn!(
+                            url = %url,
+                            silent_ms = last_seen.elapsed().as_millis() as u64,
+                            "No frames from coordinator, dropping connection"
+                        );
+                        break;
+                    }
+                    replies.push(Message::Ping(Vec::new()));
+                    ping_sent.get_or_insert_with(Instant::now);
+                    continue;
+                }
+                _ = status.tick() => {
+                    send(&replies, &AgentMessage::Status(self.status_update()));
+                    continue;
+                }
+                _ = closing.wait_for(|closing| *closing) => {
+                    shutting_down = true;
+                    break;
+                }
+            };
+            let Some(msg) = msg else { break };
+            last_seen = Instant::now();
+
+            match msg {
+                Ok(Message::Text(text)) => {
+                    let frame = Frame::Text(text);
+                    let _ = events.send(LinkEvent::Message { link, frame });
+                }
+                Ok(Message::Binary(bytes)) => {
+                    let frame = Frame::Binary(bytes);
+                    let _ = events.send(LinkEvent::Message { link, frame });
+                }
+                Ok(Message::Close(_)) => {
+                    info!(url = %url, "Connection closed by coordinator");
+                    break;
+                }
+                Ok(Message::Ping(payload)) => replies.push(Message::Pong(payload)),
+                Ok(Message::Pong(_)) => {
+                    if let Some(sent) = ping_sent.take() {
+                        let rtt_ms = sent.elapsed().as_millis() as u64;
+                        debug!(url = %url, rtt_ms, "Heartbeat answered");
+                    }
+                }
+                Err(e) => {
+                    error!(url = %url, error = %e, "WebSocket error");
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        if shutting_down && !writer_done {
+            // Queued ahead of the close, so it goes out before the writer
+            // finishes
+            let deregister = AgentMessage::Deregister { agent_id: self.agent_id() };
+            send(&replies, &deregister);
+            replies.close();
+            let mut write = writer.await??;
+            write.close().await?;
+            info!(url = %url, "Deregistered and closed connection");
+            return Ok(());
+        }
+        replies.close();
+        writer.abort();
+        let _ = events.send(LinkEvent::Down { link });
+        Ok(())
+    }
+}
+
+/// Names of the hash algorithms this build can check
+fn supported_hash_algos() -> Vec<String> {
+    HashAlgorithm::ALL
+        .iter()
+        .map(|algo| algo.name().to_string())
+        .collect()
+}
+
+/// Length of `value` serialized as JSON, without building the bytes
+fn json_len(value: &Value) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+/// Algorithm a chunk is checked with: its own, else the task's `hashAlgo`.
+/// A task that names an algorithm accepts no chunk declaring another.
+fn chunk_algorithm(
+    chunk: &DataChunk,
+    hash_algo: Option<HashAlgorithm>,
+) -> Result<HashAlgorithm, String> {
+    let Some(declared) = chunk.algorithm.as_deref() else {
+        return Ok(hash_algo.unwrap_or_default());
+    };
+    let declared = declared.parse::<HashAlgorithm>()?;
+    match hash_algo {
+        Some(task) if task != declared => Err(format!(
+            "chunk declares {} but the task uses {}",
+            declared, task
+        )),
+        _ => Ok(declared),
+    }
+}
+
+/// Collapse chunks repeated within a task, keeping first-occurrence order.
+/// Copies must match in hash, algorithm, data and signature, so a bad chunk
+/// can't hide a good one by claiming the same hash; malformed entries are
+/// never merged.
+/// Each survivor comes with the number of copies dropped.
+fn dedup_chunks(chunks: &[TaskChunk]) -> Vec<(&TaskChunk, usize)> {
+    let mut unique: Vec<(&TaskChunk, usize)> = Vec::with_capacity(chunks.len());
+    let mut by_claim: HashMap<(&str, Option<&str>), Vec<usize>> = HashMap::new();
+
+    for entry in chunks {
+        let TaskChunk::Valid(chunk) = entry else {
+            unique.push((entry, 0));
+            continue;
+        };
+        let candidates = by_claim
+            .entry((chunk.hash.as_str(), chunk.algorithm.as_deref()))
+            .or_default();
+        let existing = candidates.iter().copied().find(|&i| {
+            matches!(
+                unique[i].0,
+                TaskChunk::Valid(seen) if seen.data == chunk.data
+                    && seen.data_ref == chunk.data_ref
+                    && same_signature(seen, chunk)
+            )
+        });
+        match existing {
+            Some(i) => unique[i].1 += 1,
+            None => {
+                candidates.push(unique.len());
+                unique.push((entry, 0));
+            }
+        }
+    }
+    unique
+}
+
+/// Whether two copies of a chunk would pass or fail the signature check
+/// alike. The source matters because it picks the task's key.
+fn same_signature(a: &DataChunk, b: &DataChunk) -> bool {
+    a.signature == b.signature
+        && a.pubkey == b.pubkey
+        && (a.signature.is_none() || a.source == b.source)
+}
+
+/// Outcome of downloading a referenced chunk
+struct Fetched {
+    /// What the data hashed to, or why it could not be hashed
+    digest: Result<String, (FailureReason, String)>,
+    signature: Option<SignatureCheck>,
+}
+
+/// Weighted coverage of the expected hashes. Each expected hash weighs as
+/// much as the heaviest source that submitted it, or the default weight
+/// when no chunk did; it is covered when a chunk with that hash verified,
+/// but only by `warned_share` of its weight if every such chunk broke a
+/// `warn` rule.
+fn weigh_coverage(
+    task: &VerifyTask,
+    expected: &HashSet<String>,
+    results: &[ChunkResult],
+    warned_share: f64,
+) -> (f64, f64) {
+    let mut hashes: BTreeMap<&str, (f64, f64)> = expected
+        .iter()
+        .map(|hash| (hash.as_str(), (protocol::DEFAULT_SOURCE_WEIGHT, 0.0)))
+        .collect();
+    let mut submitted = HashSet::new();
+    for result in results {
+        let Some((weight, covered)) = hashes.get_mut(result.hash.as_str()) else {
+            continue;
+        };
+        let source_weight = task.source_weight(&result.source);
+        // The first submission replaces the default rather than competing
+        // with it
+        *weight = if submitted.insert(result.hash.as_str()) {
+            source_weight
+        } else {
+            weight.max(source_weight)
+        };
+        if result.status == ChunkStatus::Verified {
+            // A verified chunk only ever broke `warn` rules
+            let share = if result.violations.is_empty() {
+                1.0
+            } else {
+                warned_share
+            };
+            *covered = covered.max(share);
+        }
+    }
+    hashes
+        .values()
+        .fold((0.0, 0.0), |(covered_weight, total), (weight, covered)| {
+            (covered_weight + weight * covered, total + weight)
+        })
+}
+
+/// Hold the verified chunks among `results`, which line up with `unique`,
+/// to the task's rules. A chunk breaking a `fail` rule fails as
+/// `rule_violation` with the first one it broke as its detail.
+fn check_rules(rules: &RuleSet, unique: &[(&TaskChunk, usize)], results: &mut [ChunkResult]) {
+    for (result, (entry, _)) in results.iter_mut().zip(unique) {
+        if result.status != ChunkStatus::Verified {
+            continue;
+        }
+        // Referenced data is raw bytes and encrypted data is not kept
+        // once hashed, so only inline plaintext can be checked
+        let data = match entry {
+            TaskChunk::Valid(chunk) if chunk.data_ref.is_none() && chunk.encryption.is_none() => {
+                &chunk.data
+            }
+            _ => continue,
+        };
+        result.violations = rules.check(data);
+        if let Some(violation) = result
+            .violations
+            .iter()
+            .find(|violation| violation.severity == Severity::Fail)
+        {
+            result.status = ChunkStatus::Failed;
+            result.reason = Some(FailureReason::RuleViolation);
+            result.detail = Some(violation.detail.clone());
+        }
+    }
+}
+
+/// `part` of `whole` in percent; zero when `whole` is, rather than NaN
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        part / whole * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Confidence score for a percentage, rounded to the nearest point so that
+/// 94.99...% from floating-point error still meets a threshold of 95
+fn to_confidence(percent: f64) -> u8 {
+    if percent.is_nan() {
+        return 0;
+    }
+    percent.clamp(0.0, 100.0).round() as u8
+}
+
+/// Verified and failed chunk counts of each source in a task, by source
+/// name; chunks without a source are left out
+fn source_breakdown(task: &VerifyTask, results: &[ChunkResult]) -> Vec<SourceBreakdown> {
+    let mut sources: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for result in results.iter().filter(|result| !result.source.is_empty()) {
+        let (verified, failed) = sources.entry(result.source.as_str()).or_default();
+        if result.status == ChunkStatus::Verified {
+            *verified += 1;
+        } else {
+            *failed += 1;
+        }
+    }
+    sources
+        .into_iter()
+        .map(|(source, (verified, failed))| SourceBreakdown {
+            source: source.to_string(),
+            verified,
+            failed,
+            weight: task.source_weight(source),
+        })
+        .collect()
+}
+
+/// Signed result or failure report of one task
+struct TaskReport {
+    quest_id: String,
+    text: String,
+    /// Whether it settles the task, so a repeat is answered with it
+    /// rather than run again
+    settled: bool,
+}
+
+/// Source signature status, with why it is not valid
+type SignatureCheck = (SignatureStatus, Option<String>);
+
+/// Outcome of a task's chunk checks, ready to be attested
+#[derive(Default)]
+struct CheckedTask {
+    expected_count: usize,
+    /// Summed weight of the expected hashes that verified, and of all of
+    /// them; see [`weigh_coverage`]
+    covered_weight: f64,
+    expected_weight: f64,
+    source_breakdown: Vec<SourceBreakdown>,
+    /// Algorithm of the aggregate tree
+    algorithm: HashAlgorithm,
+    /// Merkle root of `verified_chunks`
+    aggregate_hash: String,
+    verified_chunks: Vec<String>,
+    failed_chunks: Vec<String>,
+    unexpected_chunks: Vec<String>,
+    chunk_results: Vec<ChunkResult>,
+    failure_details: Vec<ChunkFailure>,
+    proofs: Option<Vec<MerkleProof>>,
+    min_chunk_timestamp: Option<u64>,
+    max_chunk_timestamp: Option<u64>,
+    consistency: Option<ConsistencyReport>,
+}
+
+/// What every chunk of one task is checked against. Owns its parts, so
+/// large tasks can take it to the hashing pool.
+struct TaskRules {
+    mode: HashMode,
+    hash_algo: Option<HashAlgorithm>,
+    /// `None` when the task opts out of freshness checks
+    freshness: Option<FreshnessWindow>,
+    expected: HashSet<String>,
+    /// Task keys of the sources that sign their data
+    source_keys: HashMap<String, String>,
+    /// Key for encrypted chunks: the task's own, or else the agent's
+    decryption_key: Option<ChunkKey>,
+    max_chunk_bytes: usize,
+    /// Unix seconds when checking started
+    now: u64,
+}
+
+impl TaskRules {
+    /// Check a chunk's source signature over `bytes`, the data exactly as
+    /// hashed, which are only asked for when the chunk is signed. `None`
+    /// when there is nothing to check or the data never arrived.
+    fn signature_check<B: AsRef<[u8]>>(
+        &self,
+        chunk: &DataChunk,
+        bytes: impl FnOnce() -> Option<B>,
+    ) -> Option<SignatureCheck> {
+        let task_key = self.source_keys.get(&chunk.source).map(String::as_str);
+        let Some(signature) = chunk.signature.as_deref() else {
+            return task_key.map(|_| (SignatureStatus::Missing, None));
+        };
+        let Some(key) = task_key.or(chunk.pubkey.as_deref()) else {
+            let detail = format!("no public key for source {}", chunk.source);
+            return Some((SignatureStatus::Invalid, Some(detail)));
+        };
+        let check = match protocol::verify_chunk_signature(bytes()?.as_ref(), signature, key) {
+            Ok(()) => (SignatureStatus::Valid, None),
+            Err(e) => (SignatureStatus::Invalid, Some(e)),
+        };
+        Some(check)
+    }
+
+    /// Why a chunk timestamp (Unix seconds) is outside the freshness window
+    /// around `now`, if it is
+    fn freshness_violation(&self, timestamp: u64) -> Option<(FailureReason, String)> {
+        let window = self.freshness?;
+        if timestamp > self.now {
+            let ahead = timestamp - self.now;
+            return (ahead > window.max_skew.as_secs()).then(|| {
+                let detail = format!(
+                    "timestamp is {}s in the future (max clock skew {}s)",
+                    ahead,
+                    window.max_skew.as_secs()
+                );
+                (FailureReason::FutureTimestamp, detail)
+            });
+        }
+        let max_age = window.max_age?;
+        let age = self.now - timestamp;
+        (age > max_age.as_secs()).then(|| {
+            let detail = format!("timestamp is {}s old (max age {}s)", age, max_age.as_secs());
+            (FailureReason::Stale, detail)
+        })
+    }
+}
+
+/// How far chunk timestamps may stray from the local clock
+#[derive(Clone, Copy)]
+struct FreshnessWindow {
+    /// `None` turns the age check off
+    max_age: Option<Duration>,
+    max_skew: Duration,
+}
+
+/// A dispatched verification task that has not reported yet
+struct InFlightTask {
+    quest_id: String,
+    abort: AbortHandle,
+    /// Shares its job with the other tasks of a batch, so cancelling it
+    /// only drops its report
+    in_batch: bool,
+    /// Set once the job holds a concurrency slot; until then it is queued
+    started: Arc<AtomicBool>,
+}
+
+/// How a coordinator session ended
+enum SessionEnd {
+    /// Shutdown was requested; carries the writer if the connection is
+    /// still usable for final results and deregistration
+    Shutdown(Option<JoinHandle<Result<WsSink, tokio_tungstenite::tungstenite::Error>>>),
+    /// The connection dropped or went silent
+    Disconnected,
+}
+
+/// What an active-active link tells the executor
+enum LinkEvent {
+    /// Connected and registered; control replies go to `replies`, framed
+    /// in `encoding`
+    Up {
+        link: usize,
+        replies: Arc<OutboundQueue>,
+        encoding: Arc<FrameEncoding>,
+        negotiated: Negotiated,
+    },
+    /// A data frame from the coordinator
+    Message { link: usize, frame: Frame },
+    /// The connection is gone
+    Down { link: usize },
+}
+
+/// Write an active-active link's replies, plus shared results while the
+/// link is primary. Once `control` is closed a primary first flushes the
+/// results still to come, until `results` is closed too, then the sink is
+/// handed back.
+async fn link_writer(
+    link: usize,
+    mut write: WsSink,
+    control: Arc<OutboundQueue>,
+    results: Arc<OutboundQueue>,
+    mut primary: watch::Receiver<Option<usize>>,
+    encoding: Arc<FrameEncoding>,
+    auth: CoordinatorAuth,
+) -> Result<WsSink, tokio_tungstenite::tungstenite::Error> {
+    let seal = |message| match message {
+        Message::Text(text) => encode_frame(auth.seal(text), encoding.get()),
+        other => other,
+    };
+    let mut is_primary = *primary.borrow_and_update() == Some(link);
+    loop {
+        let message = tokio::select! {
+            biased;
+            message = control.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            Ok(()) = primary.changed() => {
+                is_primary = *primary.borrow_and_update() == Some(link);
+                continue;
+            }
+            Some(message) = results.recv(), if is_primary => message,
+        };
+        write.send(seal(message)).await?;
+    }
+    if is_primary {
+        while let Some(message) = results.recv().await {
+            write.send(seal(message)).await?;
+        }
+    }
+    Ok(write)
+}
+
+/// Payload of a data frame from the coordinator
+enum Frame {
+    /// JSON
+    Text(String),
+    /// MessagePack or CBOR, told apart by [`cbor::is_cbor`]
+    Binary(Vec<u8>),
+}
+
+/// Which encoding a binary frame is in
+fn binary_encoding(bytes: &[u8]) -> Encoding {
+    if cbor::is_cbor(bytes) {
+        Encoding::Cbor
+    } else {
+        Encoding::Msgpack
+    }
+}
+
+fn decode_binary(bytes: &[u8]) -> Result<Value, String> {
+    match binary_encoding(bytes) {
+        Encoding::Cbor => cbor::decode(bytes),
+        _ => msgpack::decode(bytes),
+    }
+}
+
+/// `type` of a frame, if it decodes to a message that has one
+fn frame_type(frame: &Frame) -> Option<String> {
+    let message = match frame {
+        Frame::Text(text) => serde_json::from_str::<Value>(text).ok()?,
+        Frame::Binary(bytes) => decode_binary(bytes).ok()?,
+    };
+    message.get("type")?.as_str().map(str::to_string)
+}
+
+/// What a coordinator accepted in its `register_ack`
+#[derive(Debug, Clone, Default)]
+struct Negotiated {
+    protocol_version: Option<u32>,
+    /// `None` when the coordinator didn't say, which accepts everything
+    /// offered
+    accepted_capabilities: Option<HashSet<String>>,
+}
+
+impl Negotiated {
+    fn accepts(&self, capability: &str) -> bool {
+        self.accepted_capabilities
+            .as_ref()
+            .is_none_or(|accepted| accepted.contains(capability))
+    }
+}
+
+/// A registered connection
+struct Registration {
+    negotiated: Negotiated,
+    /// The acknowledgement, then any frames that arrived before it
+    frames: Vec<Frame>,
+}
+
+/// The connection a frame came in on, as far as `dispatch` cares
+#[derive(Clone, Copy)]
+struct Peer<'a> {
+    /// Tasks are only taken from the primary
+    primary: bool,
+    negotiated: &'a Negotiated,
+}
+
+/// Why the coordinator refused to register the agent, if that is how
+/// connecting failed
+fn rejection_reason<'a>(e: &'a (dyn std::error::Error + 'static)) -> Option<&'a str> {
+    match e.downcast_ref::<VerifierError>() {
+        Some(VerifierError::RegistrationRejected(reason)) => Some(reason),
+        _ => None,
+    }
+}
+
+/// Encoding a connection's outbound messages are framed in. JSON until the
+/// coordinator picks another in `register_ack`; after that, whatever its
+/// latest binary frame was in, so tasks sent as MessagePack or CBOR are
+/// answered the same way. Text frames leave it as it is.
+#[derive(Default)]
+struct FrameEncoding(AtomicU8);
+
+impl FrameEncoding {
+    fn get(&self) -> Encoding {
+        match self.0.load(Ordering::Relaxed) {
+            1 => Encoding::Msgpack,
+            2 => Encoding::Cbor,
+            _ => Encoding::Json,
+        }
+    }
+
+    fn set(&self, encoding: Encoding) {
+        let tag = match encoding {
+            Encoding::Json => 0,
+            Encoding::Msgpack => 1,
+            Encoding::Cbor => 2,
+        };
+        self.0.store(tag, Ordering::Relaxed);
+    }
+}
+
+/// Frame an outbound message: as is for JSON, or re-encoded as MessagePack
+/// or CBOR
+fn encode_frame(text: String, encoding: Encoding) -> Message {
+    let encode = match encoding {
+        Encoding::Json => return Message::Text(text),
+        Encoding::Msgpack => msgpack::encode,
+        Encoding::Cbor => cbor::encode,
+    };
+    match serde_json::from_str::<Value>(&text) {
+        Ok(value) => Message::Binary(encode(&value)),
+        Err(e) => {
+            warn!(error = %e, "Sending unparseable message as text");
+            Message::Text(text)
+        }
+    }
+}
+
+/// Frame what a connection writes from now on in `encoding`
+fn use_encoding(current: &FrameEncoding, encoding: Encoding, url: &str) {
+    info!(url = %url, encoding = encoding.as_str(), "Coordinator picked an encoding");
+    current.set(encoding);
+}
+
+/// Answer in the encoding of `frame` if it is binary. Called before the
+/// frame is dispatched, so even an immediate reply goes out in kind.
+fn follow_frame(current: &FrameEncoding, frame: &Frame, url: &str) {
+    let Frame::Binary(bytes) = frame else { return };
+    let encoding = binary_encoding(bytes);
+    if current.get() != encoding {
+        info!(url = %url, encoding = encoding.as_str(), "Answering in the coordinator's encoding");
+        current.set(encoding);
+    }
+}
+
+/// `report` with the duplicate flag set, which its signature doesn't cover
+fn mark_duplicate(report: &str) -> String {
+    match serde_json::from_str::<Value>(report) {
+        Ok(mut message @ Value::Object(_)) => {
+            message[protocol::DUPLICATE_FIELD] = json!(true);
+            message.to_string()
+        }
+        _ => report.to_string(),
+    }
+}
+
+/// Queue a message for the coordinator
+fn send(responses: &OutboundQueue, message: &AgentMessage) {
+    match message.to_json() {
+        Ok(text) => responses.push(Message::Text(text)),
+        Err(e) => error!(error = %e, "Failed to encode outgoing message"),
+    }
+}
+
+/// Resolves on SIGINT, or SIGTERM on Unix
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Run `future` to completion, or give up at `deadline`
+async fn within<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Parse an environment variable, ignoring it if unset or malformed
+fn env_parse<T: FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().and_then(|v| v.parse().ok())
+}
+
+/// Confidence threshold from the environment, clamped to 0-100; garbage is
+/// reported and ignored
+fn env_confidence(key: &str) -> Option<u8> {
+    let raw = env::var(key).ok()?;
+    match protocol::parse_confidence(&raw) {
+        Ok(threshold) => Some(threshold),
+        Err(e) => {
+            warn!(key, value = %raw, error = %e, "Ignoring invalid confidence threshold");
+            None
+        }
+    }
+}
+
+/// Positive duration from a milliseconds variable, or failing that a
+/// seconds variable
+fn env_duration(ms_key: &str, secs_key: &str) -> Option<Duration> {
+    env_positive(ms_key)
+        .map(|ms| Duration::from_millis(ms as u64))
+        .or_else(|| env_positive(secs_key).map(|secs| Duration::from_secs(secs as u64)))
+}
+
+/// Like `env_parse`, but also ignores zero
+fn env_positive(key: &str) -> Option<usize> {
+    env_parse::<usize>(key).filter(|n| *n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent() -> VerifierAgent {
+        agent_with(VerifierConfig::default())
+    }
+
+    /// Hash-only agent with no chunk age limit and nothing on disk
+    fn agent_with(config: VerifierConfig) -> VerifierAgent {
+        VerifierAgent::builder()
+            .config(VerifierConfig {
+                mode: VerifierMode::HashOnly,
+                max_chunk_age: None,
+                data_dir: PathBuf::new(),
+                ..config
+            })
+            .eigen_compute(EigenCompute::new(EigenComputeConfig::default()))
+            .identity(AgentIdentity::generate())
+            .build()
+            .unwrap()
+    }
+
+    fn hash_of(data: &Value) -> String {
+        HashAlgorithm::default().digest_hex(&HashMode::Canonical.encode(data).unwrap())
+    }
+
+    /// Chunk claiming the hash of its data
+    fn chunk(source: &str, data: Value) -> Value {
+        json!({ "source": source, "hash": hash_of(&data), "data": data, "timestamp": 1 })
+    }
+
+    fn task(data: Vec<Value>, expected: Vec<String>) -> VerifyTask {
+        serde_json::from_value(json!({
+            "questId": "q1",
+            "data": data,
+            "expectedHashes": expected,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn hashes_compare_in_any_case() {
+        let data = json!({ "price": 1 });
+        let upper = hash_of(&data).to_ascii_uppercase();
+        let mut sent = chunk("a", data);
+        sent["hash"] = json!(upper);
+
+        let result = agent().verify(task(vec![sent], vec![upper])).await.unwrap();
+        assert_eq!(result.verified_chunks.len(), 1);
+        assert!(result.failed_chunks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn an_expected_hash_without_a_chunk_lowers_confidence() {
+        let a = chunk("a", json!({ "price": 1 }));
+        let missing = hash_of(&json!({ "price": 2 }));
+        let expected = vec![a["hash"].as_str().unwrap().to_string(), missing];
+
+        let result = agent().verify(task(vec![a], expected)).await.unwrap();
+        assert_eq!(result.status, "partial");
+        assert_eq!(result.raw_confidence, Some(50));
+        assert_eq!(result.verified_chunks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_repeated_chunk_counts_once() {
+        let a = chunk("a", json!({ "price": 1 }));
+        let expected = vec![a["hash"].as_str().unwrap().to_string()];
+
+        let result = agent()
+            .verify(task(vec![a.clone(), a], expected))
+            .await
+            .unwrap();
+        assert_eq!(result.raw_confidence, Some(100));
+        assert_eq!(result.verified_chunks.len(), 1);
+    }
+
+    /// Even chunks verify and odd ones claim another chunk's hash
+    fn interleaved(n: usize) -> (Vec<Value>, Vec<String>) {
+        let data: Vec<Value> = (0..n)
+            .map(|i| {
+                let mut sent = chunk(&format!("s{}", i), json!({ "n": i }));
+                if i % 2 == 1 {
+                    sent["hash"] = json!(hash_of(&json!({ "n": i + 1000 })));
+                }
+                sent
+            })
+            .collect();
+        let hashes = data
+            .iter()
+            .map(|c| c["hash"].as_str().unwrap().to_string())
+            .collect();
+        (data, hashes)
+    }
+
+    #[tokio::test]
+    async fn keeps_chunk_order_with_interleaved_results() {
+        let (data, hashes) = interleaved(64);
+        let passing: Vec<String> = hashes.iter().step_by(2).cloned().collect();
+        let failing: Vec<String> = hashes.iter().skip(1).step_by(2).cloned().collect();
+
+        // Hashed inline, then on the worker pool
+        for parallel_threshold in [usize::MAX, 4] {
+            let agent = agent_with(VerifierConfig {
+                parallel_threshold,
+                ..VerifierConfig::default()
+            });
+            let result = agent
+                .verify(task(data.clone(), hashes.clone()))
+                .await
+                .unwrap();
+            assert_eq!(result.verified_chunks, passing);
+            assert_eq!(result.failed_chunks, failing);
+            assert_eq!(result.raw_confidence, Some(50));
+            let reasons: Vec<_> = result
+                .failure_details
+                .iter()
+                .map(|f| (f.hash.clone(), f.reason))
+                .collect();
+            let expected: Vec<_> = failing
+                .iter()
+                .map(|hash| (hash.clone(), FailureReason::HashMismatch))
+                .collect();
+            assert_eq!(reasons, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn hashes_a_large_task_on_a_current_thread_runtime() {
+        let n = DEFAULT_PARALLEL_THRESHOLD;
+        let (data, hashes) = interleaved(n);
+        let result = agent().verify(task(data, hashes)).await.unwrap();
+        assert_eq!(result.verified_chunks.len(), n / 2);
+        assert_eq!(result.failed_chunks.len(), n / 2);
+    }
+
+    #[test]
+    fn json_and_binary_messages_both_need_a_mac() {
+        let agent = agent_with(VerifierConfig {
+            auth_token: Some("secret".to_string()),
+            ..VerifierConfig::default()
+        });
+        let message = json!({
+            "type": "verify_task",
+            "questId": "q1",
+            "data": [],
+            "expectedHashes": [],
+        });
+        let rejected = |reply: Result<CoordinatorMessage, Box<AgentMessage>>| match reply {
+            Err(reply) => match *reply {
+                AgentMessage::TaskFailed(failure) => {
+                    failure.code.as_deref() == Some("message_auth_failed")
+                }
+                _ => false,
+            },
+            Ok(_) => false,
+        };
+        assert!(rejected(agent.parse_message(&message.to_string())));
+        assert!(rejected(agent.parse_binary(&msgpack::encode(&message))));
+        assert!(rejected(agent.parse_binary(&cbor::encode(&message))));
+
+        let sealer = CoordinatorAuth::new(Some("secret".to_string()), MessageAuth::Hmac).unwrap();
+        let sealed: Value = serde_json::from_str(&sealer.seal(message.to_string())).unwrap();
+        assert!(agent.parse_message(&sealed.to_string()).is_ok());
+        assert!(agent.parse_binary(&msgpack::encode(&sealed)).is_ok());
+        assert!(agent.parse_binary(&cbor::encode(&sealed)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn repeated_task_is_verified_once_and_answered_as_duplicate() {
+        let agent = Arc::new(agent());
+        let data = json!({ "price": 1 });
+        let frame = Frame::Text(
+            json!({
+                "type": "verify_task",
+                "questId": "q1",
+                "data": [chunk("a", data.clone())],
+                "expectedHashes": [hash_of(&data)],
+            })
+            .to_string(),
+        );
+        let queue = Arc::new(OutboundQueue::new(16, OverflowPolicy::default()));
+        let slots = Arc::new(Semaphore::new(4));
+        let negotiated = Negotiated::default();
+        let mut tasks = JoinSet::new();
+        for delivery in 0..3 {
+            let peer = Peer {
+                primary: true,
+                negotiated: &negotiated,
+            };
+            let handed_back = agent.dispatch(&frame, &queue, &queue, peer, &slots, &mut tasks);
+            assert!(handed_back.is_none());
+            // Only the first delivery starts a verification
+            assert_eq!(tasks.len(), usize::from(delivery == 0));
+            while tasks.join_next().await.is_some() {}
+        }
+
+        let mut reports = Vec::new();
+        for _ in 0..3 {
+            let Some(Message::Text(text)) = queue.recv().await else {
+                panic!("expected a text report");
+            };
+            reports.push(serde_json::from_str::<Value>(&text).unwrap());
+        }
+        assert_eq!(reports[0]["type"], "task_result");
+        assert!(reports[0].get(protocol::DUPLICATE_FIELD).is_none());
+        for report in &reports[1..] {
+            assert_eq!(report[protocol::DUPLICATE_FIELD], json!(true));
+            let mut unflagged = report.clone();
+            unflagged
+                .as_object_mut()
+                .unwrap()
+                .remove(protocol::DUPLICATE_FIELD);
+            assert_eq!(unflagged, reports[0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_task_is_verified_again_when_repeated() {
+        let agent = Arc::new(agent());
+        let data = json!({ "price": 1 });
+        let task = |quest_id: &str| {
+            json!({
+                "type": "verify_task",
+                "questId": quest_id,
+                "data": [chunk("a", data.clone())],
+                "expectedHashes": [hash_of(&data)],
+            })
+        };
+        let batch =
+            json!({ "type": "verify_batch", "batchId": "b1", "tasks": [task("q1"), task("q2")] });
+        let cancel = json!({ "type": "cancel_task", "questId": "q1" });
+        let queue = Arc::new(OutboundQueue::new(16, OverflowPolicy::default()));
+        // No slot yet, so the batch is still queued when q1 is cancelled
+        let slots = Arc::new(Semaphore::new(0));
+        let negotiated = Negotiated::default();
+        let peer = Peer {
+            primary: true,
+            negotiated: &negotiated,
+        };
+        let mut tasks = JoinSet::new();
+        let dispatch = |message: &Value, tasks: &mut JoinSet<()>| {
+            let frame = Frame::Text(message.to_string());
+            agent.dispatch(&frame, &queue, &queue, peer, &slots, tasks);
+        };
+        let next_report = || async {
+            let Some(Message::Text(text)) = queue.recv().await else {
+                panic!("expected a text report");
+            };
+            serde_json::from_str::<Value>(&text).unwrap()
+        };
+
+        // A batch runs on for its other tasks, so q1 is still verified
+        dispatch(&batch, &mut tasks);
+        dispatch(&cancel, &mut tasks);
+        slots.add_permits(1);
+        while tasks.join_next().await.is_some() {}
+        let cancelled = next_report().await;
+        assert_eq!(cancelled["questId"], "q1");
+        assert_eq!(cancelled["status"], "cancelled");
+        assert_eq!(next_report().await["questId"], "q2");
+
+        // but its result is neither sent nor kept to answer a repeat
+        dispatch(&task("q1"), &mut tasks);
+        assert_eq!(tasks.len(), 1);
+        while tasks.join_next().await.is_some() {}
+        let report = next_report().await;
+        assert_eq!(report["questId"], "q1");
+        assert_eq!(report["status"], SIMULATED_VERIFIED_STATUS);
+        assert!(report.get(protocol::DUPLICATE_FIELD).is_none());
+
+        // A settled one is
+        dispatch(&task("q2"), &mut tasks);
+        assert!(tasks.is_empty());
+        assert_eq!(next_report().await[protocol::DUPLICATE_FIELD], json!(true));
+    }
+
+    #[tokio::test]
+    async fn cbor_task_is_answered_in_cbor() {
+        let agent = Arc::new(agent());
+        let data = json!({ "price": 1 });
+        let frame = Frame::Binary(cbor::encode(&json!({
+            "type": "verify_task",
+            "questId": "q1",
+            "data": [chunk("a", data.clone())],
+            "expectedHashes": [hash_of(&data)],
+        })));
+        let queue = Arc::new(OutboundQueue::new(16, OverflowPolicy::default()));
+        let slots = Arc::new(Semaphore::new(4));
+        let negotiated = Negotiated::default();
+        let peer = Peer {
+            primary: true,
+            negotiated: &negotiated,
+        };
+        let encoding = FrameEncoding::default();
+        let mut tasks = JoinSet::new();
+
+        // As a session reads a frame
+        follow_frame(&encoding, &frame, "ws://coordinator");
+        let handed_back = agent.dispatch(&frame, &queue, &queue, peer, &slots, &mut tasks);
+        assert!(handed_back.is_none());
+        while tasks.join_next().await.is_some() {}
+
+        // and as its writer frames the report
+        let Some(Message::Text(text)) = queue.recv().await else {
+            panic!("expected a report");
+        };
+        let Message::Binary(bytes) = encode_frame(text, encoding.get()) else {
+            panic!("report was not framed as binary");
+        };
+        assert!(cbor::is_cbor(&bytes));
+        let report = cbor::decode(&bytes).unwrap();
+        assert_eq!(report["type"], "task_result");
+        assert_eq!(report["questId"], "q1");
+        assert_eq!(report["verifiedChunks"], json!([hash_of(&data)]));
+    }
+
+    /// Task of `n` chunks, the first `passing` of which match their hash,
+    /// asking for `min_confidence`
+    fn scored_task(n: usize, passing: usize, min_confidence: u8) -> VerifyTask {
+        let data: Vec<Value> = (0..n)
+            .map(|i| {
+                let mut sent = chunk(&format!("s{}", i), json!({ "n": i }));
+                if i >= passing {
+                    sent["hash"] = json!(hash_of(&json!({ "n": i + 1000 })));
+                }
+                sent
+            })
+            .collect();
+        let expected = data
+            .iter()
+            .map(|c| c["hash"].as_str().unwrap().to_string())
+            .collect();
+        let mut task = task(data, expected);
+        task.min_confidence = Some(min_confidence);
+        task
+    }
+
+    #[tokio::test]
+    async fn rejects_a_task_without_chunks() {
+        let err = agent().verify(task(vec![], vec![])).await.unwrap_err();
+        assert_eq!(err.code(), "empty_task");
+    }
+
+    #[tokio::test]
+    async fn fails_a_task_whose_chunks_all_fail() {
+        let result = agent().verify(scored_task(4, 0, 50)).await.unwrap();
+        assert_eq!(result.status, "failed");
+        assert_eq!(result.raw_confidence, Some(0));
+        assert_eq!(result.attestation.as_ref().unwrap().confidence_score, 0);
+        assert_eq!(result.min_confidence, 50);
+        assert_eq!(result.confidence_floor, 50);
+        assert!(result.verified_chunks.is_empty());
+        assert_eq!(result.failed_chunks.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn confidence_exactly_at_the_threshold_verifies() {
+        // 57 / 100 * 100 is 56.99999999999999 in floating point
+        let at = agent().verify(scored_task(100, 57, 57)).await.unwrap();
+        assert_eq!(at.attestation.as_ref().unwrap().confidence_score, 57);
+        assert_eq!(at.raw_confidence, Some(57));
+        assert_eq!(at.status, SIMULATED_VERIFIED_STATUS);
+
+        let below = agent().verify(scored_task(100, 56, 57)).await.unwrap();
+        assert_eq!(below.attestation.as_ref().unwrap().confidence_score, 56);
+        assert_ne!(below.status, SIMULATED_VERIFIED_STATUS);
+    }
+
+    #[test]
+    fn confidence_rounds_and_clamps() {
+        assert_eq!(percent(1.0, 0.0), 0.0);
+        assert_eq!(to_confidence(percent(57.0, 100.0)), 57);
+        assert_eq!(to_confidence(94.5), 95);
+        assert_eq!(to_confidence(94.49), 94);
+        assert_eq!(to_confidence(f64::NAN), 0);
+        assert_eq!(to_confidence(-3.0), 0);
+        assert_eq!(to_confidence(250.0), 100);
+    }
+}
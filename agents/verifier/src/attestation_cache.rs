@@ -0,0 +1,100 @@
+//! Cache of recent TEE attestations
+//!
+//! A re-dispatched quest produces the same aggregate hash, and without a
+//! challenge nonce the attestation covers only that hash, so a still-fresh
+//! attestation for the same hash and algorithm can be reused instead of
+//! paying for another TEE call. Nonce-bound attestations answer one
+//! challenge and are never cached, so the cache only serves agents that
+//! run with fresh nonces off. Entries expire after `ttl`; when full, the
+//! least recently used entry is evicted.
+
+use crate::eigencloud_sdk::AttestationResponse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Aggregate hash and the algorithm that produced it
+type CacheKey = (String, String);
+
+struct Entry {
+    response: AttestationResponse,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+struct Inner {
+    entries: HashMap<CacheKey, Entry>,
+    /// Monotonic use counter for LRU ordering
+    clock: u64,
+}
+
+/// LRU cache of attestations keyed by aggregate hash and algorithm
+pub struct AttestationCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl AttestationCache {
+    /// A capacity of zero disables caching
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Fresh attestation for `data_hash` under `algorithm`, if cached
+    pub fn get(&self, data_hash: &str, algorithm: &str) -> Option<AttestationResponse> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let key = (data_hash.to_string(), algorithm.to_string());
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let entry = inner.entries.get_mut(&key)?;
+        if entry.stored_at.elapsed() >= self.ttl {
+            inner.entries.remove(&key);
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.response.clone())
+    }
+
+    pub fn insert(&self, data_hash: &str, algorithm: &str, response: AttestationResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = (data_hash.to_string(), algorithm.to_string());
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let ttl = self.ttl;
+        inner.entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.entries.insert(
+            key,
+            Entry {
+                response,
+                stored_at: Instant::now(),
+                last_used: clock,
+            },
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Append-only audit log of verification results
+//!
+//! With `ATTESTATION_LOG_PATH` set, every signed `task_result` the agent
+//! produces is appended to that file as one JSON line, before it is sent:
+//!
+//! ```json
+//! {"loggedAt":1730000000,"questId":"q1","result":{"type":"task_result",...}}
+//! ```
+//!
+//! `result` is the message exactly as signed, attestation included, so a
+//! line can be checked against the agent key long after the fact. Lines
+//! are never rewritten.
+//!
+//! `ATTESTATION_LOG_FSYNC_EVERY` trades durability for throughput: the file
+//! is synced to disk after that many records (1, the default, syncs every
+//! record), and zero leaves syncing to the OS. Whatever is unsynced is
+//! synced at shutdown. With `ATTESTATION_LOG_MAX_BYTES` set, a file that
+//! would grow past it is renamed to `<path>.<unix millis>` and a new one is
+//! started; rotated files are never deleted.
+//!
+//! A record that can't be written is logged and counted in
+//! `verifier_attestation_log_errors_total`; the result is still sent.
+
+use crate::error::VerifierError;
+use crate::metrics::METRICS;
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+/// Records per fsync unless `ATTESTATION_LOG_FSYNC_EVERY` says otherwise
+pub const DEFAULT_ATTESTATION_LOG_FSYNC_EVERY: u32 = 1;
+
+struct Inner {
+    file: File,
+    /// Bytes in the current file
+    len: u64,
+    /// Records written since the last sync
+    unsynced: u32,
+}
+
+/// JSON-lines file of signed results
+pub struct AttestationLog {
+    path: PathBuf,
+    /// Records per fsync; zero never syncs explicitly
+    fsync_every: u32,
+    /// Size at which the file is rotated, if any
+    max_bytes: Option<u64>,
+    inner: Mutex<Inner>,
+}
+
+impl AttestationLog {
+    /// Open `path` for appending, creating it and its directory if needed
+    pub fn open(
+        path: PathBuf,
+        fsync_every: u32,
+        max_bytes: Option<u64>,
+    ) -> Result<Self, VerifierError> {
+        let (file, len) = open_append(&path).map_err(|e| {
+            VerifierError::InvalidConfig(format!(
+                "cannot open attestation log {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        info!(
+            path = %path.display(),
+            fsync_every,
+            max_bytes,
+            "Writing attestation log"
+        );
+        Ok(Self {
+            path,
+            fsync_every,
+            max_bytes,
+            inner: Mutex::new(Inner {
+                file,
+                len,
+                unsynced: 0,
+            }),
+        })
+    }
+
+    /// Append the signed result `message` for `quest_id`
+    pub fn append(&self, quest_id: &str, message: &str) {
+        if let Err(e) = self.try_append(quest_id, message) {
+            METRICS
+                .attestation_log_errors
+                .fetch_add(1, Ordering::Relaxed);
+            error!(
+                quest_id,
+                error = %e,
+                path = %self.path.display(),
+                "Failed to write attestation log"
+            );
+        }
+    }
+
+    fn try_append(&self, quest_id: &str, message: &str) -> io::Result<()> {
+        let result: Value = serde_json::from_str(message)?;
+        let mut line = serde_json::to_vec(&json!({
+            "loggedAt": unix_now(),
+            "questId": quest_id,
+            "result": result,
+        }))?;
+        line.push(b'\n');
+
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(max) = self.max_bytes {
+            if inner.len > 0 && inner.len + line.len() as u64 > max {
+                self.rotate(&mut inner)?;
+            }
+        }
+        // One write per record, so a crash can cut off at most the last line
+        inner.file.write_all(&line)?;
+        inner.len += line.len() as u64;
+        inner.unsynced += 1;
+        if self.fsync_every > 0 && inner.unsynced >= self.fsync_every {
+            inner.file.sync_data()?;
+            inner.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    /// Move the full file aside and start a new one
+    fn rotate(&self, inner: &mut Inner) -> io::Result<()> {
+        inner.file.sync_all()?;
+        // Never replace a file rotated in the same millisecond
+        let mut stamp = unix_millis();
+        let rotated = loop {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(format!(".{}", stamp));
+            let rotated = PathBuf::from(rotated);
+            if !rotated.exists() {
+                break rotated;
+            }
+            stamp += 1;
+        };
+        fs::rename(&self.path, &rotated)?;
+        let (file, len) = open_append(&self.path)?;
+        *inner = Inner {
+            file,
+            len,
+            unsynced: 0,
+        };
+        info!(rotated = %rotated.display(), "Rotated attestation log");
+        Ok(())
+    }
+
+    /// Sync records still waiting for their batch
+    pub fn sync(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.unsynced == 0 {
+            return;
+        }
+        match inner.file.sync_data() {
+            Ok(()) => inner.unsynced = 0,
+            Err(e) => error!(
+                error = %e,
+                path = %self.path.display(),
+                "Failed to sync attestation log"
+            ),
+        }
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<(File, u64)> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let len = file.metadata()?.len();
+    Ok((file, len))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
@@ -0,0 +1,103 @@
+//! Circuit breaker for TEE container calls
+//!
+//! After `threshold` consecutive failures the breaker opens and calls fail
+//! fast for `cooldown`. The first call after the cooldown is let through as
+//! a trial; its outcome closes the breaker again or restarts the cooldown.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Externally visible breaker state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+struct Inner {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+/// Consecutive-failure circuit breaker
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner {
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_in_flight: false,
+            }),
+        }
+    }
+
+    /// Whether a call may proceed; while open this also returns how long
+    /// until a trial call is allowed
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(opened_at) = inner.opened_at else {
+            return Ok(());
+        };
+        let elapsed = opened_at.elapsed();
+        if elapsed < self.cooldown {
+            return Err(self.cooldown - elapsed);
+        }
+        // Half-open: let exactly one trial through
+        if inner.trial_in_flight {
+            return Err(Duration::ZERO);
+        }
+        inner.trial_in_flight = true;
+        Ok(())
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.trial_in_flight = false;
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        if inner.trial_in_flight || inner.consecutive_failures >= self.threshold {
+            if inner.opened_at.is_none() || inner.trial_in_flight {
+                tracing::warn!(
+                    consecutive_failures = inner.consecutive_failures,
+                    "TEE circuit breaker open"
+                );
+            }
+            inner.opened_at = Some(Instant::now());
+            inner.trial_in_flight = false;
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Canonical JSON serialization (RFC 8785 / JCS)
+//!
+//! Produces a byte-stable encoding of a JSON value so that semantically
+//! identical payloads hash identically regardless of the producer:
+//! - object keys sorted by UTF-16 code units
+//! - no insignificant whitespace
+//! - numbers formatted as ECMAScript `Number.prototype.toString`
+//! - minimal string escaping
+
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use std::io::{self, Write};
+
+/// How a chunk's `data` is turned into bytes before hashing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashMode {
+    /// JCS canonical form; key order and number formatting don't matter
+    #[default]
+    Canonical,
+    /// Compact serde_json output, for producers that hash those exact bytes
+    Raw,
+}
+
+impl HashMode {
+    /// Encode a value according to this mode
+    ///
+    /// Fails rather than yielding empty bytes, which would hash to a value
+    /// a producer could claim without having any data.
+    pub fn encode(self, value: &Value) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        self.encode_to(value, &mut out)?;
+        Ok(out)
+    }
+
+    /// Encode a value according to this mode straight into `out`, so a
+    /// hasher can consume it without an intermediate buffer
+    pub fn encode_to<W: Write>(self, value: &Value, out: &mut W) -> Result<(), String> {
+        match self {
+            HashMode::Canonical => canonicalize_to(value, out).map_err(|e| e.to_string()),
+            HashMode::Raw => serde_json::to_writer(out, value).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Serialize a JSON value into its canonical byte form
+///
+/// `{"b":2,"a":1}` and `{"a":1,"b":2}` both encode to `{"a":1,"b":2}`,
+/// and `1.0` encodes to `1`.
+pub fn canonicalize(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    canonicalize_to(value, &mut out).expect("writing to a Vec cannot fail");
+    out
+}
+
+/// Write the canonical byte form of a JSON value to `out`
+pub fn canonicalize_to<W: Write>(value: &Value, out: &mut W) -> io::Result<()> {
+    write_value(value, out)
+}
+
+fn write_value<W: Write>(value: &Value, out: &mut W) -> io::Result<()> {
+    match value {
+        Value::Null => out.write_all(b"null"),
+        Value::Bool(true) => out.write_all(b"true"),
+        Value::Bool(false) => out.write_all(b"false"),
+        Value::Number(n) => write_number(n, out),
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.write_all(b"[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",")?;
+                }
+                write_value(item, out)?;
+            }
+            out.write_all(b"]")
+        }
+        Value::Object(map) => write_object(map, out),
+    }
+}
+
+fn write_object<W: Write>(map: &Map<String, Value>, out: &mut W) -> io::Result<()> {
+    // JCS orders keys by their UTF-16 representation, which differs from
+    // byte order for characters outside the Basic Multilingual Plane
+    let mut entries: Vec<(Vec<u16>, &String, &Value)> = map
+        .iter()
+        .map(|(k, v)| (k.encode_utf16().collect(), k, v))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    out.write_all(b"{")?;
+    for (i, (_, key, value)) in entries.into_iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        write_string(key, out)?;
+        out.write_all(b":")?;
+        write_value(value, out)?;
+    }
+    out.write_all(b"}")
+}
+
+fn write_string<W: Write>(s: &str, out: &mut W) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\u{08}' => out.write_all(b"\\b")?,
+            '\t' => out.write_all(b"\\t")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\u{0C}' => out.write_all(b"\\f")?,
+            '\r' => out.write_all(b"\\r")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => {
+                let mut buf = [0u8; 4];
+                out.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+            }
+        }
+    }
+    out.write_all(b"\"")
+}
+
+fn write_number<W: Write>(n: &Number, out: &mut W) -> io::Result<()> {
+    // JCS treats every number as an IEEE 754 double
+    let v = n.as_f64().unwrap_or(0.0);
+    out.write_all(format_es6_number(v).as_bytes())
+}
+
+/// Rust picks the upper of two shortest digit strings equally near `v`;
+/// ECMAScript picks the even one. `digits` are the significant digits of
+/// positive `v` and `exponent` the power of ten of the first.
+fn break_tie_to_even(v: f64, digits: String, exponent: i32) -> String {
+    let (head, last) = digits.split_at(digits.len() - 1);
+    let last = last.as_bytes()[0] - b'0';
+    if last.is_multiple_of(2) {
+        return digits;
+    }
+    let shift = exponent - head.len() as i32;
+    let neighbours = [last - 1, last + 1]
+        .into_iter()
+        .filter(|d| *d <= 9)
+        .map(|d| format!("{}{}", head, d));
+    for neighbour in neighbours {
+        if format!("{}e{}", neighbour, shift).parse::<f64>() != Ok(v) {
+            continue;
+        }
+        // A tie only if `v` is exactly halfway, which takes its full
+        // decimal expansion to see
+        let midpoint = if neighbour < digits {
+            format!("{}5", neighbour)
+        } else {
+            format!("{}5", digits)
+        };
+        let exact = format!("{:.800e}", v);
+        let exact: String = exact
+            .split_once('e')
+            .map_or(exact.as_str(), |(mantissa, _)| mantissa)
+            .chars()
+            .filter(|c| *c != '.')
+            .collect();
+        if exact.trim_end_matches('0') == midpoint {
+            return neighbour;
+        }
+    }
+    digits
+}
+
+/// Format a finite f64 the way ECMAScript's `Number.prototype.toString` does
+fn format_es6_number(v: f64) -> String {
+    if v == 0.0 {
+        return "0".to_string();
+    }
+    if v < 0.0 {
+        return format!("-{}", format_es6_number(-v));
+    }
+
+    // Rust's `{:e}` yields the shortest round-tripping digits, e.g. "1.2345e6"
+    let sci = format!("{:e}", v);
+    let (mantissa, exponent) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let digits = break_tie_to_even(v, digits, exponent);
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+
+    if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (int_part, frac_part) = digits.split_at(n as usize);
+        format!("{}.{}", int_part, frac_part)
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat((-n) as usize), digits)
+    } else {
+        let sign = if n - 1 < 0 { '-' } else { '+' };
+        let (first, rest) = digits.split_at(1);
+        if rest.is_empty() {
+            format!("{}e{}{}", first, sign, (n - 1).abs())
+        } else {
+            format!("{}.{}e{}{}", first, rest, sign, (n - 1).abs())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn canonical(value: &Value) -> String {
+        String::from_utf8(canonicalize(value)).unwrap()
+    }
+
+    #[test]
+    fn sorts_keys_and_drops_whitespace() {
+        let value: Value =
+            serde_json::from_str(r#"{ "b": 2, "a": [1, 2], "c": { "y": null, "x": true } }"#)
+                .unwrap();
+        assert_eq!(
+            canonical(&value),
+            r#"{"a":[1,2],"b":2,"c":{"x":true,"y":null}}"#
+        );
+    }
+
+    #[test]
+    fn key_order_does_not_change_the_bytes() {
+        let a: Value =
+            serde_json::from_str(r#"{"price":1,"source":"x","meta":{"k":1,"j":2}}"#).unwrap();
+        let b: Value =
+            serde_json::from_str(r#"{"meta":{"j":2,"k":1},"source":"x","price":1}"#).unwrap();
+        assert_eq!(canonicalize(&a), canonicalize(&b));
+    }
+
+    #[test]
+    fn keeps_array_order() {
+        assert_eq!(canonical(&json!([3, 1, 2])), "[3,1,2]");
+        assert_eq!(canonical(&json!([])), "[]");
+        assert_eq!(canonical(&json!({})), "{}");
+    }
+
+    #[test]
+    fn writes_integral_floats_as_integers() {
+        assert_eq!(canonical(&json!(1.0)), "1");
+        assert_eq!(canonical(&json!(-0.0)), "0");
+        assert_eq!(canonical(&json!(4.5)), "4.5");
+        assert_eq!(canonical(&json!(-12)), "-12");
+    }
+
+    #[test]
+    fn writes_literals() {
+        assert_eq!(canonical(&json!([null, true, false])), "[null,true,false]");
+    }
+
+    #[test]
+    fn streams_the_same_bytes() {
+        let value = json!({ "b": [1.5, "x"], "a": null });
+        let mut out = Vec::new();
+        canonicalize_to(&value, &mut out).unwrap();
+        assert_eq!(out, canonicalize(&value));
+    }
+
+    /// RFC 8785 Appendix B: IEEE 754 bit patterns and their JCS form
+    #[test]
+    fn formats_numbers_as_rfc_8785_appendix_b() {
+        let vectors: [(u64, &str); 24] = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+        for (bits, expected) in vectors {
+            let value = json!(f64::from_bits(bits));
+            assert_eq!(canonical(&value), expected, "bits {:016x}", bits);
+        }
+    }
+
+    /// RFC 8785 section 3.2.3: keys sorted by UTF-16 code units, so the
+    /// emoji (a surrogate pair) comes before U+FB33
+    #[test]
+    fn sorts_keys_by_utf16_as_rfc_8785() {
+        let value: Value = serde_json::from_str(
+            r#"{
+                "€": "Euro Sign",
+                "\r": "Carriage Return",
+                "דּ": "Hebrew Letter Dalet With Dagesh",
+                "1": "One",
+                "😀": "Emoji: Grinning Face",
+                "\u0080": "Control",
+                "ö": "Latin Small Letter O With Diaeresis"
+            }"#,
+        )
+        .unwrap();
+        // Parsing the output back would re-sort it, so the order is read
+        // off the text
+        let text = canonical(&value);
+        let positions: Vec<usize> = [
+            "Carriage Return",
+            "One",
+            "Control",
+            "Latin Small Letter O With Diaeresis",
+            "Euro Sign",
+            "Emoji: Grinning Face",
+            "Hebrew Letter Dalet With Dagesh",
+        ]
+        .iter()
+        .map(|name| text.find(name).unwrap())
+        .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", text);
+    }
+
+    /// RFC 8785 section 3.2.2: the example with numbers, escapes and
+    /// literals
+    #[test]
+    fn encodes_rfc_8785_example() {
+        let value: Value = serde_json::from_str(
+            r#"{
+                "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                "literals": [null, true, false]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            canonical(&value),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+}
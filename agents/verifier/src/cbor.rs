@@ -0,0 +1,597 @@
+//! CBOR encoding of coordinator messages
+//!
+//! The other binary framing beside [`crate::msgpack`], handled the same
+//! way: messages go through [`serde_json::Value`], so a message decoded
+//! from CBOR deserializes exactly as its JSON twin would.
+//!
+//! Only what JSON can express is understood: null, booleans, integers,
+//! floats, text strings, arrays and maps with text keys, of definite or
+//! indefinite length. Byte strings, `undefined` and other simple values
+//! are rejected, as are non-finite floats and any tag but the
+//! self-describe tag. Integers keep their integer type and floats theirs,
+//! so chunk data hashes the same either way.
+
+use serde_json::{Map, Number, Value};
+
+/// Deepest nesting decoded, as for serde_json
+const MAX_DEPTH: usize = 128;
+
+/// Tag some encoders put in front of a message to mark it as CBOR
+const SELF_DESCRIBE_TAG: u64 = 55799;
+
+/// Whether a binary frame is CBOR rather than MessagePack. Every message
+/// is a map, and a CBOR map's first byte (0xa0-0xbf) is never that of a
+/// MessagePack map (0x80-0x8f, 0xde, 0xdf); nor is the self-describe tag's.
+pub fn is_cbor(bytes: &[u8]) -> bool {
+    matches!(bytes, [0xa0..=0xbf, ..] | [0xd9, 0xd9, 0xf7, ..])
+}
+
+/// CBOR encoding of `value`, with definite lengths in their shortest form
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_text(out, s),
+        Value::Array(items) => {
+            write_head(out, 4, items.len() as u64);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            write_head(out, 5, map.len() as u64);
+            for (key, item) in map {
+                write_text(out, key);
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+fn write_text(out: &mut Vec<u8>, s: &str) {
+    write_head(out, 3, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Major type and argument: immediate up to 23, else the smallest of the
+/// 8, 16, 32 and 64-bit forms
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    match arg {
+        0..=23 => out.push(major | arg as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, arg as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(arg as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(arg as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&arg.to_be_bytes());
+        }
+    }
+}
+
+fn write_number(out: &mut Vec<u8>, n: &Number) {
+    if let Some(u) = n.as_u64() {
+        write_head(out, 0, u);
+    } else if let Some(i) = n.as_i64() {
+        // Only negative values get here; CBOR stores -1 - i
+        write_head(out, 1, (-1 - i) as u64);
+    } else {
+        out.push(0xfb);
+        out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+    }
+}
+
+/// Decode one CBOR value filling all of `bytes`
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let value = reader.value(0)?;
+    if reader.pos != bytes.len() {
+        return Err(format!(
+            "{} trailing bytes after the message",
+            bytes.len() - reader.pos
+        ));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("message ends early at byte {}", self.bytes.len()))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Argument of a head whose additional information is `info`; `None`
+    /// for indefinite length
+    fn arg(&mut self, info: u8, at: usize) -> Result<Option<u64>, String> {
+        let arg = match info {
+            0..=23 => info as u64,
+            24 => self.u8()? as u64,
+            25 => u16::from_be_bytes(self.array()?) as u64,
+            26 => u32::from_be_bytes(self.array()?) as u64,
+            27 => u64::from_be_bytes(self.array()?),
+            31 => return Ok(None),
+            _ => return Err(format!("invalid additional information at byte {}", at)),
+        };
+        Ok(Some(arg))
+    }
+
+    /// Whether the next byte is the break ending an indefinite-length
+    /// item, which is consumed if so
+    fn at_break(&mut self) -> Result<bool, String> {
+        match self.bytes.get(self.pos) {
+            Some(0xff) => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(format!("message ends early at byte {}", self.bytes.len())),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("nested deeper than {}", MAX_DEPTH));
+        }
+        let at = self.pos;
+        let initial = self.u8()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return self.simple(info, at);
+        }
+        match (major, self.arg(info, at)?) {
+            (0, Some(n)) => Ok(Value::from(n)),
+            (1, Some(n)) => i64::try_from(n)
+                .map(|n| Value::from(-1 - n))
+                .map_err(|_| format!("integer at byte {} is out of range", at)),
+            (2, _) => Err(format!("byte string at byte {}", at)),
+            (3, len) => self.text(len, at),
+            (4, len) => self.seq(len, depth),
+            (5, len) => self.map(len, depth),
+            (6, Some(SELF_DESCRIBE_TAG)) => self.value(depth + 1),
+            (6, Some(tag)) => Err(format!("tag {} at byte {}", tag, at)),
+            _ => Err(format!("indefinite length not allowed at byte {}", at)),
+        }
+    }
+
+    fn simple(&mut self, info: u8, at: usize) -> Result<Value, String> {
+        match info {
+            20 => Ok(Value::Bool(false)),
+            21 => Ok(Value::Bool(true)),
+            22 => Ok(Value::Null),
+            25 => float(half(u16::from_be_bytes(self.array()?))),
+            26 => float(f32::from_be_bytes(self.array()?) as f64),
+            27 => float(f64::from_be_bytes(self.array()?)),
+            31 => Err(format!("unexpected break at byte {}", at)),
+            _ => Err(format!("simple value {} at byte {}", info, at)),
+        }
+    }
+
+    /// Text string; one of indefinite length is a run of definite-length
+    /// chunks up to a break
+    fn text(&mut self, len: Option<u64>, at: usize) -> Result<Value, String> {
+        if let Some(len) = len {
+            return self.str(len, at).map(Value::String);
+        }
+        let mut text = String::new();
+        while !self.at_break()? {
+            let chunk_at = self.pos;
+            let initial = self.u8()?;
+            match (initial >> 5, self.arg(initial & 0x1f, chunk_at)?) {
+                (3, Some(len)) => text.push_str(&self.str(len, chunk_at)?),
+                _ => return Err(format!("invalid text chunk at byte {}", chunk_at)),
+            }
+        }
+        Ok(Value::String(text))
+    }
+
+    fn str(&mut self, len: u64, at: usize) -> Result<String, String> {
+        let bytes = self.take(usize::try_from(len).unwrap_or(usize::MAX))?;
+        std::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| format!("string at byte {} is not UTF-8", at))
+    }
+
+    fn seq(&mut self, len: Option<u64>, depth: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        match len {
+            Some(len) => {
+                // Every element takes at least a byte, so a forged length
+                // can't reserve more than the message could hold
+                let remaining = (self.bytes.len() - self.pos) as u64;
+                items.reserve(len.min(remaining) as usize);
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+            }
+            None => {
+                while !self.at_break()? {
+                    items.push(self.value(depth + 1)?);
+                }
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: Option<u64>, depth: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        let mut remaining = len;
+        loop {
+            match &mut remaining {
+                Some(0) => break,
+                Some(n) => *n -= 1,
+                None if self.at_break()? => break,
+                None => {}
+            }
+            let at = self.pos;
+            let Value::String(key) = self.value(depth + 1)? else {
+                return Err(format!("map key at byte {} is not a string", at));
+            };
+            let value = self.value(depth + 1)?;
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+/// IEEE 754 half-precision value
+fn half(bits: u16) -> f64 {
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent as i32 - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+fn float(f: f64) -> Result<Value, String> {
+    Number::from_f64(f)
+        .map(Value::Number)
+        .ok_or_else(|| format!("{} has no JSON form", f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canonical_json::HashMode;
+    use crate::msgpack;
+    use crate::{
+        AgentIdentity, EigenCompute, EigenComputeConfig, HashAlgorithm, VerifierAgent,
+        VerifierConfig, VerifierMode, VerifyTask,
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn round_trip(value: &Value) -> Value {
+        decode(&encode(value)).unwrap()
+    }
+
+    fn task_json() -> Value {
+        let data = json!({ "price": 101.25, "volume": 70000, "delta": -3, "pair": "ETH/USD" });
+        let hash = HashAlgorithm::default().digest_hex(&HashMode::Canonical.encode(&data).unwrap());
+        json!({
+            "type": "verify_task",
+            "questId": "q1",
+            "data": [
+                { "source": "a", "hash": hash, "data": data, "timestamp": 1 },
+                { "source": "b", "hash": "00", "data": { "price": null }, "timestamp": 1 },
+            ],
+            "expectedHashes": [hash, "00"],
+            "minConfidence": 50,
+            "sourceWeights": { "a": 1.5, "b": 0.25 },
+        })
+    }
+
+    #[test]
+    fn verify_task_decodes_like_its_json_twin() {
+        let json = task_json();
+        let decoded = round_trip(&json);
+        assert_eq!(decoded, json);
+        let from_cbor: VerifyTask = serde_json::from_value(decoded).unwrap();
+        let from_json: VerifyTask = serde_json::from_str(&json.to_string()).unwrap();
+        assert_eq!(from_cbor.quest_id, from_json.quest_id);
+        assert_eq!(
+            format!("{:?}", from_cbor.data),
+            format!("{:?}", from_json.data)
+        );
+        assert_eq!(from_cbor.expected_hashes, from_json.expected_hashes);
+        assert_eq!(from_cbor.min_confidence, from_json.min_confidence);
+        assert_eq!(from_cbor.source_weights, from_json.source_weights);
+    }
+
+    #[tokio::test]
+    async fn verification_result_round_trips_like_json() {
+        let agent = VerifierAgent::builder()
+            .config(VerifierConfig {
+                mode: VerifierMode::HashOnly,
+                max_chunk_age: None,
+                data_dir: PathBuf::new(),
+                ..VerifierConfig::default()
+            })
+            .eigen_compute(EigenCompute::new(EigenComputeConfig::default()))
+            .identity(AgentIdentity::generate())
+            .build()
+            .unwrap();
+        let task = serde_json::from_value(task_json()).unwrap();
+        let result = serde_json::to_value(agent.verify(task).await.unwrap()).unwrap();
+        let via_json: Value = serde_json::from_str(&result.to_string()).unwrap();
+        assert_eq!(round_trip(&result), via_json);
+    }
+
+    #[test]
+    fn tells_cbor_from_msgpack() {
+        let message = task_json();
+        assert!(is_cbor(&encode(&message)));
+        assert!(!is_cbor(&msgpack::encode(&message)));
+        let mut described = vec![0xd9, 0xd9, 0xf7];
+        described.extend_from_slice(&encode(&message));
+        assert!(is_cbor(&described));
+        assert_eq!(decode(&described).unwrap(), message);
+    }
+
+    #[test]
+    fn integers_use_the_smallest_form() {
+        let cases: [(i128, &[u8]); 20] = [
+            (0, &[0x00]),
+            (23, &[0x17]),
+            (24, &[0x18, 0x18]),
+            (0xff, &[0x18, 0xff]),
+            (0x100, &[0x19, 0x01, 0x00]),
+            (0xffff, &[0x19, 0xff, 0xff]),
+            (0x1_0000, &[0x1a, 0x00, 0x01, 0x00, 0x00]),
+            (0xffff_ffff, &[0x1a, 0xff, 0xff, 0xff, 0xff]),
+            (0x1_0000_0000, &[0x1b, 0, 0, 0, 0x01, 0, 0, 0, 0]),
+            (
+                u64::MAX as i128,
+                &[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (-1, &[0x20]),
+            (-24, &[0x37]),
+            (-25, &[0x38, 0x18]),
+            (-256, &[0x38, 0xff]),
+            (-257, &[0x39, 0x01, 0x00]),
+            (-65_536, &[0x39, 0xff, 0xff]),
+            (-65_537, &[0x3a, 0x00, 0x01, 0x00, 0x00]),
+            (-0x1_0000_0000, &[0x3a, 0xff, 0xff, 0xff, 0xff]),
+            (-0x1_0000_0001, &[0x3b, 0, 0, 0, 0x01, 0, 0, 0, 0]),
+            (
+                i64::MIN as i128,
+                &[0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+        ];
+        for (n, bytes) in cases {
+            let value: Value = serde_json::from_str(&n.to_string()).unwrap();
+            assert_eq!(encode(&value), bytes, "{}", n);
+            assert_eq!(decode(bytes).unwrap(), value, "{}", n);
+        }
+        // Below i64::MIN
+        assert!(decode(&[0x3b, 0x80, 0, 0, 0, 0, 0, 0, 0])
+            .unwrap_err()
+            .contains("out of range"));
+    }
+
+    #[test]
+    fn floats_stay_floats() {
+        for f in [0.0, 0.5, -1.25, 1e300, f64::MIN_POSITIVE] {
+            let value = json!(f);
+            let bytes = encode(&value);
+            assert_eq!(bytes[0], 0xfb);
+            assert_eq!(round_trip(&value), value);
+        }
+        // 1.0 is a float, not the integer 1
+        assert!(round_trip(&json!(1.0)).is_f64());
+        assert_eq!(decode(&[0xf9, 0x3e, 0x00]).unwrap(), json!(1.5));
+        assert_eq!(decode(&[0xf9, 0x00, 0x01]).unwrap(), json!(2f64.powi(-24)));
+        let mut single = vec![0xfa];
+        single.extend_from_slice(&(-2.5f32).to_be_bytes());
+        assert_eq!(decode(&single).unwrap(), json!(-2.5));
+    }
+
+    #[test]
+    fn lengths_use_the_smallest_form() {
+        // Length heads with the major type left out; text, arrays and
+        // maps share them
+        let cases: [(usize, &[u8]); 7] = [
+            (0, &[0x00]),
+            (23, &[0x17]),
+            (24, &[0x18, 24]),
+            (255, &[0x18, 0xff]),
+            (256, &[0x19, 1, 0]),
+            (65_535, &[0x19, 0xff, 0xff]),
+            (65_536, &[0x1a, 0, 1, 0, 0]),
+        ];
+        for (len, head) in cases {
+            let with_major = |major: u8| {
+                let mut head = head.to_vec();
+                head[0] |= major << 5;
+                head
+            };
+            let map: Map<String, Value> = (0..len).map(|i| (i.to_string(), Value::Null)).collect();
+            for (major, value) in [
+                (3, Value::String("x".repeat(len))),
+                (4, Value::Array(vec![Value::Null; len])),
+                (5, Value::Object(map)),
+            ] {
+                let bytes = encode(&value);
+                let head = with_major(major);
+                assert_eq!(&bytes[..head.len()], head, "major {} of {}", major, len);
+                assert_eq!(decode(&bytes).unwrap(), value);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_indefinite_lengths() {
+        // (_ "ab", "c")
+        assert_eq!(
+            decode(&[0x7f, 0x62, b'a', b'b', 0x61, b'c', 0xff]).unwrap(),
+            json!("abc")
+        );
+        assert_eq!(decode(&[0x7f, 0xff]).unwrap(), json!(""));
+        // [_ 1, [_ 2]]
+        assert_eq!(
+            decode(&[0x9f, 0x01, 0x9f, 0x02, 0xff, 0xff]).unwrap(),
+            json!([1, [2]])
+        );
+        // {_ "a": 1, "b": {_ }}
+        assert_eq!(
+            decode(&[0xbf, 0x61, b'a', 0x01, 0x61, b'b', 0xbf, 0xff, 0xff]).unwrap(),
+            json!({ "a": 1, "b": {} })
+        );
+        // A definite map with an indefinite key
+        assert_eq!(
+            decode(&[0xa1, 0x7f, 0x61, b'k', 0xff, 0xf6]).unwrap(),
+            json!({ "k": null })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_indefinite_lengths() {
+        let cases: [&[u8]; 7] = [
+            // Text chunks must be definite-length text
+            &[0x7f, 0x01, 0xff],
+            &[0x7f, 0x7f, 0xff, 0xff],
+            // No break
+            &[0x9f, 0x01],
+            &[0xbf, 0x61, b'a', 0x01],
+            // Integers and tags have no indefinite form
+            &[0x1f],
+            &[0xdf, 0x00],
+            // A break outside any indefinite item
+            &[0xff],
+        ];
+        for bytes in cases {
+            assert!(decode(bytes).is_err(), "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn only_the_self_describe_tag_is_accepted() {
+        assert_eq!(decode(&[0xd9, 0xd9, 0xf7, 0x01]).unwrap(), json!(1));
+        // Nested self-describe tags are still just tags
+        assert_eq!(
+            decode(&[0xd9, 0xd9, 0xf7, 0xd9, 0xd9, 0xf7, 0xa0]).unwrap(),
+            json!({})
+        );
+        // Epoch time, bignum and a tag inside a map
+        for bytes in [
+            &[0xc1, 0x01][..],
+            &[0xc2, 0x41, 0x01],
+            &[0xa1, 0x61, b'a', 0xc0, 0x60],
+        ] {
+            assert!(decode(bytes).unwrap_err().contains("tag"), "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn map_keys_must_be_text() {
+        // { 1: null }, { []: null }, { null: 1 }, {_ -1: 1 }
+        for bytes in [
+            &[0xa1, 0x01, 0xf6][..],
+            &[0xa1, 0x80, 0xf6],
+            &[0xa1, 0xf6, 0x01],
+            &[0xbf, 0x20, 0x01, 0xff],
+        ] {
+            assert!(
+                decode(bytes).unwrap_err().contains("not a string"),
+                "{:02x?}",
+                bytes
+            );
+        }
+        // { h'00': 1 }
+        assert!(decode(&[0xa1, 0x41, 0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    fn rejects_what_json_cannot_express() {
+        // Byte strings, definite and indefinite; undefined and other
+        // simple values; non-finite floats
+        for bytes in [
+            &[0x41, 0xaa][..],
+            &[0x58, 0x01, 0xaa],
+            &[0x59, 0x00, 0x01, 0xaa],
+            &[0x5a, 0x00, 0x00, 0x00, 0x01, 0xaa],
+            &[0x5f, 0x41, 0xaa, 0xff],
+            &[0xf7],
+            &[0xf0],
+            &[0xf8, 0x20],
+            &[0xf9, 0x7e, 0x00],
+            &[0xf9, 0x7c, 0x00],
+        ] {
+            assert!(decode(bytes).is_err(), "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let bytes = encode(&task_json());
+        for end in 0..bytes.len() {
+            assert!(decode(&bytes[..end]).is_err(), "cut at {}", end);
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let cases: [&[u8]; 8] = [
+            // Reserved additional information
+            &[0x1c],
+            // Trailing byte
+            &[0xf6, 0xf6],
+            // Not UTF-8
+            &[0x62, 0xff, 0xfe],
+            // Lengths far beyond the message
+            &[0x7b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0x9a, 0xff, 0xff, 0xff, 0xff, 0xf6],
+            // Half a float
+            &[0xfb, 0x00, 0x00],
+        ];
+        for bytes in cases {
+            assert!(decode(bytes).is_err(), "{:02x?}", bytes);
+        }
+        let deep = vec![0x81; MAX_DEPTH + 2];
+        assert!(decode(&deep).unwrap_err().contains("nested deeper"));
+        let deep = vec![0x9f; MAX_DEPTH + 2];
+        assert!(decode(&deep).unwrap_err().contains("nested deeper"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Command-line flags
+//!
+//! Flags cover the settings most often changed for one-off local runs and
+//! take precedence over the environment and the configuration file. Each
+//! falls back to the variable named in the usage text, via [`Config`].
+//!
+//! `verify-file <PATH>` runs a single task from disk instead of the agent,
+//! for checking a chunk set without a coordinator.
+
+use crate::agent::VerifierMode;
+use crate::config::{Config, CONFIG_ENV};
+use std::path::PathBuf;
+
+/// Printed for `--help` and after argument errors
+pub const USAGE: &str = "\
+Usage: verifier-agent [OPTIONS]
+       verifier-agent [OPTIONS] verify-file [--attest] <PATH>
+
+Commands:
+  verify-file <PATH>            Verify a verify_task JSON file offline and print the result;
+                                exits 1 unless its chunks verify
+
+Options:
+      --config <PATH>           TOML configuration file [env: VERIFIER_CONFIG]
+      --coordinator-url <URL>   Coordinator WebSocket URL [env: COORDINATOR_WS_URL]
+      --agent-id <ID>           Agent identifier [env: AGENT_ID]
+      --environment <NAME>      EigenCloud environment, testnet or mainnet [env: EIGENCLOUD_ENVIRONMENT]
+      --dev-mode                Simulate TEE attestations locally [env: EIGENCLOUD_DEV_MODE]
+      --no-attestation          Verify hashes only, without any TEE attestation [env: VERIFIER_MODE=hash-only]
+      --attest                  With verify-file, attest in dev mode instead of checking hashes only
+  -h, --help                    Print help
+  -V, --version                 Print version";
+
+/// Settings given on the command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cli {
+    pub config: Option<PathBuf>,
+    pub coordinator_url: Option<String>,
+    pub agent_id: Option<String>,
+    pub environment: Option<String>,
+    pub dev_mode: bool,
+    pub no_attestation: bool,
+}
+
+/// What the command line asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invocation {
+    Run(Cli),
+    /// Verify the task in `path` and exit
+    VerifyFile {
+        cli: Cli,
+        path: PathBuf,
+        /// Attest in dev mode rather than check hashes only
+        attest: bool,
+    },
+    Help,
+    Version,
+}
+
+impl Cli {
+    /// Parse arguments, excluding the program name. Values may follow their
+    /// flag as the next argument or after `=`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Invocation, String> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+        // `Some` once `verify-file` is seen, holding its path when given
+        let mut verify_file: Option<Option<PathBuf>> = None;
+        let mut attest = false;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg, None),
+            };
+            let mut value = |name: &str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| format!("{} requires a value", name))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => return Ok(Invocation::Help),
+                "-V" | "--version" => return Ok(Invocation::Version),
+                "--config" => cli.config = Some(PathBuf::from(value("--config")?)),
+                "--coordinator-url" => cli.coordinator_url = Some(value("--coordinator-url")?),
+                "--agent-id" => cli.agent_id = Some(value("--agent-id")?),
+                "--environment" => cli.environment = Some(value("--environment")?),
+                "--dev-mode" if inline.is_none() => cli.dev_mode = true,
+                "--dev-mode" => return Err("--dev-mode does not take a value".to_string()),
+                "--no-attestation" if inline.is_none() => cli.no_attestation = true,
+                "--no-attestation" => {
+                    return Err("--no-attestation does not take a value".to_string())
+                }
+                "--attest" if inline.is_none() => attest = true,
+                "--attest" => return Err("--attest does not take a value".to_string()),
+                "verify-file" if verify_file.is_none() => verify_file = Some(None),
+                path if matches!(verify_file, Some(None)) && !path.starts_with('-') => {
+                    verify_file = Some(Some(PathBuf::from(path)));
+                }
+                other => return Err(format!("unexpected argument '{}'", other)),
+            }
+        }
+
+        match verify_file {
+            Some(Some(path)) => Ok(Invocation::VerifyFile { cli, path, attest }),
+            Some(None) => Err("verify-file requires a path".to_string()),
+            None if attest => Err("--attest only applies to verify-file".to_string()),
+            None => Ok(Invocation::Run(cli)),
+        }
+    }
+
+    /// Configuration file named by `--config`, else by `VERIFIER_CONFIG`
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(|| {
+            std::env::var(CONFIG_ENV)
+                .ok()
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    /// Overlay the flags onto merged file and environment settings
+    pub fn apply(&self, mut config: Config) -> Config {
+        if let Some(url) = &self.coordinator_url {
+            config.agent.coordinator_urls = vec![url.clone()];
+        }
+        if let Some(agent_id) = &self.agent_id {
+            config.agent.agent_id = agent_id.clone();
+        }
+        if let Some(environment) = &self.environment {
+            config.tee.environment = environment.clone();
+        }
+        if self.dev_mode {
+            config.tee.dev_mode = true;
+        }
+        if self.no_attestation {
+            config.agent.mode = VerifierMode::HashOnly;
+        }
+        config
+    }
+}
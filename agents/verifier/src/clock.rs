@@ -0,0 +1,51 @@
+//! Wall-clock time for the timestamps the agent produces
+//!
+//! Attestations, registrations and freshness checks read the time through
+//! a [`Clock`], so an embedding service or a test can pin it. The system
+//! clock reads as zero rather than panicking if it is set before the epoch.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    /// Seconds since the Unix epoch
+    fn now_unix(&self) -> u64;
+}
+
+/// The host's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// Clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct MockClock(AtomicU64);
+
+impl MockClock {
+    pub fn new(now_unix: u64) -> Self {
+        Self(AtomicU64::new(now_unix))
+    }
+
+    pub fn set(&self, now_unix: u64) {
+        self.0.store(now_unix, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.0.fetch_add(secs, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_unix(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
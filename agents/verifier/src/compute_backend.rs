@@ -0,0 +1,593 @@
+//! Backends for managing EigenCloud deployments
+//!
+//! [`EigenCompute`](crate::eigencloud_sdk::EigenCompute) deploys, lists,
+//! inspects and terminates TEE containers through a [`ComputeBackend`].
+//! `EIGENCLOUD_BACKEND=cli`, the default, shells out to the `ecloud` CLI,
+//! which needs a node runtime and an OS keyring. `EIGENCLOUD_BACKEND=api`
+//! calls the EigenCloud REST API at `EIGENCLOUD_API_URL` directly, with the
+//! bearer token in `EIGENCLOUD_API_TOKEN`. Anything else implementing the
+//! trait, such as a mock, can be handed to `EigenCompute::with_backend`.
+//!
+//! API routes, relative to the API URL, mirror the CLI commands:
+//!
+//! | CLI                          | API                                   |
+//! |------------------------------|---------------------------------------|
+//! | `ecloud auth whoami`         | `GET /v1/auth/whoami`                 |
+//! | `ecloud deploy <image>`      | `POST /v1/deployments`                |
+//! | `ecloud list`                | `GET /v1/deployments?env=<env>`       |
+//! | `ecloud status <id>`         | `GET /v1/deployments/<id>`            |
+//! | `ecloud logs <id>`           | `GET /v1/deployments/<id>/logs`       |
+//! | `ecloud stop <id>`           | `POST /v1/deployments/<id>/stop`      |
+//! | `ecloud upgrade <id> <image>`| `PATCH /v1/deployments/<id>`          |
+//! | `ecloud terminate <id>`      | `DELETE /v1/deployments/<id>`         |
+//!
+//! Failures name the deployment where it matters:
+//! [`VerifierError::DeploymentNotFound`] and
+//! [`VerifierError::PermissionDenied`] are final, while
+//! [`VerifierError::Transport`] may pass on a retry. The CLI's `--json`
+//! error payload is read for its code or HTTP status, falling back to the
+//! wording of the message.
+
+use crate::eigencloud_sdk::DeploymentStatus;
+use crate::error::VerifierError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::process::{Output, Stdio};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Future returned by [`ComputeBackend`] methods
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, VerifierError>> + Send + 'a>>;
+
+/// Deployment management on EigenCloud
+pub trait ComputeBackend: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Account the backend is authenticated as
+    fn check_auth(&self) -> BackendFuture<'_, String>;
+
+    /// Start a deployment of `image`
+    fn deploy<'a>(&'a self, image: &'a str) -> BackendFuture<'a, DeploymentStatus>;
+
+    /// Every deployment in the environment
+    fn list(&self) -> BackendFuture<'_, Vec<DeploymentStatus>>;
+
+    /// Current state of deployment `id`, or `None` if it isn't listed
+    fn status<'a>(&'a self, id: &'a str) -> BackendFuture<'a, Option<DeploymentStatus>> {
+        Box::pin(async move { Ok(self.list().await?.into_iter().find(|d| d.id == id)) })
+    }
+
+    /// Output of deployment `id`
+    fn logs<'a>(&'a self, id: &'a str) -> BackendFuture<'a, String>;
+
+    /// Stop deployment `id` without removing it
+    fn stop<'a>(&'a self, id: &'a str) -> BackendFuture<'a, ()>;
+
+    /// Roll deployment `id` to `image`
+    fn update<'a>(&'a self, id: &'a str, image: &'a str) -> BackendFuture<'a, DeploymentStatus>;
+
+    /// Stop and remove deployment `id`
+    fn terminate<'a>(&'a self, id: &'a str) -> BackendFuture<'a, ()>;
+}
+
+/// Which [`ComputeBackend`] the agent uses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    /// The `ecloud` CLI
+    #[default]
+    Cli,
+    /// The EigenCloud REST API
+    Api,
+}
+
+impl BackendKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackendKind::Cli => "cli",
+            BackendKind::Api => "api",
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cli" => Ok(BackendKind::Cli),
+            "api" => Ok(BackendKind::Api),
+            other => Err(format!("unsupported EigenCloud backend: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Deployment list, which some CLI versions and the API wrap in an object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DeploymentList {
+    Bare(Vec<DeploymentStatus>),
+    Wrapped { deployments: Vec<DeploymentStatus> },
+}
+
+impl From<DeploymentList> for Vec<DeploymentStatus> {
+    fn from(list: DeploymentList) -> Self {
+        match list {
+            DeploymentList::Bare(deployments) => deployments,
+            DeploymentList::Wrapped { deployments } => deployments,
+        }
+    }
+}
+
+/// Runs the `ecloud` CLI, which keeps its credentials in the OS keyring
+pub struct CliBackend {
+    /// "testnet" or "mainnet"
+    environment: String,
+    /// Deadline for each invocation
+    timeout: Duration,
+}
+
+impl CliBackend {
+    pub fn new(environment: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            environment: environment.into(),
+            timeout,
+        }
+    }
+
+    /// Run an ecloud CLI command, killing it if it exceeds the timeout
+    async fn run(&self, args: &[&str]) -> io::Result<Output> {
+        let child = Command::new("ecloud")
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        // Dropping the child on timeout kills it via kill_on_drop
+        match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(output) => output,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "ecloud {} timed out after {}ms",
+                    args.first().unwrap_or(&""),
+                    self.timeout.as_millis()
+                ),
+            )),
+        }
+    }
+}
+
+impl ComputeBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    fn check_auth(&self) -> BackendFuture<'_, String> {
+        Box::pin(async move {
+            let output = self
+                .run(&["auth", "whoami"])
+                .await
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::TimedOut => VerifierError::CliTimeout(e.to_string()),
+                    _ => VerifierError::CliMissing(e.to_string()),
+                })?;
+
+            if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            } else {
+                Err(VerifierError::CliUnauthenticated(
+                    String::from_utf8_lossy(&output.stderr).to_string(),
+                ))
+            }
+        })
+    }
+
+    fn deploy<'a>(&'a self, image: &'a str) -> BackendFuture<'a, DeploymentStatus> {
+        Box::pin(async move {
+            let output = self
+                .run(&["deploy", image, "--env", &self.environment, "--json"])
+                .await
+                .map_err(|e| cli_error(e, "Failed to deploy"))?;
+
+            if !output.status.success() {
+                return Err(VerifierError::DeploymentFailed(
+                    String::from_utf8_lossy(&output.stderr).to_string(),
+                ));
+            }
+
+            serde_json::from_slice(&output.stdout).map_err(|e| {
+                VerifierError::DeploymentFailed(format!(
+                    "Failed to parse deployment response: {}",
+                    e
+                ))
+            })
+        })
+    }
+
+    fn list(&self) -> BackendFuture<'_, Vec<DeploymentStatus>> {
+        Box::pin(async move {
+            let output = self
+                .run(&["list", "--env", &self.environment, "--json"])
+                .await
+                .map_err(|e| cli_error(e, "Failed to list deployments"))?;
+
+            if !output.status.success() {
+                return Err(VerifierError::Transport(
+                    String::from_utf8_lossy(&output.stderr).to_string(),
+                ));
+            }
+
+            serde_json::from_slice::<DeploymentList>(&output.stdout)
+                .map(Vec::from)
+                .map_err(|e| {
+                    VerifierError::Transport(format!("Failed to parse deployment list: {}", e))
+                })
+        })
+    }
+
+    fn logs<'a>(&'a self, id: &'a str) -> BackendFuture<'a, String> {
+        Box::pin(async move {
+            let output = self
+                .run(&["logs", id, "--env", &self.environment])
+                .await
+                .map_err(|e| cli_error(e, "Failed to get logs"))?;
+
+            if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            } else {
+                Err(VerifierError::Transport(
+                    String::from_utf8_lossy(&output.stderr).to_string(),
+                ))
+            }
+        })
+    }
+
+    fn status<'a>(&'a self, id: &'a str) -> BackendFuture<'a, Option<DeploymentStatus>> {
+        Box::pin(async move {
+            let output = self
+                .run(&["status", id, "--env", &self.environment, "--json"])
+                .await
+                .map_err(|e| cli_error(e, "Failed to get deployment status"))?;
+
+            if !output.status.success() {
+                return match cli_failure(&output, id, VerifierError::Transport) {
+                    VerifierError::DeploymentNotFound(_) => Ok(None),
+                    e => Err(e),
+                };
+            }
+
+            serde_json::from_slice(&output.stdout)
+                .map(Some)
+                .map_err(|e| {
+                    VerifierError::Transport(format!("Failed to parse deployment {}: {}", id, e))
+                })
+        })
+    }
+
+    fn stop<'a>(&'a self, id: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let output = self
+                .run(&["stop", id, "--env", &self.environment, "--json"])
+                .await
+                .map_err(|e| cli_error(e, "Failed to stop"))?;
+
+            if output.status.success() {
+                Ok(())
+            } else {
+                Err(cli_failure(&output, id, VerifierError::Transport))
+            }
+        })
+    }
+
+    fn update<'a>(&'a self, id: &'a str, image: &'a str) -> BackendFuture<'a, DeploymentStatus> {
+        Box::pin(async move {
+            let output = self
+                .run(&["upgrade", id, image, "--env", &self.environment, "--json"])
+                .await
+                .map_err(|e| cli_error(e, "Failed to update"))?;
+
+            if !output.status.success() {
+                return Err(cli_failure(&output, id, VerifierError::DeploymentFailed));
+            }
+
+            serde_json::from_slice(&output.stdout).map_err(|e| {
+                VerifierError::DeploymentFailed(format!(
+                    "Failed to parse deployment response: {}",
+                    e
+                ))
+            })
+        })
+    }
+
+    fn terminate<'a>(&'a self, id: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let output = self
+                .run(&["terminate", id, "--env", &self.environment])
+                .await
+                .map_err(|e| cli_error(e, "Failed to terminate"))?;
+
+            if output.status.success() {
+                Ok(())
+            } else {
+                Err(VerifierError::Transport(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ))
+            }
+        })
+    }
+}
+
+/// Calls the EigenCloud REST API with a bearer token
+pub struct ApiBackend {
+    http: reqwest::Client,
+    /// API root, without a trailing slash
+    base_url: String,
+    token: Option<String>,
+    /// "testnet" or "mainnet"
+    environment: String,
+}
+
+impl ApiBackend {
+    /// `timeout` bounds each request
+    pub fn new(
+        base_url: &str,
+        token: Option<String>,
+        environment: impl Into<String>,
+        timeout: Duration,
+    ) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("failed to build EigenCloud API client");
+
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            environment: environment.into(),
+        }
+    }
+
+    /// Send an authenticated request to `path` and return the successful
+    /// response, or `None` for a 404
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<Option<reqwest::Response>, VerifierError> {
+        let token = self.token.as_deref().ok_or_else(|| {
+            VerifierError::ApiUnauthenticated("EIGENCLOUD_API_TOKEN is not set".to_string())
+        })?;
+
+        let mut request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path))
+            .bearer_auth(token);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.map_err(|e| {
+            VerifierError::Transport(format!("EigenCloud API request to {} failed: {}", path, e))
+        })?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if status.is_success() {
+            return Ok(Some(response));
+        }
+        let detail = response.text().await.unwrap_or_default();
+        let detail = format!("{} returned {}: {}", path, status, detail.trim());
+        Err(match status {
+            reqwest::StatusCode::UNAUTHORIZED => VerifierError::ApiUnauthenticated(detail),
+            reqwest::StatusCode::FORBIDDEN => VerifierError::PermissionDenied(detail),
+            _ => VerifierError::Transport(detail),
+        })
+    }
+
+    /// Like `send`, treating a 404 as an error
+    async fn send_expecting(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<reqwest::Response, VerifierError> {
+        self.send(method, path, body)
+            .await?
+            .ok_or_else(|| VerifierError::Transport(format!("{} returned 404 Not Found", path)))
+    }
+}
+
+impl ComputeBackend for ApiBackend {
+    fn name(&self) -> &'static str {
+        "api"
+    }
+
+    fn check_auth(&self) -> BackendFuture<'_, String> {
+        Box::pin(async move {
+            let response = self
+                .send_expecting(reqwest::Method::GET, "/v1/auth/whoami", None)
+                .await?;
+            response
+                .text()
+                .await
+                .map_err(|e| VerifierError::Transport(e.to_string()))
+        })
+    }
+
+    fn deploy<'a>(&'a self, image: &'a str) -> BackendFuture<'a, DeploymentStatus> {
+        Box::pin(async move {
+            let body = serde_json::json!({ "image": image, "env": self.environment });
+            let response = self
+                .send_expecting(reqwest::Method::POST, "/v1/deployments", Some(body))
+                .await
+                .map_err(|e| match e {
+                    VerifierError::Transport(detail) => VerifierError::DeploymentFailed(detail),
+                    other => other,
+                })?;
+            response.json().await.map_err(|e| {
+                VerifierError::DeploymentFailed(format!(
+                    "Failed to parse deployment response: {}",
+                    e
+                ))
+            })
+        })
+    }
+
+    fn list(&self) -> BackendFuture<'_, Vec<DeploymentStatus>> {
+        Box::pin(async move {
+            let path = format!("/v1/deployments?env={}", self.environment);
+            let response = self
+                .send_expecting(reqwest::Method::GET, &path, None)
+                .await?;
+            response
+                .json::<DeploymentList>()
+                .await
+                .map(Vec::from)
+                .map_err(|e| {
+                    VerifierError::Transport(format!("Failed to parse deployment list: {}", e))
+                })
+        })
+    }
+
+    fn status<'a>(&'a self, id: &'a str) -> BackendFuture<'a, Option<DeploymentStatus>> {
+        Box::pin(async move {
+            let path = format!("/v1/deployments/{}", id);
+            let Some(response) = self.send(reqwest::Method::GET, &path, None).await? else {
+                return Ok(None);
+            };
+            response.json().await.map(Some).map_err(|e| {
+                VerifierError::Transport(format!("Failed to parse deployment {}: {}", id, e))
+            })
+        })
+    }
+
+    fn logs<'a>(&'a self, id: &'a str) -> BackendFuture<'a, String> {
+        Box::pin(async move {
+            let path = format!("/v1/deployments/{}/logs", id);
+            let response = self
+                .send_expecting(reqwest::Method::GET, &path, None)
+                .await?;
+            response
+                .text()
+                .await
+                .map_err(|e| VerifierError::Transport(e.to_string()))
+        })
+    }
+
+    fn stop<'a>(&'a self, id: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let path = format!("/v1/deployments/{}/stop", id);
+            match self.send(reqwest::Method::POST, &path, None).await? {
+                Some(_) => Ok(()),
+                None => Err(VerifierError::DeploymentNotFound(id.to_string())),
+            }
+        })
+    }
+
+    fn update<'a>(&'a self, id: &'a str, image: &'a str) -> BackendFuture<'a, DeploymentStatus> {
+        Box::pin(async move {
+            let path = format!("/v1/deployments/{}", id);
+            let body = serde_json::json!({ "image": image });
+            let response = self
+                .send(reqwest::Method::PATCH, &path, Some(body))
+                .await?
+                .ok_or_else(|| VerifierError::DeploymentNotFound(id.to_string()))?;
+            response.json().await.map_err(|e| {
+                VerifierError::DeploymentFailed(format!(
+                    "Failed to parse deployment response: {}",
+                    e
+                ))
+            })
+        })
+    }
+
+    fn terminate<'a>(&'a self, id: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let path = format!("/v1/deployments/{}", id);
+            self.send_expecting(reqwest::Method::DELETE, &path, None)
+                .await
+                .map(|_| ())
+        })
+    }
+}
+
+/// Map a CLI invocation failure, keeping timeouts distinct
+fn cli_error(e: io::Error, context: &str) -> VerifierError {
+    match e.kind() {
+        io::ErrorKind::TimedOut => VerifierError::CliTimeout(e.to_string()),
+        _ => VerifierError::Transport(format!("{}: {}", context, e)),
+    }
+}
+
+/// Typed error for a CLI command on deployment `id` that exited with a
+/// failure. The JSON error it printed, on stdout or stderr, is read as
+/// `{"code", "message", "status"}`, either at the top level or nested
+/// under `error`, which may also be the message itself. Failures that are
+/// neither missing deployments, permissions nor transient go to
+/// `otherwise`.
+fn cli_failure(output: &Output, id: &str, otherwise: fn(String) -> VerifierError) -> VerifierError {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let payload = [&output.stdout, &output.stderr]
+        .into_iter()
+        .find_map(|bytes| serde_json::from_slice::<Value>(bytes).ok())
+        .filter(Value::is_object)
+        .unwrap_or_default();
+    let nested = payload.get("error").filter(|e| e.is_object());
+    let field = |name: &str| {
+        nested
+            .and_then(|e| e.get(name))
+            .or_else(|| payload.get(name))
+    };
+
+    let code = field("code")
+        .and_then(Value::as_str)
+        .map(|c| c.to_ascii_lowercase().replace('-', "_"));
+    let status = field("status")
+        .or_else(|| field("statusCode"))
+        .and_then(Value::as_u64);
+    let message = field("message")
+        .or_else(|| payload.get("error"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or(stderr);
+    let detail = format!("{}: {}", id, message);
+
+    let lowered = message.to_ascii_lowercase();
+    match (code.as_deref(), status) {
+        (Some("not_found" | "deployment_not_found" | "app_not_found"), _) | (_, Some(404)) => {
+            VerifierError::DeploymentNotFound(detail)
+        }
+        (Some("forbidden" | "permission_denied" | "access_denied"), _) | (_, Some(403)) => {
+            VerifierError::PermissionDenied(detail)
+        }
+        (Some("unauthorized" | "unauthenticated"), _) | (_, Some(401)) => {
+            VerifierError::CliUnauthenticated(detail)
+        }
+        (Some("timeout" | "unavailable" | "rate_limited" | "internal"), _) => {
+            VerifierError::Transport(detail)
+        }
+        (_, Some(429 | 500..=599)) => VerifierError::Transport(detail),
+        (None, None) if lowered.contains("not found") => VerifierError::DeploymentNotFound(detail),
+        (None, None) if lowered.contains("permission denied") || lowered.contains("forbidden") => {
+            VerifierError::PermissionDenied(detail)
+        }
+        _ => otherwise(detail),
+    }
+}
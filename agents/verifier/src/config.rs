@@ -0,0 +1,536 @@
+//! Configuration file support
+//!
+//! Settings are merged in layers, each overriding the one before:
+//!
+//! 1. built-in defaults
+//! 2. the TOML file named by `--config <path>` or, failing that,
+//!    `VERIFIER_CONFIG`
+//! 3. environment variables (including those loaded from `.env`)
+//! 4. command-line flags, applied by [`crate::cli::Cli::apply`]
+//!
+//! The file has an `[agent]` table for [`VerifierConfig`] and a `[tee]`
+//! table for [`EigenComputeConfig`]. Keys are the field names, with
+//! durations spelled `*_secs` or `*_ms` like their environment variables:
+//!
+//! ```toml
+//! [agent]
+//! agent_id = "verifier-eu-1"
+//! coordinator_urls = ["wss://coordinator.example/ws", "wss://standby.example/ws"]
+//! heartbeat_interval_ms = 10000
+//!
+//! [tee]
+//! container_url = "http://10.0.0.5:8090"
+//! retry_attempts = 5
+//! ```
+//!
+//! Only the part of TOML these files need is understood: tables, comments
+//! and single-line strings, integers, floats, booleans and arrays of those.
+//! Unknown keys are rejected so a typo doesn't silently fall back to a
+//! default.
+
+use crate::agent::VerifierConfig;
+use crate::eigencloud_sdk::EigenComputeConfig;
+use crate::error::VerifierError;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable naming the configuration file when `--config` is
+/// absent
+pub(crate) const CONFIG_ENV: &str = "VERIFIER_CONFIG";
+
+/// Complete agent configuration after merging all layers
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub agent: VerifierConfig,
+    pub tee: EigenComputeConfig,
+}
+
+impl Config {
+    /// Merge defaults, the file named by `VERIFIER_CONFIG` (if any) and the
+    /// environment
+    pub fn load() -> Result<Self, VerifierError> {
+        let path = std::env::var(CONFIG_ENV).ok().filter(|p| !p.is_empty());
+        Self::load_from(path.as_deref().map(Path::new))
+    }
+
+    /// Merge defaults, the file at `path` and the environment
+    pub fn load_from(path: Option<&Path>) -> Result<Self, VerifierError> {
+        let mut config = Self::default();
+        if let Some(path) = path {
+            let text = fs::read_to_string(path).map_err(|e| {
+                VerifierError::InvalidConfig(format!("failed to read {}: {}", path.display(), e))
+            })?;
+            config = config.with_file(&text).map_err(|e| {
+                VerifierError::InvalidConfig(format!("{}: {}", path.display(), e))
+            })?;
+        }
+        Ok(Self {
+            agent: config.agent.with_env(),
+            tee: config.tee.with_env(),
+        })
+    }
+
+    /// Overlay the settings in a TOML document
+    pub fn with_file(self, text: &str) -> Result<Self, String> {
+        let file: FileConfig =
+            serde_json::from_value(parse_toml(text)?).map_err(|e| e.to_string())?;
+        let (agent, tee) = (file.agent, file.tee);
+        let (base, tee_base) = (self.agent, self.tee);
+        let tee_type = match tee.tee_type {
+            Some(t) => t.parse()?,
+            None => tee_base.tee_type,
+        };
+        let backend = match tee.backend {
+            Some(b) => b.parse()?,
+            None => tee_base.backend,
+        };
+        let mode = match agent.mode {
+            Some(m) => m.parse()?,
+            None => base.mode,
+        };
+        let message_auth = match agent.message_auth {
+            Some(m) => m.parse()?,
+            None => base.message_auth,
+        };
+        let coordinator_mode = match agent.coordinator_mode {
+            Some(m) => m.parse()?,
+            None => base.coordinator_mode,
+        };
+        let outbound_overflow = match agent.outbound_overflow {
+            Some(policy) => policy.parse()?,
+            None => base.outbound_overflow,
+        };
+        // Zero lifts the limit, as with TASKS_PER_SEC
+        let tasks_per_sec = match agent.tasks_per_sec {
+            Some(0.0) => None,
+            Some(rate) if rate > 0.0 && rate.is_finite() => Some(rate),
+            Some(rate) => return Err(format!("tasks_per_sec must be positive, got {}", rate)),
+            None => base.tasks_per_sec,
+        };
+        // Zero lifts the limit, as with TASK_TIMEOUT_SECS; task_timeout_ms
+        // wins over the seconds
+        let task_timeout = match millis(agent.task_timeout_ms).or(secs(agent.task_timeout_secs)) {
+            Some(timeout) if timeout.is_zero() => None,
+            Some(timeout) => Some(timeout),
+            None => base.task_timeout,
+        };
+
+        Ok(Self {
+            agent: VerifierConfig {
+                agent_id: agent.agent_id.unwrap_or(base.agent_id),
+                mode,
+                coordinator_urls: agent
+                    .coordinator_urls
+                    .or(agent.coordinator_url.map(|url| vec![url]))
+                    .unwrap_or(base.coordinator_urls),
+                coordinator_mode,
+                coordinator_ca_cert: agent.coordinator_ca_cert.or(base.coordinator_ca_cert),
+                coordinator_pin_sha256: agent
+                    .coordinator_pin_sha256
+                    .or(base.coordinator_pin_sha256),
+                // Kept out of the file; only AGENT_AUTH_TOKEN sets it
+                auth_token: base.auth_token,
+                message_auth,
+                max_concurrent_tasks: agent
+                    .max_concurrent_tasks
+                    .unwrap_or(base.max_concurrent_tasks),
+                tasks_per_sec,
+                task_burst: agent.task_burst.unwrap_or(base.task_burst),
+                max_queued_tasks: agent.max_queued_tasks.unwrap_or(base.max_queued_tasks),
+                outbound_queue_capacity: agent
+                    .outbound_queue_capacity
+                    .unwrap_or(base.outbound_queue_capacity),
+                outbound_overflow,
+                parallelism: agent.parallelism.unwrap_or(base.parallelism),
+                parallel_threshold: agent.parallel_threshold.unwrap_or(base.parallel_threshold),
+                drain_timeout: secs(agent.drain_timeout_secs).unwrap_or(base.drain_timeout),
+                shutdown_grace: secs(agent.shutdown_grace_secs).unwrap_or(base.shutdown_grace),
+                heartbeat_interval: millis(agent.heartbeat_interval_ms)
+                    .unwrap_or(base.heartbeat_interval),
+                heartbeat_timeout: millis(agent.heartbeat_timeout_ms)
+                    .unwrap_or(base.heartbeat_timeout),
+                status_interval: millis(agent.status_interval_ms).unwrap_or(base.status_interval),
+                register_timeout: millis(agent.register_timeout_ms)
+                    .unwrap_or(base.register_timeout),
+                confidence_threshold: agent
+                    .confidence_threshold
+                    .unwrap_or(base.confidence_threshold),
+                confidence_floor: agent.confidence_floor.unwrap_or(base.confidence_floor),
+                consistency_weight: agent.consistency_weight.unwrap_or(base.consistency_weight),
+                rule_warning_penalty: agent
+                    .rule_warning_penalty
+                    .unwrap_or(base.rule_warning_penalty),
+                // Zero turns the age check off, as with MAX_CHUNK_AGE_SECS
+                max_chunk_age: match agent.max_chunk_age_secs {
+                    Some(0) => None,
+                    Some(s) => Some(Duration::from_secs(s)),
+                    None => base.max_chunk_age,
+                },
+                max_clock_skew: secs(agent.max_clock_skew_secs).unwrap_or(base.max_clock_skew),
+                max_chunk_bytes: agent.max_chunk_bytes.unwrap_or(base.max_chunk_bytes),
+                max_chunks_per_task: agent
+                    .max_chunks_per_task
+                    .unwrap_or(base.max_chunks_per_task),
+                max_task_bytes: agent.max_task_bytes.unwrap_or(base.max_task_bytes),
+                max_fetch_bytes: agent.max_fetch_bytes.unwrap_or(base.max_fetch_bytes),
+                fetch_timeout: secs(agent.fetch_timeout_secs).unwrap_or(base.fetch_timeout),
+                max_concurrent_fetches: agent
+                    .max_concurrent_fetches
+                    .unwrap_or(base.max_concurrent_fetches),
+                max_batch_tasks: agent.max_batch_tasks.unwrap_or(base.max_batch_tasks),
+                task_timeout,
+                data_dir: agent.data_dir.unwrap_or(base.data_dir),
+                pending_dir: agent.pending_dir.or(base.pending_dir),
+                outbox_retention: secs(agent.outbox_retention_secs)
+                    .unwrap_or(base.outbox_retention),
+                outbox_max_entries: agent.outbox_max_entries.unwrap_or(base.outbox_max_entries),
+                dedup_window: agent.dedup_window.unwrap_or(base.dedup_window),
+                dedup_ttl: secs(agent.dedup_ttl_secs).unwrap_or(base.dedup_ttl),
+                // Kept out of the file; only SETTLEMENT_KEY sets it
+                settlement_key: base.settlement_key,
+                // Likewise only CHUNK_DECRYPTION_KEY
+                chunk_decryption_key: base.chunk_decryption_key,
+                attestation_log_path: agent.attestation_log_path.or(base.attestation_log_path),
+                attestation_log_fsync_every: agent
+                    .attestation_log_fsync_every
+                    .unwrap_or(base.attestation_log_fsync_every),
+                // Zero turns rotation off, as with ATTESTATION_LOG_MAX_BYTES
+                attestation_log_max_bytes: match agent.attestation_log_max_bytes {
+                    Some(0) => None,
+                    Some(bytes) => Some(bytes),
+                    None => base.attestation_log_max_bytes,
+                },
+            },
+            tee: EigenComputeConfig {
+                environment: tee.environment.unwrap_or(tee_base.environment),
+                dev_mode: tee.dev_mode.unwrap_or(tee_base.dev_mode),
+                allow_dev_on_mainnet: tee
+                    .allow_dev_on_mainnet
+                    .unwrap_or(tee_base.allow_dev_on_mainnet),
+                // Key material stays out of the file, like the API token
+                dev_attestation_key: tee_base.dev_attestation_key,
+                dev_attestation_seed: tee_base.dev_attestation_seed,
+                tee_type,
+                backend,
+                api_url: tee.api_url.unwrap_or(tee_base.api_url),
+                // Kept out of the file; only EIGENCLOUD_API_TOKEN sets it
+                api_token: tee_base.api_token,
+                tee_container_url: tee.container_url.or(tee_base.tee_container_url),
+                tee_image: tee.image.unwrap_or(tee_base.tee_image),
+                deploy_image: tee.deploy_image.or(tee_base.deploy_image),
+                deploy_timeout: secs(tee.deploy_timeout_secs).unwrap_or(tee_base.deploy_timeout),
+                teardown_on_shutdown: tee
+                    .teardown_on_shutdown
+                    .unwrap_or(tee_base.teardown_on_shutdown),
+                tee_timeout: millis(tee.request_timeout_ms).unwrap_or(tee_base.tee_timeout),
+                tee_connect_timeout: millis(tee.connect_timeout_ms)
+                    .unwrap_or(tee_base.tee_connect_timeout),
+                tee_ca_cert: tee.ca_cert.or(tee_base.tee_ca_cert),
+                tee_pin_sha256: tee.pin_sha256.or(tee_base.tee_pin_sha256),
+                cli_timeout: millis(tee.cli_timeout_ms).unwrap_or(tee_base.cli_timeout),
+                // Zero turns the re-checks off, as with ECLOUD_AUTH_CHECK_INTERVAL_SECS
+                auth_check_interval: match tee.auth_check_interval_secs {
+                    Some(0) => None,
+                    Some(s) => Some(Duration::from_secs(s)),
+                    None => tee_base.auth_check_interval,
+                },
+                pool_max_idle_per_host: tee
+                    .pool_max_idle_per_host
+                    .unwrap_or(tee_base.pool_max_idle_per_host),
+                pool_idle_timeout: secs(tee.pool_idle_timeout_secs)
+                    .unwrap_or(tee_base.pool_idle_timeout),
+                keepalive: match tee.keepalive_secs {
+                    Some(0) => None,
+                    Some(s) => Some(Duration::from_secs(s)),
+                    None => tee_base.keepalive,
+                },
+                retry_attempts: tee.retry_attempts.unwrap_or(tee_base.retry_attempts),
+                retry_base_delay: millis(tee.retry_base_delay_ms)
+                    .unwrap_or(tee_base.retry_base_delay),
+                breaker_threshold: tee.breaker_threshold.unwrap_or(tee_base.breaker_threshold),
+                breaker_cooldown: secs(tee.breaker_cooldown_secs)
+                    .unwrap_or(tee_base.breaker_cooldown),
+                attestation_cache_size: tee
+                    .attestation_cache_size
+                    .unwrap_or(tee_base.attestation_cache_size),
+                attestation_cache_ttl: secs(tee.attestation_cache_ttl_secs)
+                    .unwrap_or(tee_base.attestation_cache_ttl),
+                allowed_measurements: tee
+                    .allowed_measurements
+                    .unwrap_or(tee_base.allowed_measurements),
+                fresh_nonces: tee.fresh_nonce.unwrap_or(tee_base.fresh_nonces),
+            },
+        })
+    }
+}
+
+/// Shape of the configuration file; every key is optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    agent: AgentSection,
+    tee: TeeSection,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct AgentSection {
+    agent_id: Option<String>,
+    mode: Option<String>,
+    coordinator_url: Option<String>,
+    /// Failover list; wins over `coordinator_url`
+    coordinator_urls: Option<Vec<String>>,
+    coordinator_mode: Option<String>,
+    coordinator_ca_cert: Option<PathBuf>,
+    coordinator_pin_sha256: Option<String>,
+    message_auth: Option<String>,
+    max_concurrent_tasks: Option<usize>,
+    tasks_per_sec: Option<f64>,
+    task_burst: Option<u32>,
+    max_queued_tasks: Option<usize>,
+    outbound_queue_capacity: Option<usize>,
+    outbound_overflow: Option<String>,
+    parallelism: Option<usize>,
+    parallel_threshold: Option<usize>,
+    drain_timeout_secs: Option<u64>,
+    shutdown_grace_secs: Option<u64>,
+    heartbeat_interval_ms: Option<u64>,
+    heartbeat_timeout_ms: Option<u64>,
+    status_interval_ms: Option<u64>,
+    register_timeout_ms: Option<u64>,
+    confidence_threshold: Option<u8>,
+    confidence_floor: Option<u8>,
+    consistency_weight: Option<u8>,
+    rule_warning_penalty: Option<u8>,
+    max_chunk_age_secs: Option<u64>,
+    max_clock_skew_secs: Option<u64>,
+    max_chunk_bytes: Option<usize>,
+    max_chunks_per_task: Option<usize>,
+    max_task_bytes: Option<usize>,
+    max_fetch_bytes: Option<u64>,
+    fetch_timeout_secs: Option<u64>,
+    max_concurrent_fetches: Option<usize>,
+    max_batch_tasks: Option<usize>,
+    task_timeout_secs: Option<u64>,
+    task_timeout_ms: Option<u64>,
+    data_dir: Option<PathBuf>,
+    pending_dir: Option<PathBuf>,
+    outbox_retention_secs: Option<u64>,
+    outbox_max_entries: Option<usize>,
+    dedup_window: Option<usize>,
+    dedup_ttl_secs: Option<u64>,
+    attestation_log_path: Option<PathBuf>,
+    attestation_log_fsync_every: Option<u32>,
+    attestation_log_max_bytes: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TeeSection {
+    environment: Option<String>,
+    dev_mode: Option<bool>,
+    allow_dev_on_mainnet: Option<bool>,
+    tee_type: Option<String>,
+    backend: Option<String>,
+    api_url: Option<String>,
+    container_url: Option<String>,
+    image: Option<String>,
+    deploy_image: Option<String>,
+    deploy_timeout_secs: Option<u64>,
+    teardown_on_shutdown: Option<bool>,
+    request_timeout_ms: Option<u64>,
+    connect_timeout_ms: Option<u64>,
+    ca_cert: Option<PathBuf>,
+    pin_sha256: Option<String>,
+    cli_timeout_ms: Option<u64>,
+    auth_check_interval_secs: Option<u64>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout_secs: Option<u64>,
+    keepalive_secs: Option<u64>,
+    retry_attempts: Option<u32>,
+    retry_base_delay_ms: Option<u64>,
+    breaker_threshold: Option<u32>,
+    breaker_cooldown_secs: Option<u64>,
+    attestation_cache_size: Option<usize>,
+    attestation_cache_ttl_secs: Option<u64>,
+    allowed_measurements: Option<Vec<String>>,
+    fresh_nonce: Option<bool>,
+}
+
+fn secs(value: Option<u64>) -> Option<Duration> {
+    value.map(Duration::from_secs)
+}
+
+fn millis(value: Option<u64>) -> Option<Duration> {
+    value.map(Duration::from_millis)
+}
+
+/// Parse the supported TOML subset into nested JSON objects
+fn parse_toml(text: &str) -> Result<Value, String> {
+    let mut root = Map::new();
+    let mut table: Option<String> = None;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header
+                .strip_suffix(']')
+                .map(str::trim)
+                .filter(|n| is_bare_key(n))
+                .ok_or_else(|| format!("line {}: invalid table header", line_no))?;
+            if root.contains_key(name) {
+                return Err(format!("line {}: table [{}] defined twice", line_no, name));
+            }
+            root.insert(name.to_string(), Value::Object(Map::new()));
+            table = Some(name.to_string());
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", line_no))?;
+        let key = key.trim();
+        if !is_bare_key(key) {
+            return Err(format!("line {}: invalid key `{}`", line_no, key));
+        }
+        let value = parse_value(value.trim()).map_err(|e| format!("line {}: {}", line_no, e))?;
+
+        let target = match &table {
+            Some(name) => root
+                .get_mut(name)
+                .and_then(Value::as_object_mut)
+                .expect("table inserted when its header was read"),
+            None => &mut root,
+        };
+        if target.insert(key.to_string(), value).is_some() {
+            return Err(format!("line {}: duplicate key `{}`", line_no, key));
+        }
+    }
+
+    Ok(Value::Object(root))
+}
+
+/// Split array items on commas that are not inside a string
+fn split_array(body: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in body.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' => {
+                items.push(&body[start..i]);
+                start = i + 1;
+            }
+            None => {}
+        }
+    }
+    items.push(&body[start..]);
+    items
+}
+
+/// Drop a trailing `#` comment that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+    if let Some(body) = raw.strip_prefix('[') {
+        let body = body
+            .strip_suffix(']')
+            .ok_or_else(|| "unterminated array".to_string())?;
+        return split_array(body)
+            .into_iter()
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(parse_value)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array);
+    }
+    if let Some(body) = raw.strip_prefix('"') {
+        let body = body
+            .strip_suffix('"')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        return unescape(body).map(Value::String);
+    }
+    if let Some(body) = raw.strip_prefix('\'') {
+        let body = body
+            .strip_suffix('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        return Ok(Value::String(body.to_string()));
+    }
+    match raw {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    let digits = raw.replace('_', "");
+    if let Ok(n) = digits.parse::<i64>() {
+        return Ok(Value::Number(n.into()));
+    }
+    digits
+        .parse::<f64>()
+        .ok()
+        .and_then(Number::from_f64)
+        .map(Value::Number)
+        .ok_or_else(|| format!("unsupported value `{}`", raw))
+}
+
+/// Resolve the escapes of a TOML basic string
+fn unescape(body: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return Err("unescaped quote in string".to_string());
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(other) => return Err(format!("unsupported escape `\\{}`", other)),
+            None => return Err("dangling backslash in string".to_string()),
+        }
+    }
+    Ok(out)
+}
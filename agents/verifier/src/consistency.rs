@@ -0,0 +1,174 @@
+//! Cross-source consistency of numeric chunk data
+//!
+//! Hash checks show each chunk is what its source sent, not that the
+//! sources agree. A task's `consistency` block names a JSON pointer; the
+//! value there is read from every verified chunk's inline data, the values
+//! are aggregated, and each chunk is held to the aggregate within the
+//! tolerance. Findings sit beside the hash checks without changing any
+//! chunk's status. The share of chunks within tolerance is blended into the
+//! confidence score.
+//!
+//! A chunk with nothing numeric at the pointer, or whose data is encrypted
+//! or referenced rather than inline, is flagged and counts against the
+//! score, but takes no part in the aggregate.
+
+use crate::protocol::{
+    Aggregation, ChunkConsistency, ConsistencyCheck, ConsistencyReport, ConsistencyStatus,
+    SourceDeviation, Tolerance,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Reject a check that could never be met or read
+pub fn validate(check: &ConsistencyCheck) -> Result<(), String> {
+    if !check.pointer.is_empty() && !check.pointer.starts_with('/') {
+        return Err(format!(
+            "pointer {:?} is not a JSON pointer; it must start with /",
+            check.pointer
+        ));
+    }
+    let (Tolerance::Absolute(tolerance) | Tolerance::Percent(tolerance)) = check.tolerance;
+    if !tolerance.is_finite() || tolerance < 0.0 {
+        return Err(format!(
+            "tolerance {} is not a finite, non-negative number",
+            tolerance
+        ));
+    }
+    Ok(())
+}
+
+/// Value at `pointer` in a chunk's inline `data`, which is `None` when the
+/// chunk's data is encrypted or referenced. A string holding a number
+/// counts, as price feeds often send them that way.
+pub fn read(data: Option<&Value>, pointer: &str) -> Result<f64, ConsistencyStatus> {
+    let data = data.ok_or(ConsistencyStatus::Unavailable)?;
+    let value = match data.pointer(pointer) {
+        None | Some(Value::Null) => return Err(ConsistencyStatus::MissingValue),
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.trim().parse::<f64>().ok(),
+        Some(_) => None,
+    };
+    value
+        .filter(|v| v.is_finite())
+        .ok_or(ConsistencyStatus::NonNumeric)
+}
+
+/// Judge every verified chunk's value, given with its source in input
+/// order, against the aggregate of them all. Returns each chunk's finding,
+/// in the same order, and the task's report.
+pub fn evaluate(
+    check: &ConsistencyCheck,
+    weight: u8,
+    samples: &[(&str, Result<f64, ConsistencyStatus>)],
+) -> (Vec<ChunkConsistency>, ConsistencyReport) {
+    let mut values: Vec<f64> = samples.iter().filter_map(|(_, v)| v.ok()).collect();
+    let aggregate = combine(check.aggregation, &mut values);
+
+    let findings: Vec<ChunkConsistency> = samples
+        .iter()
+        .map(|(_, value)| match *value {
+            Ok(value) => {
+                // Any value at all makes an aggregate
+                let aggregate = aggregate.unwrap_or(value);
+                let deviation = value - aggregate;
+                ChunkConsistency {
+                    status: if within(check.tolerance, deviation, aggregate) {
+                        ConsistencyStatus::Consistent
+                    } else {
+                        ConsistencyStatus::Outlier
+                    },
+                    value: Some(value),
+                    deviation: Some(deviation),
+                }
+            }
+            Err(status) => ChunkConsistency {
+                status,
+                value: None,
+                deviation: None,
+            },
+        })
+        .collect();
+
+    let count = |status| findings.iter().filter(|f| f.status == status).count();
+    let consistent = count(ConsistencyStatus::Consistent);
+    let outliers = count(ConsistencyStatus::Outlier);
+    let checked = findings.len();
+    let score = if checked == 0 {
+        0
+    } else {
+        (consistent as f64 / checked as f64 * 100.0).round() as u8
+    };
+
+    let mut by_source: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for (source, value) in samples {
+        if let Ok(value) = value {
+            by_source.entry(source).or_default().push(*value);
+        }
+    }
+    let source_deviations = match aggregate {
+        Some(overall) => by_source
+            .into_iter()
+            .filter_map(|(source, mut values)| {
+                let value = combine(check.aggregation, &mut values)?;
+                let deviation = value - overall;
+                Some(SourceDeviation {
+                    source: source.to_string(),
+                    value,
+                    deviation,
+                    outlier: !within(check.tolerance, deviation, overall),
+                })
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let report = ConsistencyReport {
+        pointer: check.pointer.clone(),
+        aggregation: check.aggregation,
+        tolerance: check.tolerance,
+        aggregate,
+        checked,
+        consistent,
+        outliers,
+        unreadable: checked - consistent - outliers,
+        score,
+        weight,
+        source_deviations,
+    };
+    (findings, report)
+}
+
+/// Blend a coverage confidence with a consistency score, giving the
+/// latter `weight` percent
+pub fn blend(confidence: f64, report: &ConsistencyReport) -> f64 {
+    let weight = f64::from(report.weight.min(100)) / 100.0;
+    confidence * (1.0 - weight) + f64::from(report.score) * weight
+}
+
+fn combine(aggregation: Aggregation, values: &mut [f64]) -> Option<f64> {
+    match aggregation {
+        Aggregation::Median => median(values),
+    }
+}
+
+/// Median, the mean of the middle two for an even count
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+/// Whether `deviation` from `aggregate` is within `tolerance`
+fn within(tolerance: Tolerance, deviation: f64, aggregate: f64) -> bool {
+    match tolerance {
+        Tolerance::Absolute(limit) => deviation.abs() <= limit,
+        Tolerance::Percent(percent) => deviation.abs() <= aggregate.abs() * percent / 100.0,
+    }
+}
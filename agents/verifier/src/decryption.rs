@@ -0,0 +1,96 @@
+//! Decryption of chunks that foragers encrypt to the verifier
+//!
+//! An encrypted chunk's `data` is a base64 AES-256-GCM ciphertext with the
+//! 16-byte tag appended. Its hash and signature are over the plaintext,
+//! which is only ever held in buffers that are zeroed when dropped.
+
+use crate::protocol::ChunkEncryption;
+use base64::Engine;
+use openssl::symm::{self, Cipher};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+/// The only algorithm accepted in a chunk's `encryption.alg`
+pub const AES_256_GCM: &str = "aes-256-gcm";
+
+const TAG_BYTES: usize = 16;
+
+/// Symmetric key for encrypted chunks, zeroed when dropped
+#[derive(Clone)]
+pub struct ChunkKey(Zeroizing<[u8; 32]>);
+
+impl ChunkKey {
+    /// Key derived from a secret, which foragers derive the same way:
+    /// BLAKE3 in key derivation mode over the secret's UTF-8 bytes
+    pub fn derive(secret: &str) -> Self {
+        Self(Zeroizing::new(blake3::derive_key(
+            "aetherswarm chunk decryption key v1",
+            secret.as_bytes(),
+        )))
+    }
+}
+
+impl std::fmt::Debug for ChunkKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ChunkKey(..)")
+    }
+}
+
+/// A task's `decryptionKey` is the secret, never kept once derived
+impl<'de> Deserialize<'de> for ChunkKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let secret = Zeroizing::new(String::deserialize(deserializer)?);
+        Ok(ChunkKey::derive(&secret))
+    }
+}
+
+/// Decrypt a chunk's `data`. Errors describe the envelope only, never the
+/// plaintext, so they are safe to log and report.
+pub fn decrypt(
+    encryption: &ChunkEncryption,
+    data: &Value,
+    key: &ChunkKey,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    if !encryption.alg.eq_ignore_ascii_case(AES_256_GCM) {
+        return Err(format!(
+            "unsupported encryption algorithm: {}",
+            encryption.alg
+        ));
+    }
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let nonce = b64
+        .decode(&encryption.nonce)
+        .map_err(|e| format!("nonce is not base64: {}", e))?;
+    if nonce.len() != 12 {
+        return Err(format!("nonce is {} bytes, expected 12", nonce.len()));
+    }
+    let aad = match &encryption.aad {
+        Some(aad) => b64
+            .decode(aad)
+            .map_err(|e| format!("aad is not base64: {}", e))?,
+        None => Vec::new(),
+    };
+    let sealed = data
+        .as_str()
+        .ok_or("encrypted data must be a base64 string")?;
+    let sealed = b64
+        .decode(sealed)
+        .map_err(|e| format!("ciphertext is not base64: {}", e))?;
+    if sealed.len() < TAG_BYTES {
+        return Err("ciphertext is shorter than the authentication tag".to_string());
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_BYTES);
+    symm::decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key.0[..],
+        Some(&nonce),
+        &aad,
+        ciphertext,
+        tag,
+    )
+    .map(Zeroizing::new)
+    // OpenSSL's errors say nothing useful here; a wrong key and a
+    // tampered chunk look the same
+    .map_err(|_| "authentication failed: wrong key or tampered chunk".to_string())
+}
@@ -1,108 +1,577 @@
 //! EigenCloud SDK for Verifiable Compute
 //! 
 //! Implements the EigenCompute SDK for TEE-based verification
-//! Uses Intel TDX attestation via EigenLayer's infrastructure
+//! Uses Intel TDX or SGX attestation via EigenLayer's infrastructure
 //! 
 //! EigenCloud Authentication:
 //! - Install: npm install -g @layr-labs/ecloud-cli
 //! - Auth: ecloud auth login (or ecloud auth generate --store)
 //! - Credentials stored in OS keyring
+//! - Or, without the CLI: EIGENCLOUD_BACKEND=api and EIGENCLOUD_API_TOKEN
+//!   (see [`crate::compute_backend`])
 
+use crate::attestation_cache::AttestationCache;
+use crate::breaker::{BreakerState, CircuitBreaker};
+use crate::canonical_json::canonicalize;
+use crate::clock::{Clock, SystemClock};
+use crate::compute_backend::{ApiBackend, BackendFuture, BackendKind, CliBackend, ComputeBackend};
+use crate::error::VerifierError;
+use crate::metrics::METRICS;
+use crate::quote::{self, ParsedQuote};
+use crate::tee_backend::TeeBackend;
+use crate::tls;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Default timeout for a single request to the TEE container
+const DEFAULT_TEE_REQUEST_TIMEOUT_MS: u64 = 30_000;
+/// Default time allowed to open a connection to the TEE container
+const DEFAULT_TEE_CONNECT_TIMEOUT_MS: u64 = 5_000;
+/// Default timeout for a single ecloud CLI invocation or API request
+const DEFAULT_CLI_TIMEOUT_MS: u64 = 120_000;
+/// Default time between ecloud auth re-checks after startup
+const DEFAULT_AUTH_CHECK_INTERVAL_SECS: u64 = 300;
+/// Default EigenCloud REST API root for the `api` backend
+const DEFAULT_API_URL: &str = "https://api.eigencloud.xyz";
+/// Default idle keep-alive connections kept per TEE host
+const DEFAULT_POOL_MAX_IDLE_PER_HOST: u64 = 8;
+/// Default lifetime of an idle pooled connection
+const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+/// Default interval of TCP and HTTP/2 keep-alive probes to the TEE host
+const DEFAULT_TEE_KEEPALIVE_SECS: u64 = 30;
+/// TEE container used when none is configured or discovered
+const DEFAULT_TEE_CONTAINER_URL: &str = "http://localhost:8090";
+/// Image name prefix identifying verification container deployments
+const DEFAULT_TEE_IMAGE: &str = "aetherswarm-verifier";
+/// Default attempts per TEE call, including the first
+const DEFAULT_TEE_RETRY_ATTEMPTS: u64 = 3;
+/// Default delay before the first retry, doubled for each further one
+const DEFAULT_TEE_RETRY_BASE_DELAY_MS: u64 = 200;
+/// Default consecutive failures that open the circuit breaker
+const DEFAULT_TEE_BREAKER_THRESHOLD: u64 = 5;
+/// Default time the breaker stays open before a trial call
+const DEFAULT_TEE_BREAKER_COOLDOWN_SECS: u64 = 30;
+/// Default number of attestations kept for reuse
+const DEFAULT_ATTESTATION_CACHE_SIZE: usize = 256;
+/// Default time a cached attestation may be reused
+const DEFAULT_ATTESTATION_CACHE_TTL_SECS: u64 = 600;
+/// Default wait for a deployment started at startup to report `running`
+const DEFAULT_DEPLOY_TIMEOUT_SECS: u64 = 600;
+/// Delay between deployment status checks
+const DEPLOYMENT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Deployment states that will never become `running`
+const DEPLOYMENT_FAILED_STATES: &[&str] = &["failed", "error", "stopped", "terminated"];
+
+/// TEE hardware the attestation is requested from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TeeType {
+    /// Intel Trust Domain Extensions
+    #[default]
+    #[serde(rename = "TDX")]
+    Tdx,
+    /// Intel Software Guard Extensions
+    #[serde(rename = "SGX")]
+    Sgx,
+}
+
+impl TeeType {
+    /// Uppercase name, as used on the wire
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TeeType::Tdx => "TDX",
+            TeeType::Sgx => "SGX",
+        }
+    }
+}
+
+impl FromStr for TeeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tdx" => Ok(TeeType::Tdx),
+            "sgx" => Ok(TeeType::Sgx),
+            other => Err(format!("unsupported TEE type: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for TeeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 /// EigenCloud attestation response
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AttestationResponse {
     pub quote: String,
+    /// TEE type the container reports; older containers omit it
+    #[serde(rename = "teeType", default)]
+    pub tee_type: Option<TeeType>,
     #[serde(rename = "validatorPubkey")]
     pub validator_pubkey: String,
     pub signature: String,
+    /// Echo of the request's challenge nonce, covered by `signature`
+    #[serde(default)]
+    pub nonce: Option<String>,
+    /// When the container produced the attestation, in Unix seconds, by
+    /// its own clock; not covered by `signature`
+    #[serde(default)]
+    pub timestamp: Option<u64>,
     pub success: bool,
     pub error: Option<String>,
+    /// Served from the attestation cache rather than a fresh TEE call
+    #[serde(skip)]
+    pub cached: bool,
+    /// Produced by the agent itself in dev mode or hash-only mode, not by
+    /// TEE hardware; a container can't claim it
+    #[serde(skip)]
+    pub simulated: bool,
 }
 
 /// TEE deployment status
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct DeploymentStatus {
     pub id: String,
     pub status: String,
+    #[serde(default)]
+    pub image: Option<String>,
     pub address: Option<String>,
     pub logs: Option<String>,
+    /// Creation time as the backend reports it, RFC 3339 or Unix seconds
+    #[serde(
+        rename = "createdAt",
+        alias = "created_at",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub created_at: Option<String>,
+    pub region: Option<String>,
+}
+
+/// Settings for an [`EigenCompute`] client
+#[derive(Debug, Clone)]
+pub struct EigenComputeConfig {
+    /// "testnet" or "mainnet"
+    pub environment: String,
+    /// Simulate attestations locally instead of calling EigenCloud
+    pub dev_mode: bool,
+    /// Let dev mode run against `mainnet`, which is refused otherwise
+    pub allow_dev_on_mainnet: bool,
+    /// Hex-encoded ed25519 seed that signs simulated attestations
+    pub dev_attestation_key: Option<String>,
+    /// Passphrase the dev key is derived from when no seed is given; unset
+    /// uses a fixed, publicly derivable key
+    pub dev_attestation_seed: Option<String>,
+    pub tee_type: TeeType,
+    /// How deployments are managed: the ecloud CLI or the REST API
+    pub backend: BackendKind,
+    /// REST API root for the `api` backend
+    pub api_url: String,
+    /// Bearer token for the `api` backend
+    pub api_token: Option<String>,
+    /// Fixed TEE container URL; discovered via `ecloud list` when unset
+    pub tee_container_url: Option<String>,
+    /// Image prefix used to discover the TEE container
+    pub tee_image: String,
+    /// Image deployed at startup when no container URL is configured and
+    /// no deployment of it is running; unset leaves discovery to the first
+    /// TEE call
+    pub deploy_image: Option<String>,
+    /// How long startup waits for that deployment to report `running`
+    pub deploy_timeout: Duration,
+    /// Terminate the deployment started at startup when the agent exits
+    pub teardown_on_shutdown: bool,
+    /// Whole-request deadline for TEE container calls
+    pub tee_timeout: Duration,
+    /// Deadline for connecting to the TEE container
+    pub tee_connect_timeout: Duration,
+    /// PEM bundle trusted for an `https://` TEE container in addition to
+    /// the system roots
+    pub tee_ca_cert: Option<PathBuf>,
+    /// Required SHA-256 fingerprint of the TEE container's certificate
+    pub tee_pin_sha256: Option<String>,
+    /// Deadline for each ecloud CLI invocation or API request
+    pub cli_timeout: Duration,
+    /// How often auth is re-checked after startup; `None` checks only once
+    pub auth_check_interval: Option<Duration>,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration,
+    /// Interval of TCP keep-alive and HTTP/2 PING probes, so pooled
+    /// connections a middlebox has silently dropped are noticed before a
+    /// verification is sent down them; `None` sends none
+    pub keepalive: Option<Duration>,
+    /// Attempts per TEE call, including the first
+    pub retry_attempts: u32,
+    pub retry_base_delay: Duration,
+    /// Consecutive failures that open the circuit breaker
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    /// Attestations kept for reuse; zero disables the cache. Only
+    /// unchallenged attestations are cached, so the cache does nothing
+    /// unless `fresh_nonces` is off
+    pub attestation_cache_size: usize,
+    pub attestation_cache_ttl: Duration,
+    /// Hex MRTDs a TDX quote must match; empty accepts any measurement
+    pub allowed_measurements: Vec<String>,
+    /// Challenge the TEE with a random nonce when the task brings none.
+    /// Off, unchallenged attestations can be cached and containers that
+    /// ignore nonces still verify. On, every TEE call is challenged and
+    /// the attestation cache never hits.
+    pub fresh_nonces: bool,
 }
 
 /// EigenCompute client for TEE operations
-/// Uses ecloud CLI for authentication (credentials in OS keyring)
+/// Manages deployments through a [`ComputeBackend`]
 pub struct EigenCompute {
-    environment: String,  // "testnet" or "mainnet"
+    /// ecloud CLI or EigenCloud API
+    backend: Box<dyn ComputeBackend>,
     dev_mode: bool,
+    /// TEE hardware attestations are requested from
+    tee_type: TeeType,
+    /// Shared HTTP client for TEE container calls
+    http: reqwest::Client,
+    /// Per-request deadline for TEE container calls
+    tee_timeout: Duration,
+    /// Leaf certificate hash every TEE container response must come from
+    tee_pin: Option<[u8; 32]>,
+    /// Key that signs simulated attestations in dev mode
+    dev_key: SigningKey,
+    /// Time between auth re-checks after startup
+    auth_check_interval: Option<Duration>,
+    /// Configured TEE container URL, bypassing discovery
+    tee_container_url: Option<String>,
+    /// Image prefix used to discover the TEE container
+    tee_image: String,
+    /// TEE container address found by listing deployments, cached until it
+    /// fails
+    discovered_tee_url: std::sync::Mutex<Option<String>>,
+    deploy_image: Option<String>,
+    deploy_timeout: Duration,
+    teardown_on_shutdown: bool,
+    /// Deployment started by this agent, and its container URL
+    deployment: std::sync::Mutex<Option<(String, String)>>,
+    /// Attempts per TEE call, including the first
+    retry_attempts: u32,
+    /// Backoff before the first retry
+    retry_base_delay: Duration,
+    /// Stops calls to a TEE container that keeps failing
+    breaker: CircuitBreaker,
+    /// Recent verified attestations, reused for identical requests
+    attestation_cache: AttestationCache,
+    /// Accepted TDX measurements (lowercase hex MRTD); empty accepts any.
+    /// Replaced wholesale on reload.
+    allowed_measurements: std::sync::RwLock<HashSet<String>>,
+    fresh_nonces: bool,
+    /// Time for TEE requests and dev deployment IDs
+    clock: Arc<dyn Clock>,
 }
 
 impl EigenCompute {
-    pub fn new() -> Self {
-        let environment = std::env::var("EIGENCLOUD_ENVIRONMENT")
-            .unwrap_or_else(|_| "testnet".to_string());
-        let dev_mode = std::env::var("EIGENCLOUD_DEV_MODE").is_ok();
-        
-        Self {
-            environment,
-            dev_mode,
+    /// Panics if the TEE CA bundle or pin is unusable; `try_new` reports it
+    pub fn new(config: EigenComputeConfig) -> Self {
+        Self::try_new(config).expect("invalid TEE TLS settings")
+    }
+
+    /// Client for `config`, failing if `tee_ca_cert` can't be read or
+    /// `tee_pin_sha256` isn't a fingerprint
+    pub fn try_new(config: EigenComputeConfig) -> Result<Self, VerifierError> {
+        let backend: Box<dyn ComputeBackend> = match config.backend {
+            BackendKind::Cli => Box::new(CliBackend::new(&config.environment, config.cli_timeout)),
+            BackendKind::Api => Box::new(ApiBackend::new(
+                &config.api_url,
+                config.api_token.clone(),
+                &config.environment,
+                config.cli_timeout,
+            )),
+        };
+        Self::assemble(config, backend)
+    }
+
+    /// Client that manages deployments through `backend` instead of the one
+    /// `config.backend` names. Panics like `new`.
+    pub fn with_backend(config: EigenComputeConfig, backend: Box<dyn ComputeBackend>) -> Self {
+        Self::assemble(config, backend).expect("invalid TEE TLS settings")
+    }
+
+    /// Read the time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn assemble(
+        config: EigenComputeConfig,
+        backend: Box<dyn ComputeBackend>,
+    ) -> Result<Self, VerifierError> {
+        // One client for the lifetime of the agent so keep-alive connections
+        // are reused across verifications
+        let mut http = reqwest::Client::builder()
+            .timeout(config.tee_timeout)
+            .connect_timeout(config.tee_connect_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .tcp_keepalive(config.keepalive);
+        if let Some(interval) = config.keepalive {
+            // A PING unanswered within the connect timeout closes the
+            // connection; idle ones are probed too, as they are the ones
+            // reused for the next verification
+            http = http
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_timeout(config.tee_connect_timeout)
+                .http2_keep_alive_while_idle(true);
+        }
+        if let Some(path) = &config.tee_ca_cert {
+            for cert in tls::read_certificates(path)? {
+                let der = cert.to_der().map_err(|e| {
+                    VerifierError::InvalidConfig(format!("invalid CA certificate: {}", e))
+                })?;
+                let cert = reqwest::Certificate::from_der(&der).map_err(|e| {
+                    VerifierError::InvalidConfig(format!("invalid CA certificate: {}", e))
+                })?;
+                http = http.add_root_certificate(cert);
+            }
+        }
+        let tee_pin = config
+            .tee_pin_sha256
+            .as_deref()
+            .map(|pin| tls::parse_pin(pin, "TEE_CERT_PIN"))
+            .transpose()?;
+        // The pin is checked against the certificate each response came over
+        let http = http
+            .tls_info(tee_pin.is_some())
+            .build()
+            .map_err(|e| VerifierError::InvalidConfig(format!("TEE TLS setup failed: {}", e)))?;
+        // Simulated attestations on mainnet would look like real ones to
+        // anyone not checking `simulated`
+        if config.dev_mode && config.environment.eq_ignore_ascii_case("mainnet") {
+            if !config.allow_dev_on_mainnet {
+                return Err(VerifierError::InvalidConfig(
+                    "dev mode on mainnet needs ALLOW_DEV_ATTESTATIONS_ON_MAINNET=1".into(),
+                ));
+            }
+            warn!("Dev mode on mainnet: every attestation is simulated, not hardware-backed");
+        }
+        let allowed_measurements = normalize_measurements(&config.allowed_measurements);
+        let dev_key = match &config.dev_attestation_key {
+            Some(key) => decode_key_material(key.trim())
+                .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                .map(|seed| SigningKey::from_bytes(&seed))
+                .ok_or_else(|| {
+                    VerifierError::InvalidConfig(
+                        "DEV_ATTESTATION_KEY is not a hex-encoded 32-byte seed".into(),
+                    )
+                })?,
+            None => dev_signing_key(config.dev_attestation_seed.as_deref().unwrap_or_default()),
+        };
+        if config.attestation_cache_size > 0 && config.fresh_nonces && !config.dev_mode {
+            info!("Attestation cache unused: every TEE call carries a fresh nonce (TEE_FRESH_NONCE=false to cache)");
         }
+        if config.dev_mode {
+            warn_allowlist_bypassed(&allowed_measurements);
+            // Whoever checks simulated attestations offline pins this key
+            info!(
+                dev_pubkey = %hex::encode(dev_key.verifying_key().as_bytes()),
+                "Simulated attestations are signed with the dev key"
+            );
+        }
+
+        Ok(Self {
+            backend,
+            dev_mode: config.dev_mode,
+            tee_type: config.tee_type,
+            http,
+            tee_timeout: config.tee_timeout,
+            tee_pin,
+            dev_key,
+            auth_check_interval: config.auth_check_interval,
+            tee_container_url: config.tee_container_url,
+            tee_image: config.tee_image,
+            discovered_tee_url: std::sync::Mutex::new(None),
+            deploy_image: config.deploy_image,
+            deploy_timeout: config.deploy_timeout,
+            teardown_on_shutdown: config.teardown_on_shutdown,
+            deployment: std::sync::Mutex::new(None),
+            retry_attempts: config.retry_attempts.max(1),
+            retry_base_delay: config.retry_base_delay,
+            breaker: CircuitBreaker::new(config.breaker_threshold, config.breaker_cooldown),
+            attestation_cache: AttestationCache::new(
+                config.attestation_cache_size,
+                config.attestation_cache_ttl,
+            ),
+            allowed_measurements: std::sync::RwLock::new(allowed_measurements),
+            fresh_nonces: config.fresh_nonces,
+            clock: Arc::new(SystemClock),
+        })
     }
 
-    /// Check if ecloud CLI is authenticated
-    pub fn check_auth(&self) -> Result<String, String> {
-        let output = Command::new("ecloud")
-            .args(["auth", "whoami"])
-            .output()
-            .map_err(|e| format!("Failed to run ecloud CLI: {}. Install with: npm install -g @layr-labs/ecloud-cli", e))?;
+    /// Client configured from `EIGENCLOUD_*` and `TEE_*` environment
+    /// variables. Panics like `new`.
+    pub fn from_env() -> Self {
+        Self::new(EigenComputeConfig::from_env())
+    }
 
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
-        } else {
-            Err(format!(
-                "Not authenticated. Run: ecloud auth login\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ))
+    /// TEE hardware attestations are requested from
+    pub fn tee_type(&self) -> TeeType {
+        self.tee_type
+    }
+
+    /// Nonce to challenge the TEE with: the requested one, else a fresh
+    /// random one unless fresh nonces are turned off
+    pub fn challenge_nonce(&self, requested: Option<&str>) -> Option<String> {
+        match requested {
+            Some(nonce) => Some(nonce.to_string()),
+            None if self.fresh_nonces => Some(hex::encode(rand::random::<[u8; 16]>())),
+            None => None,
         }
     }
 
+    /// State of the TEE circuit breaker; anything but closed means degraded
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    /// Whether attestations are simulated locally instead of using EigenCloud
+    pub fn is_dev_mode(&self) -> bool {
+        self.dev_mode
+    }
+
+    /// Backend deployments are managed through
+    pub fn backend(&self) -> &dyn ComputeBackend {
+        self.backend.as_ref()
+    }
+
+    /// Check if the backend is authenticated
+    pub async fn check_auth(&self) -> Result<String, VerifierError> {
+        self.backend.check_auth().await
+    }
+
+    /// How often to re-check auth once the agent is running, if at all
+    pub fn auth_check_interval(&self) -> Option<Duration> {
+        self.auth_check_interval
+    }
+
     /// Deploy a verification container to EigenCloud TEE
     pub async fn deploy_verification_container(
         &self,
         image: &str,
-    ) -> Result<DeploymentStatus, String> {
+    ) -> Result<DeploymentStatus, VerifierError> {
         // In dev mode, skip actual deployment
         if self.dev_mode {
             return Ok(DeploymentStatus {
-                id: format!("dev-{}", SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()),
+                id: format!("dev-{}", self.clock.now_unix()),
                 status: "running".to_string(),
-                address: Some("http://localhost:8090".to_string()),
+                image: Some(image.to_string()),
+                address: Some(DEFAULT_TEE_CONTAINER_URL.to_string()),
                 logs: None,
+                created_at: None,
+                region: None,
             });
         }
 
-        // Use ecloud CLI to deploy
-        let output = Command::new("ecloud")
-            .args([
-                "deploy",
-                image,
-                "--env", &self.environment,
-                "--json"
-            ])
-            .output()
-            .map_err(|e| format!("Failed to deploy: {}", e))?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Deployment failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        self.backend.deploy(image).await
+    }
+
+    /// Poll the backend until deployment `id` reports `running` with an
+    /// address, failing if it reaches a terminal state or `timeout` passes
+    pub async fn wait_for_deployment(
+        &self,
+        id: &str,
+        timeout: Duration,
+    ) -> Result<DeploymentStatus, VerifierError> {
+        let started = Instant::now();
+        let mut last_status = "unknown".to_string();
+        loop {
+            match self.backend.status(id).await {
+                Ok(deployment) => match deployment {
+                    Some(d) if d.status.eq_ignore_ascii_case("running") && d.address.is_some() => {
+                        return Ok(d)
+                    }
+                    Some(d) if is_failed_state(&d.status) => {
+                        let logs = d.logs.map(|logs| format!(": {}", logs.trim()));
+                        return Err(VerifierError::DeploymentFailed(format!(
+                            "deployment {} is {}{}",
+                            id,
+                            d.status,
+                            logs.unwrap_or_default()
+                        )));
+                    }
+                    Some(d) => last_status = d.status,
+                    None => last_status = "not listed".to_string(),
+                },
+                // A slow or flaky status call is worth another try before the deadline
+                Err(e) => warn!(deployment_id = id, error = %e, "Deployment status check failed"),
+            }
+
+            let remaining = timeout.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                return Err(VerifierError::DeploymentFailed(format!(
+                    "deployment {} not running after {}s (last status: {})",
+                    id,
+                    timeout.as_secs(),
+                    last_status
+                )));
+            }
+            info!(deployment_id = id, status = %last_status, "Waiting for deployment");
+            tokio::time::sleep(DEPLOYMENT_POLL_INTERVAL.min(remaining)).await;
         }
+    }
 
-        serde_json::from_slice(&output.stdout)
-            .map_err(|e| format!("Failed to parse deployment response: {}", e))
+    /// Bring up the TEE container at startup when `deploy_image` is set and
+    /// no container URL is configured: reuse a running deployment of the
+    /// image, or deploy it and wait until it is running
+    pub async fn provision_container(&self) -> Result<(), VerifierError> {
+        let Some(image) = &self.deploy_image else {
+            return Ok(());
+        };
+        if self.dev_mode || self.tee_container_url.is_some() {
+            return Ok(());
+        }
+
+        let running = self.find_running_deployment(image).await;
+        if let Some(address) = running.and_then(|d| d.address) {
+            let url = container_url(address);
+            info!(url = %url, image = %image, "Using running TEE deployment");
+            *self.discovered_tee_url.lock().unwrap() = Some(url);
+            return Ok(());
+        }
+
+        info!(image = %image, "Deploying TEE verification container");
+        let deployed = self.deploy_verification_container(image).await?;
+        let running = self
+            .wait_for_deployment(&deployed.id, self.deploy_timeout)
+            .await?;
+        let url = container_url(running.address.unwrap_or_default());
+        info!(deployment_id = %running.id, url = %url, "TEE verification container running");
+        *self.deployment.lock().unwrap() = Some((running.id, url));
+        Ok(())
+    }
+
+    /// Terminate the deployment `provision_container` started, if
+    /// `teardown_on_shutdown` is set
+    pub async fn release_container(&self) {
+        if !self.teardown_on_shutdown {
+            return;
+        }
+        let Some((id, _)) = self.deployment.lock().unwrap().take() else {
+            return;
+        };
+
+        match self.backend.terminate(&id).await {
+            Ok(()) => info!(deployment_id = %id, "Terminated TEE deployment"),
+            Err(e) => error!(deployment_id = %id, error = %e, "Failed to terminate TEE deployment"),
+        }
     }
 
     /// Execute verification in TEE and get attestation
@@ -112,107 +581,949 @@ impl EigenCompute {
     pub async fn execute_verification(
         &self,
         data_hash: &str,
+        data_hash_algorithm: &str,
         verified_hashes: &[String],
         quest_id: &str,
-    ) -> Result<AttestationResponse, String> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        nonce: Option<&str>,
+    ) -> Result<AttestationResponse, VerifierError> {
+        let timestamp = self.clock.now_unix();
 
         // In dev mode, generate local attestation
         if self.dev_mode {
-            return Ok(self.generate_dev_attestation(data_hash, quest_id));
+            return Ok(self.generate_dev_attestation(data_hash, quest_id, nonce));
+        }
+
+        // A nonce binds the attestation to one challenge, so only
+        // unchallenged requests can share one. With fresh nonces on every
+        // request carries a nonce and this never hits.
+        let cacheable = nonce.is_none();
+        if cacheable {
+            if let Some(mut cached) = self.attestation_cache.get(data_hash, data_hash_algorithm) {
+                METRICS.attestation_cache_hits.fetch_add(1, Ordering::Relaxed);
+                info!(data_hash, "Reusing cached attestation");
+                cached.cached = true;
+                // The container's time is that of the original attestation
+                cached.timestamp = None;
+                return Ok(cached);
+            }
         }
 
         // In production, call the deployed TEE container
-        // The container provides attestation via TDX hardware
+        // The container provides attestation via TDX or SGX hardware
         let payload = serde_json::json!({
             "operation": "verify_data_integrity",
             "dataHash": data_hash,
             "verifiedHashes": verified_hashes,
             "questId": quest_id,
+            "nonce": nonce,
             "timestamp": timestamp,
-            "teeType": "TDX"
+            "teeType": self.tee_type.as_str()
         });
 
-        // Get deployment address from environment or use default
-        let tee_url = std::env::var("TEE_CONTAINER_URL")
-            .unwrap_or_else(|_| "http://localhost:8090".to_string());
+        // Only unreachable/5xx failures are retried; a 4xx or a bad
+        // attestation would fail the same way again
+        let mut attempt = 1;
+        loop {
+            if let Err(retry_in) = self.breaker.try_acquire() {
+                return Err(VerifierError::CircuitOpen(format!(
+                    "TEE container calls suspended, next trial in {}s",
+                    retry_in.as_secs()
+                )));
+            }
+
+            let tee_url = self.tee_url().await;
+            let started = Instant::now();
+            let result = self.request_attestation(&tee_url, &payload).await;
+            let elapsed = started.elapsed();
+            METRICS.tee_latency.observe(elapsed);
+            info!(
+                attempt,
+                latency_ms = elapsed.as_millis() as u64,
+                ok = result.is_ok(),
+                "TEE attestation call finished"
+            );
+
+            match result {
+                Err(VerifierError::TeeUnreachable(e)) => {
+                    self.breaker.record_failure();
+                    // A discovered deployment may have moved; look it up again
+                    self.discovered_tee_url.lock().unwrap().take();
+                    if attempt >= self.retry_attempts {
+                        error!(attempt, error = %e, "TEE call failed, retries exhausted");
+                        return Err(VerifierError::TeeUnreachable(format!(
+                            "{} (gave up after {} attempts)",
+                            e, attempt
+                        )));
+                    }
+                    let delay = self.retry_delay(attempt);
+                    warn!(
+                        attempt,
+                        max_attempts = self.retry_attempts,
+                        error = %e,
+                        retry_in_ms = delay.as_millis() as u64,
+                        "TEE call failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                other => {
+                    self.breaker.record_success();
+                    if let Ok(resp) = &other {
+                        if cacheable && self.verify_attestation(resp, data_hash, nonce).is_ok() {
+                            self.attestation_cache.insert(data_hash, data_hash_algorithm, resp.clone());
+                        }
+                    }
+                    return other;
+                }
+            }
+        }
+    }
+
+    /// Exponential backoff with full jitter before retry number `attempt`
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        let cap = self
+            .retry_base_delay
+            .saturating_mul(1u32 << (attempt - 1).min(16));
+        let jittered = rand::thread_rng().gen_range(0..=cap.as_millis() as u64);
+        Duration::from_millis(jittered)
+    }
+
+    /// TEE container base URL: the configured one if set, then the one
+    /// deployed at startup, then the address of a running verifier
+    /// deployment, otherwise localhost
+    async fn tee_url(&self) -> String {
+        if let Some(url) = &self.tee_container_url {
+            return url.clone();
+        }
+        if let Some((_, url)) = self.deployment.lock().unwrap().clone() {
+            return url;
+        }
+        if let Some(url) = self.discovered_tee_url.lock().unwrap().clone() {
+            return url;
+        }
+
+        let discovered = self
+            .find_running_deployment(&self.tee_image)
+            .await
+            .and_then(|d| d.address);
+        match discovered {
+            Some(address) => {
+                let url = container_url(address);
+                info!(url = %url, "Using discovered TEE container");
+                *self.discovered_tee_url.lock().unwrap() = Some(url.clone());
+                url
+            }
+            None => DEFAULT_TEE_CONTAINER_URL.to_string(),
+        }
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
+    /// POST the verification payload to the TEE container and decode the reply
+    async fn request_attestation(
+        &self,
+        tee_url: &str,
+        payload: &serde_json::Value,
+    ) -> Result<AttestationResponse, VerifierError> {
+        let response = self
+            .http
             .post(format!("{}/verify", tee_url))
-            .json(&payload)
+            .json(payload)
             .send()
             .await
-            .map_err(|e| format!("TEE container error: {}", e))?;
+            .map_err(|e| self.request_error(e))?;
+        if let Some(pin) = &self.tee_pin {
+            let der = response
+                .extensions()
+                .get::<reqwest::tls::TlsInfo>()
+                .and_then(|info| info.peer_certificate());
+            tls::check_pin(der, pin).map_err(|detail| {
+                VerifierError::CertificatePinMismatch(format!(
+                    "TEE container {} {}",
+                    tee_url, detail
+                ))
+            })?;
+        }
 
-        if !response.status().is_success() {
-            return Err(format!(
-                "TEE verification failed: {}",
-                response.text().await.unwrap_or_default()
-            ));
+        // 5xx means the container is unhealthy and may recover; 4xx means
+        // it rejected this particular request
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let detail = format!("{}: {}", status, body);
+            return Err(if status.is_server_error() {
+                VerifierError::TeeUnreachable(detail)
+            } else {
+                VerifierError::TeeRejected(detail)
+            });
         }
 
-        response
+        let mut attestation = response
             .json::<AttestationResponse>()
             .await
-            .map_err(|e| format!("Failed to parse attestation: {}", e))
+            .map_err(|e| {
+                if e.is_timeout() {
+                    self.request_error(e)
+                } else {
+                    VerifierError::AttestationParse(e.to_string())
+                }
+            })?;
+
+        if !attestation.success {
+            return Err(VerifierError::TeeRejected(
+                attestation
+                    .error
+                    .unwrap_or_else(|| "container reported failure".to_string()),
+            ));
+        }
+
+        match attestation.tee_type {
+            Some(reported) if reported != self.tee_type => {
+                return Err(VerifierError::AttestationInvalid(format!(
+                    "requested {} attestation, container returned {}",
+                    self.tee_type, reported
+                )));
+            }
+            Some(_) => {}
+            None => attestation.tee_type = Some(self.tee_type),
+        }
+
+        Ok(attestation)
+    }
+
+    /// Map a reqwest failure to a TEE error, calling out timeouts and
+    /// failed TLS handshakes explicitly
+    fn request_error(&self, e: reqwest::Error) -> VerifierError {
+        if e.is_timeout() {
+            return VerifierError::TeeUnreachable(format!(
+                "request timed out after {}ms",
+                self.tee_timeout.as_millis()
+            ));
+        }
+        // Both surface as connect errors; only a native-tls error in the
+        // cause chain marks a certificate problem rather than the network
+        let mut cause: &dyn std::error::Error = &e;
+        let mut tls_failed = false;
+        while let Some(source) = cause.source() {
+            tls_failed |= source.is::<native_tls::Error>();
+            cause = source;
+        }
+        if tls_failed {
+            VerifierError::TlsValidation(format!(
+                "TLS handshake with TEE container failed: {}",
+                cause
+            ))
+        } else if e.is_connect() {
+            VerifierError::TeeUnreachable(format!("network error: {}", e))
+        } else {
+            VerifierError::TeeUnreachable(e.to_string())
+        }
     }
 
     /// Generate a development attestation (NOT for production)
-    /// This simulates what EigenCloud TEE would return
-    fn generate_dev_attestation(&self, data_hash: &str, quest_id: &str) -> AttestationResponse {
+    /// This simulates what EigenCloud TEE would return, signed with the dev key
+    fn generate_dev_attestation(
+        &self,
+        data_hash: &str,
+        quest_id: &str,
+        nonce: Option<&str>,
+    ) -> AttestationResponse {
         use blake3::Hasher;
         
         let mut hasher = Hasher::new();
         hasher.update(data_hash.as_bytes());
         hasher.update(quest_id.as_bytes());
+        if let Some(nonce) = nonce {
+            hasher.update(nonce.as_bytes());
+        }
         hasher.update(b"eigencloud_dev_attestation");
         
         let quote_hash = hasher.finalize();
-        
+        let quote = format!("DEV_{}_QUOTE_{}", self.tee_type, quote_hash.to_hex());
+        let signature = self.dev_key.sign(&attestation_payload(data_hash, &quote, nonce));
+
         AttestationResponse {
-            quote: format!("DEV_TDX_QUOTE_{}", quote_hash.to_hex()),
-            validator_pubkey: format!("DEV_PUBKEY_{}", &quote_hash.to_hex()[..16]),
-            signature: format!("DEV_SIG_{}", &quote_hash.to_hex()[16..48]),
+            quote,
+            tee_type: Some(self.tee_type),
+            validator_pubkey: hex::encode(self.dev_key.verifying_key().as_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+            nonce: nonce.map(str::to_string),
+            timestamp: None,
             success: true,
             error: None,
+            cached: false,
+            simulated: true,
         }
     }
 
-    /// List deployed applications
-    pub fn list_deployments(&self) -> Result<String, String> {
-        let output = Command::new("ecloud")
-            .args(["list", "--env", &self.environment])
-            .output()
-            .map_err(|e| format!("Failed to list deployments: {}", e))?;
-
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
-        } else {
-            Err(String::from_utf8_lossy(&output.stderr).to_string())
+    /// Check that an attestation's signature covers `data_hash`, and the
+    /// challenge `nonce` if one was sent, under the pubkey it claims. In dev
+    /// mode the pubkey must also be the dev key.
+    ///
+    /// TDX quotes are parsed, must bind `data_hash`, `nonce` and the pubkey
+    /// in their report data (see [`quote::report_data`]) and, if an
+    /// allowlist is set, carry an allowed MRTD; the parsed quote
+    /// is returned. Simulated quotes are only accepted in dev mode, where
+    /// they skip the allowlist, and yield `None`, as do SGX quotes.
+    pub fn verify_attestation(
+        &self,
+        resp: &AttestationResponse,
+        data_hash: &str,
+        nonce: Option<&str>,
+    ) -> Result<Option<ParsedQuote>, VerifierError> {
+        if nonce.is_some() && resp.nonce.as_deref() != nonce {
+            return Err(VerifierError::AttestationInvalid(
+                "attestation does not echo the challenge nonce".into(),
+            ));
+        }
+
+        let pubkey_bytes: [u8; 32] = decode_key_material(&resp.validator_pubkey)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| VerifierError::AttestationInvalid("malformed validator pubkey".into()))?;
+        let pubkey = VerifyingKey::from_bytes(&pubkey_bytes)
+            .map_err(|e| VerifierError::AttestationInvalid(format!("invalid validator pubkey: {}", e)))?;
+
+        if self.dev_mode && pubkey != self.dev_key.verifying_key() {
+            return Err(VerifierError::AttestationInvalid(
+                "dev attestation not signed by the dev key".into(),
+            ));
+        }
+
+        let signature_bytes: [u8; 64] = decode_key_material(&resp.signature)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| VerifierError::AttestationInvalid("malformed signature".into()))?;
+        let signature = Signature::from_bytes(&signature_bytes);
+
+        pubkey
+            .verify(&attestation_payload(data_hash, &resp.quote, nonce), &signature)
+            .map_err(|_| VerifierError::AttestationInvalid(
+                "signature does not cover the attested data hash and nonce".into(),
+            ))?;
+
+        if quote::is_simulated(&resp.quote) {
+            if !self.dev_mode {
+                return Err(VerifierError::AttestationInvalid(
+                    "simulated quote outside dev mode".into(),
+                ));
+            }
+            return Ok(None);
+        }
+        if self.tee_type == TeeType::Sgx {
+            return Ok(None);
         }
+        let parsed = ParsedQuote::parse(&resp.quote)?;
+        parsed.check_report_data(data_hash, nonce, &pubkey_bytes)?;
+        self.check_measurement(&parsed.mrtd)?;
+        Ok(Some(parsed))
     }
 
-    /// Get logs from a deployment
-    pub fn get_logs(&self, deployment_id: &str) -> Result<String, String> {
-        let output = Command::new("ecloud")
-            .args(["logs", deployment_id, "--env", &self.environment])
-            .output()
-            .map_err(|e| format!("Failed to get logs: {}", e))?;
-
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
-        } else {
-            Err(String::from_utf8_lossy(&output.stderr).to_string())
+    /// Reject measurements missing from a non-empty allowlist
+    fn check_measurement(&self, mrtd: &str) -> Result<(), VerifierError> {
+        let allowed = self.allowed_measurements.read().unwrap();
+        if allowed.is_empty() || allowed.contains(&mrtd.to_ascii_lowercase()) {
+            return Ok(());
         }
+        warn!(mrtd, allowed = allowed.len(), "Attestation measurement not in allowlist");
+        Err(VerifierError::MeasurementNotAllowed(mrtd.to_string()))
+    }
+
+    /// Replace the measurement allowlist, e.g. after the container image
+    /// was rotated; an empty list accepts any measurement
+    pub fn set_allowed_measurements(&self, measurements: &[String]) {
+        let normalized = normalize_measurements(measurements);
+        info!(count = normalized.len(), "Updated allowed TEE measurements");
+        if self.dev_mode {
+            warn_allowlist_bypassed(&normalized);
+        }
+        *self.allowed_measurements.write().unwrap() = normalized;
+    }
+
+    /// List deployed applications
+    pub async fn list_deployments(&self) -> Result<Vec<DeploymentStatus>, VerifierError> {
+        self.backend.list().await
+    }
+
+    /// First running deployment whose image starts with `image_prefix`
+    pub async fn find_running_deployment(&self, image_prefix: &str) -> Option<DeploymentStatus> {
+        let deployments = match self.list_deployments().await {
+            Ok(deployments) => deployments,
+            Err(e) => {
+                warn!(error = %e, "Deployment discovery failed");
+                return None;
+            }
+        };
+
+        deployments.into_iter().find(|d| {
+            d.status.eq_ignore_ascii_case("running")
+                && d.image
+                    .as_deref()
+                    .is_some_and(|image| image.starts_with(image_prefix))
+        })
+    }
+
+    /// Current state of deployment `id`
+    pub async fn get_deployment(&self, id: &str) -> Result<DeploymentStatus, VerifierError> {
+        self.backend
+            .status(id)
+            .await?
+            .ok_or_else(|| VerifierError::DeploymentNotFound(id.to_string()))
+    }
+
+    /// Stop deployment `id`, keeping it for a later restart or update
+    pub async fn stop_deployment(&self, id: &str) -> Result<(), VerifierError> {
+        self.backend.stop(id).await
+    }
+
+    /// Roll deployment `id` to `image`, returning its state once the
+    /// backend accepted the change; poll with `wait_for_deployment` for it
+    /// to be running again
+    pub async fn update_deployment(
+        &self,
+        id: &str,
+        image: &str,
+    ) -> Result<DeploymentStatus, VerifierError> {
+        self.backend.update(id, image).await
+    }
+
+    /// Get logs from a deployment
+    pub async fn get_logs(&self, deployment_id: &str) -> Result<String, VerifierError> {
+        self.backend.logs(deployment_id).await
     }
 }
 
-impl Default for EigenCompute {
+impl TeeBackend for EigenCompute {
+    fn name(&self) -> &'static str {
+        "eigencloud"
+    }
+
+    fn tee_type(&self) -> TeeType {
+        self.tee_type
+    }
+
+    fn is_dev_mode(&self) -> bool {
+        self.dev_mode
+    }
+
+    fn challenge_nonce(&self, requested: Option<&str>) -> Option<String> {
+        EigenCompute::challenge_nonce(self, requested)
+    }
+
+    fn execute_verification<'a>(
+        &'a self,
+        data_hash: &'a str,
+        data_hash_algorithm: &'a str,
+        verified_hashes: &'a [String],
+        quest_id: &'a str,
+        nonce: Option<&'a str>,
+    ) -> BackendFuture<'a, AttestationResponse> {
+        Box::pin(EigenCompute::execute_verification(
+            self,
+            data_hash,
+            data_hash_algorithm,
+            verified_hashes,
+            quest_id,
+            nonce,
+        ))
+    }
+
+    fn verify_attestation(
+        &self,
+        attestation: &AttestationResponse,
+        data_hash: &str,
+        nonce: Option<&str>,
+    ) -> Result<Option<ParsedQuote>, VerifierError> {
+        EigenCompute::verify_attestation(self, attestation, data_hash, nonce)
+    }
+
+    fn breaker_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    fn set_allowed_measurements(&self, measurements: &[String]) {
+        EigenCompute::set_allowed_measurements(self, measurements)
+    }
+
+    fn check_auth(&self) -> BackendFuture<'_, String> {
+        self.backend.check_auth()
+    }
+
+    fn auth_check_interval(&self) -> Option<Duration> {
+        self.auth_check_interval
+    }
+
+    fn provision(&self) -> BackendFuture<'_, ()> {
+        Box::pin(self.provision_container())
+    }
+
+    fn release(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            self.release_container().await;
+            Ok(())
+        })
+    }
+}
+
+impl Default for EigenComputeConfig {
     fn default() -> Self {
-        Self::new()
+        Self {
+            environment: "testnet".to_string(),
+            dev_mode: false,
+            allow_dev_on_mainnet: false,
+            dev_attestation_key: None,
+            dev_attestation_seed: None,
+            tee_type: TeeType::default(),
+            backend: BackendKind::default(),
+            api_url: DEFAULT_API_URL.to_string(),
+            api_token: None,
+            tee_container_url: None,
+            tee_image: DEFAULT_TEE_IMAGE.to_string(),
+            deploy_image: None,
+            deploy_timeout: Duration::from_secs(DEFAULT_DEPLOY_TIMEOUT_SECS),
+            teardown_on_shutdown: false,
+            tee_timeout: Duration::from_millis(DEFAULT_TEE_REQUEST_TIMEOUT_MS),
+            tee_connect_timeout: Duration::from_millis(DEFAULT_TEE_CONNECT_TIMEOUT_MS),
+            tee_ca_cert: None,
+            tee_pin_sha256: None,
+            cli_timeout: Duration::from_millis(DEFAULT_CLI_TIMEOUT_MS),
+            auth_check_interval: Some(Duration::from_secs(DEFAULT_AUTH_CHECK_INTERVAL_SECS)),
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST as usize,
+            pool_idle_timeout: Duration::from_secs(DEFAULT_POOL_IDLE_TIMEOUT_SECS),
+            keepalive: Some(Duration::from_secs(DEFAULT_TEE_KEEPALIVE_SECS)),
+            retry_attempts: DEFAULT_TEE_RETRY_ATTEMPTS as u32,
+            retry_base_delay: Duration::from_millis(DEFAULT_TEE_RETRY_BASE_DELAY_MS),
+            breaker_threshold: DEFAULT_TEE_BREAKER_THRESHOLD as u32,
+            breaker_cooldown: Duration::from_secs(DEFAULT_TEE_BREAKER_COOLDOWN_SECS),
+            attestation_cache_size: DEFAULT_ATTESTATION_CACHE_SIZE,
+            attestation_cache_ttl: Duration::from_secs(DEFAULT_ATTESTATION_CACHE_TTL_SECS),
+            allowed_measurements: Vec::new(),
+            fresh_nonces: true,
+        }
+    }
+}
+
+impl EigenComputeConfig {
+    /// Read settings from the environment, keeping defaults for unset or
+    /// invalid values
+    pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    /// Overlay environment variables onto these settings; unset or invalid
+    /// variables leave the current value in place
+    pub fn with_env(self) -> Self {
+        let tee_type = match std::env::var("EIGENCLOUD_TEE_TYPE") {
+            Ok(v) => v.parse().unwrap_or_else(|e| {
+                warn!(error = %e, fallback = %self.tee_type, "Invalid EIGENCLOUD_TEE_TYPE");
+                self.tee_type
+            }),
+            Err(_) => self.tee_type,
+        };
+
+        let backend = match std::env::var("EIGENCLOUD_BACKEND") {
+            Ok(v) => v.parse().unwrap_or_else(|e| {
+                warn!(error = %e, fallback = %self.backend, "Invalid EIGENCLOUD_BACKEND");
+                self.backend
+            }),
+            Err(_) => self.backend,
+        };
+
+        Self {
+            environment: std::env::var("EIGENCLOUD_ENVIRONMENT").unwrap_or(self.environment),
+            dev_mode: self.dev_mode || std::env::var("EIGENCLOUD_DEV_MODE").is_ok(),
+            allow_dev_on_mainnet: match std::env::var("ALLOW_DEV_ATTESTATIONS_ON_MAINNET") {
+                Ok(v) => matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"),
+                Err(_) => self.allow_dev_on_mainnet,
+            },
+            dev_attestation_key: std::env::var("DEV_ATTESTATION_KEY")
+                .ok()
+                .filter(|k| !k.is_empty())
+                .or(self.dev_attestation_key),
+            dev_attestation_seed: std::env::var("DEV_ATTESTATION_SEED")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .or(self.dev_attestation_seed),
+            tee_type,
+            backend,
+            api_url: std::env::var("EIGENCLOUD_API_URL")
+                .ok()
+                .filter(|u| !u.is_empty())
+                .unwrap_or(self.api_url),
+            api_token: std::env::var("EIGENCLOUD_API_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty())
+                .or(self.api_token),
+            tee_container_url: std::env::var("TEE_CONTAINER_URL")
+                .ok()
+                .filter(|u| !u.is_empty())
+                .or(self.tee_container_url),
+            tee_image: std::env::var("EIGENCLOUD_VERIFIER_IMAGE").unwrap_or(self.tee_image),
+            // Set but empty turns startup deployment off
+            deploy_image: match std::env::var("TEE_IMAGE") {
+                Ok(image) if image.is_empty() => None,
+                Ok(image) => Some(image),
+                Err(_) => self.deploy_image,
+            },
+            deploy_timeout: Duration::from_secs(env_u64(
+                "TEE_DEPLOY_TIMEOUT_SECS",
+                self.deploy_timeout.as_secs(),
+            )),
+            teardown_on_shutdown: match std::env::var("TEE_TEARDOWN_ON_SHUTDOWN") {
+                Ok(v) => matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"),
+                Err(_) => self.teardown_on_shutdown,
+            },
+            tee_timeout: Duration::from_millis(env_u64(
+                "TEE_REQUEST_TIMEOUT_MS",
+                self.tee_timeout.as_millis() as u64,
+            )),
+            tee_connect_timeout: Duration::from_millis(env_u64(
+                "TEE_CONNECT_TIMEOUT_MS",
+                self.tee_connect_timeout.as_millis() as u64,
+            )),
+            // Set but empty clears a value from the config file
+            tee_ca_cert: match std::env::var("TEE_CA_FILE") {
+                Ok(path) if path.is_empty() => None,
+                Ok(path) => Some(PathBuf::from(path)),
+                Err(_) => self.tee_ca_cert,
+            },
+            tee_pin_sha256: match std::env::var("TEE_CERT_PIN") {
+                Ok(pin) if pin.is_empty() => None,
+                Ok(pin) => Some(pin),
+                Err(_) => self.tee_pin_sha256,
+            },
+            cli_timeout: Duration::from_millis(env_u64(
+                "ECLOUD_CLI_TIMEOUT_MS",
+                self.cli_timeout.as_millis() as u64,
+            )),
+            // Zero turns the re-checks off
+            auth_check_interval: match std::env::var("ECLOUD_AUTH_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => self.auth_check_interval,
+            },
+            pool_max_idle_per_host: env_u64(
+                "TEE_POOL_MAX_IDLE_PER_HOST",
+                self.pool_max_idle_per_host as u64,
+            ) as usize,
+            pool_idle_timeout: Duration::from_secs(env_u64(
+                "TEE_POOL_IDLE_TIMEOUT_SECS",
+                self.pool_idle_timeout.as_secs(),
+            )),
+            // Zero turns the probes off
+            keepalive: match std::env::var("TEE_KEEPALIVE_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => self.keepalive,
+            },
+            retry_attempts: env_u64("TEE_RETRY_ATTEMPTS", self.retry_attempts as u64) as u32,
+            retry_base_delay: Duration::from_millis(env_u64(
+                "TEE_RETRY_BASE_DELAY_MS",
+                self.retry_base_delay.as_millis() as u64,
+            )),
+            breaker_threshold: env_u64("TEE_BREAKER_THRESHOLD", self.breaker_threshold as u64)
+                as u32,
+            breaker_cooldown: Duration::from_secs(env_u64(
+                "TEE_BREAKER_COOLDOWN_SECS",
+                self.breaker_cooldown.as_secs(),
+            )),
+            // Zero is meaningful here: it turns the cache off
+            attestation_cache_size: std::env::var("TEE_ATTESTATION_CACHE_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(self.attestation_cache_size),
+            attestation_cache_ttl: Duration::from_secs(env_u64(
+                "TEE_ATTESTATION_CACHE_TTL_SECS",
+                self.attestation_cache_ttl.as_secs(),
+            )),
+            // Comma-separated; set but empty lifts the restriction.
+            // EXPECTED_MRTD is read when TEE_ALLOWED_MEASUREMENTS is unset.
+            allowed_measurements: match std::env::var("TEE_ALLOWED_MEASUREMENTS")
+                .or_else(|_| std::env::var("EXPECTED_MRTD"))
+            {
+                Ok(list) => list
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|m| !m.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                Err(_) => self.allowed_measurements,
+            },
+            fresh_nonces: match std::env::var("TEE_FRESH_NONCE") {
+                Ok(v) => matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"),
+                Err(_) => self.fresh_nonces,
+            },
+        }
+    }
+}
+
+/// Bytes an attestation signature is expected to cover; `nonce` is only
+/// part of it when the request carried one, so older containers still verify
+pub fn attestation_payload(data_hash: &str, quote: &str, nonce: Option<&str>) -> Vec<u8> {
+    let mut payload = serde_json::json!({
+        "dataHash": data_hash,
+        "quote": quote,
+    });
+    if let Some(nonce) = nonce {
+        payload["nonce"] = serde_json::json!(nonce);
+    }
+    canonicalize(&payload)
+}
+
+fn is_failed_state(status: &str) -> bool {
+    DEPLOYMENT_FAILED_STATES
+        .iter()
+        .any(|state| status.eq_ignore_ascii_case(state))
+}
+
+/// Deployments may list a bare `host:port`; the container speaks HTTP
+fn container_url(address: String) -> String {
+    if address.contains("://") {
+        address
+    } else {
+        format!("http://{}", address)
+    }
+}
+
+/// Simulated quotes carry no measurement, so a dev-mode agent can't hold
+/// them to an allowlist
+fn warn_allowlist_bypassed(allowed: &HashSet<String>) {
+    if !allowed.is_empty() {
+        warn!(
+            allowed = allowed.len(),
+            "Dev mode accepts simulated quotes without checking the measurement allowlist"
+        );
+    }
+}
+
+/// Lowercase hex without a 0x prefix, as `ParsedQuote::mrtd` is reported
+fn normalize_measurements(measurements: &[String]) -> HashSet<String> {
+    measurements
+        .iter()
+        .map(|m| m.trim())
+        .map(|m| m.strip_prefix("0x").unwrap_or(m).to_ascii_lowercase())
+        .filter(|m| !m.is_empty())
+        .collect()
+}
+
+/// Accept a string or a number, keeping either as a string
+fn string_or_number<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::String(s)) => Some(s),
+        Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// Key for simulated attestations derived from `seed`; the empty seed gives
+/// the fixed, publicly derivable default
+fn dev_signing_key(seed: &str) -> SigningKey {
+    SigningKey::from_bytes(&blake3::derive_key(
+        "aetherswarm dev attestation key v1",
+        seed.as_bytes(),
+    ))
+}
+
+/// Decode hex (optionally 0x-prefixed) or base64 key material
+fn decode_key_material(s: &str) -> Option<Vec<u8>> {
+    let trimmed = s.strip_prefix("0x").unwrap_or(s);
+    hex::decode(trimmed)
+        .ok()
+        .or_else(|| base64::engine::general_purpose::STANDARD.decode(s).ok())
+}
+
+/// Read a positive integer from the environment, falling back to a default
+fn env_u64(key: &str, default: u64) -> u64 {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quote::tests::tdx_quote;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    const HASH: &str = "aa000000000000000000000000000000000000000000000000000000000000bb";
+
+    fn compute() -> EigenCompute {
+        EigenCompute::new(EigenComputeConfig {
+            tee_type: TeeType::Tdx,
+            ..EigenComputeConfig::default()
+        })
+    }
+
+    /// Hex TDX quote whose report data binds `nonce` and `key`
+    fn bound_quote(nonce: Option<&str>, key: &SigningKey) -> String {
+        let report_data = quote::report_data(HASH, nonce, key.verifying_key().as_bytes());
+        hex::encode(tdx_quote(4, &report_data))
+    }
+
+    /// Response carrying `quote`, signed by `key` as an answer to `nonce`
+    fn response(quote: &str, nonce: Option<&str>, key: &SigningKey) -> AttestationResponse {
+        let signature = key.sign(&attestation_payload(HASH, quote, nonce));
+        AttestationResponse {
+            quote: quote.to_string(),
+            tee_type: Some(TeeType::Tdx),
+            validator_pubkey: hex::encode(key.verifying_key().as_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+            nonce: nonce.map(str::to_string),
+            timestamp: None,
+            success: true,
+            error: None,
+            cached: false,
+            simulated: false,
+        }
+    }
+
+    #[test]
+    fn accepts_a_quote_bound_to_its_nonce_and_key() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let quote = bound_quote(Some("n1"), &key);
+        let parsed = compute()
+            .verify_attestation(&response(&quote, Some("n1"), &key), HASH, Some("n1"))
+            .unwrap();
+        assert_eq!(parsed.unwrap().mrtd, "11".repeat(48));
+    }
+
+    #[test]
+    fn rejects_a_quote_replayed_against_a_new_nonce() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let quote = bound_quote(Some("n1"), &key);
+        // The signature is fresh and covers the new nonce; only the quote
+        // is replayed
+        let replayed = response(&quote, Some("n2"), &key);
+        assert!(compute()
+            .verify_attestation(&replayed, HASH, Some("n2"))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_a_quote_replayed_under_a_new_key() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let quote = bound_quote(Some("n1"), &key);
+        let other_key = SigningKey::from_bytes(&[2; 32]);
+        let replayed = response(&quote, Some("n1"), &other_key);
+        assert!(compute()
+            .verify_attestation(&replayed, HASH, Some("n1"))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_a_quote_carrying_only_the_data_hash() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let quote = hex::encode(tdx_quote(4, &hex::decode(HASH).unwrap()));
+        assert!(compute()
+            .verify_attestation(&response(&quote, None, &key), HASH, None)
+            .is_err());
+    }
+
+    /// Keep-alive HTTP/1.1 container that answers every request with a
+    /// successful attestation, and the count of connections it accepted
+    async fn keep_alive_container() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(answer_requests(stream));
+            }
+        });
+        (url, connections)
+    }
+
+    async fn answer_requests(stream: TcpStream) {
+        let reply = r#"{"quote":"Q","validatorPubkey":"00","signature":"00","success":true}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            reply.len(),
+            reply
+        );
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            if reader.read_exact(&mut body).await.is_err() {
+                return;
+            }
+            let stream = reader.get_mut();
+            if stream.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn production(tee_container_url: String) -> EigenComputeConfig {
+        EigenComputeConfig {
+            tee_container_url: Some(tee_container_url),
+            attestation_cache_size: 0,
+            ..EigenComputeConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn reuses_one_connection_across_verifications() {
+        let (url, connections) = keep_alive_container().await;
+        let compute = EigenCompute::new(production(url));
+        for quest in 0..5 {
+            compute
+                .execute_verification(HASH, "blake3", &[], &format!("q{}", quest), Some("n"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn times_out_a_container_that_never_answers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let compute = EigenCompute::new(EigenComputeConfig {
+            tee_timeout: Duration::from_millis(200),
+            retry_attempts: 1,
+            ..production(url)
+        });
+
+        let started = Instant::now();
+        let e = compute
+            .execute_verification(HASH, "blake3", &[], "q1", Some("n"))
+            .await
+            .unwrap_err();
+        assert!(matches!(e, VerifierError::TeeUnreachable(_)), "{}", e);
+        assert!(e.to_string().contains("timed out after 200ms"), "{}", e);
+        assert!(started.elapsed() < Duration::from_secs(5));
     }
 }
@@ -0,0 +1,151 @@
+//! Error types for the Verifier Agent
+//!
+//! Display strings keep the wording of the original log lines so existing
+//! log searches keep working; callers can match on the variant instead.
+
+use thiserror::Error;
+
+/// Failure modes of verification and EigenCloud operations
+#[derive(Debug, Clone, Error)]
+pub enum VerifierError {
+    /// ecloud CLI is not installed or could not be started
+    #[error("Failed to run ecloud CLI: {0}. Install with: npm install -g @layr-labs/ecloud-cli")]
+    CliMissing(String),
+    /// ecloud CLI is installed but not logged in
+    #[error("Not authenticated. Run: ecloud auth login\n{0}")]
+    CliUnauthenticated(String),
+    /// EigenCloud API token is missing or was refused
+    #[error("EigenCloud API rejected the request, check EIGENCLOUD_API_TOKEN: {0}")]
+    ApiUnauthenticated(String),
+    /// ecloud CLI did not finish within its timeout
+    #[error("ecloud CLI timed out: {0}")]
+    CliTimeout(String),
+    /// ecloud deploy failed or returned an unusable response
+    #[error("Deployment failed: {0}")]
+    DeploymentFailed(String),
+    /// Deployment does not exist, or is not visible to this account
+    #[error("Deployment not found: {0}")]
+    DeploymentNotFound(String),
+    /// Authenticated, but not allowed to act on the deployment
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    /// TEE container could not be reached or failed server-side
+    #[error("TEE container error: {0}")]
+    TeeUnreachable(String),
+    /// TEE container refused the request
+    #[error("TEE verification failed: {0}")]
+    TeeRejected(String),
+    /// TEE calls are suspended because the circuit breaker is open
+    #[error("TEE circuit breaker open: {0}")]
+    CircuitOpen(String),
+    /// TEE container answered but the attestation could not be decoded
+    #[error("Failed to parse attestation: {0}")]
+    AttestationParse(String),
+    /// Attestation signature does not check out locally
+    #[error("Invalid attestation: {0}")]
+    AttestationInvalid(String),
+    /// Quote measurement is not one of the allowed container images
+    #[error("Attestation rejected: measurement {0} is not allowed")]
+    MeasurementNotAllowed(String),
+    /// Coordinator's or TEE container's TLS certificate does not match the
+    /// configured pin
+    #[error("Certificate rejected: {0}")]
+    CertificatePinMismatch(String),
+    /// TLS handshake failed, typically an untrusted or invalid certificate
+    #[error("TLS validation failed: {0}")]
+    TlsValidation(String),
+    /// A signed message failed signature verification
+    #[error("Invalid signature: {0}")]
+    SignatureInvalid(String),
+    /// Coordinator message whose MAC does not match `AGENT_AUTH_TOKEN`
+    #[error("Message authentication failed: {0}")]
+    MessageAuthFailed(String),
+    /// No submitted chunk matched its claimed hash
+    #[error("Hash mismatch: {0}")]
+    HashMismatch(String),
+    /// Coordinator message with a `type` this agent does not handle
+    #[error("Unsupported message type: {0}")]
+    UnsupportedMessage(String),
+    /// Coordinator message that is not valid JSON or misses required fields
+    #[error("Malformed message: {0}")]
+    MalformedMessage(String),
+    /// Verification task without any chunks
+    #[error("Empty task: {0}")]
+    EmptyTask(String),
+    /// Task or message exceeds the agent's size limits
+    #[error("Task too large: {0}")]
+    TaskTooLarge(String),
+    /// Task ran past its deadline and its remaining work was dropped
+    #[error("Task timed out: {0}")]
+    TaskTimeout(String),
+    /// Agent has too many tasks queued to take another
+    #[error("Agent busy: {0}")]
+    Busy(String),
+    /// Task from a coordinator that is not the agent's primary, in
+    /// active-active mode
+    #[error("Not primary: {0}")]
+    NotPrimary(String),
+    /// Coordinator refused the agent's `register`, with its reason
+    #[error("Registration rejected: {0}")]
+    RegistrationRejected(String),
+    /// Configuration file is unreadable or invalid
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    /// A payload could not be serialized
+    #[error("Serialization failed: {0}")]
+    SerializationFailed(String),
+    /// Local I/O or CLI invocation failure
+    #[error("Transport error: {0}")]
+    Transport(String),
+}
+
+impl VerifierError {
+    /// Stable machine-readable code sent to the coordinator
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerifierError::CliMissing(_) => "cli_missing",
+            VerifierError::CliUnauthenticated(_) => "cli_unauthenticated",
+            VerifierError::ApiUnauthenticated(_) => "api_unauthenticated",
+            VerifierError::CliTimeout(_) => "cli_timeout",
+            VerifierError::DeploymentFailed(_) => "deployment_failed",
+            VerifierError::DeploymentNotFound(_) => "deployment_not_found",
+            VerifierError::PermissionDenied(_) => "permission_denied",
+            VerifierError::TeeUnreachable(_) => "tee_unreachable",
+            VerifierError::TeeRejected(_) => "tee_rejected",
+            VerifierError::CircuitOpen(_) => "circuit_open",
+            VerifierError::AttestationParse(_) => "attestation_parse",
+            VerifierError::AttestationInvalid(_) => "attestation_invalid",
+            VerifierError::MeasurementNotAllowed(_) => "attestation_rejected",
+            VerifierError::CertificatePinMismatch(_) => "certificate_pin_mismatch",
+            VerifierError::TlsValidation(_) => "tls_validation",
+            VerifierError::SignatureInvalid(_) => "signature_invalid",
+            VerifierError::MessageAuthFailed(_) => "message_auth_failed",
+            VerifierError::HashMismatch(_) => "hash_mismatch",
+            VerifierError::UnsupportedMessage(_) => "unsupported_message",
+            VerifierError::MalformedMessage(_) => "malformed_message",
+            VerifierError::EmptyTask(_) => "empty_task",
+            VerifierError::TaskTooLarge(_) => "task_too_large",
+            VerifierError::TaskTimeout(_) => "timeout",
+            VerifierError::Busy(_) => "busy",
+            VerifierError::NotPrimary(_) => "not_primary",
+            VerifierError::RegistrationRejected(_) => "registration_rejected",
+            VerifierError::InvalidConfig(_) => "invalid_config",
+            VerifierError::SerializationFailed(_) => "serialization_failed",
+            VerifierError::Transport(_) => "transport",
+        }
+    }
+
+    /// Whether the same request may succeed if tried again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            VerifierError::CliTimeout(_)
+                | VerifierError::TeeUnreachable(_)
+                | VerifierError::CircuitOpen(_)
+                | VerifierError::TaskTimeout(_)
+                | VerifierError::Busy(_)
+                | VerifierError::NotPrimary(_)
+                | VerifierError::Transport(_)
+        )
+    }
+}
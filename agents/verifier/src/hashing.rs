@@ -0,0 +1,238 @@
+//! Hash algorithms supported for chunk verification
+//!
+//! Blake3 is the native algorithm; SHA-256 and SHA-512 match upstream data
+//! providers and Keccak-256 matches on-chain settlement.
+
+use sha2::{Digest, Sha256, Sha512};
+use sha3::Keccak256;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Digest algorithm used to check a chunk's claimed hash and to build the
+/// aggregate Merkle tree; Blake3 unless a task or chunk names another
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+    Sha512,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    /// Every algorithm this build can check, advertised on registration
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Keccak256,
+    ];
+
+    /// Canonical lowercase name, as used on the wire
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Keccak256 => "keccak256",
+        }
+    }
+
+    /// Digest length in bytes
+    pub fn output_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha512 => 64,
+            _ => 32,
+        }
+    }
+
+    /// Hex-encoded digest of `bytes`
+    pub fn digest_hex(self, bytes: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Blake3 => blake3::hash(bytes).to_hex().to_string(),
+            HashAlgorithm::Sha256 => format!("{:x}", Sha256::digest(bytes)),
+            HashAlgorithm::Sha512 => format!("{:x}", Sha512::digest(bytes)),
+            HashAlgorithm::Keccak256 => format!("{:x}", Keccak256::digest(bytes)),
+        }
+    }
+
+    /// Incremental hasher, for data streamed rather than held in memory
+    pub fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Box::default()),
+            HashAlgorithm::Keccak256 => Hasher::Keccak256(Box::default()),
+        }
+    }
+}
+
+/// Running digest fed one piece at a time
+pub enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+    Sha512(Box<Sha512>),
+    Keccak256(Box<Keccak256>),
+}
+
+impl Hasher {
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Sha512(hasher) => hasher.update(bytes),
+            Hasher::Keccak256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Digest of everything fed in
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+            Hasher::Keccak256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+
+    /// Hex-encoded digest of everything fed in, same as `digest_hex` over
+    /// the concatenation
+    pub fn finalize_hex(self) -> String {
+        hex::encode(self.finalize())
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104) of the concatenated `parts` under `key`
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    // Keys longer than a block are hashed down first
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    for part in parts {
+        inner.update(part);
+    }
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// Lets serializers write straight into the digest
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "sha512" | "sha-512" => Ok(HashAlgorithm::Sha512),
+            "keccak256" | "keccak-256" => Ok(HashAlgorithm::Keccak256),
+            other => Err(format!("unsupported hash algorithm: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Digests of the empty input; a chunk claiming one of these hashes
+    /// carries no data, which the agent must not accept as verified
+    const EMPTY: [(HashAlgorithm, &str); 4] = [
+        (
+            HashAlgorithm::Blake3,
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+        ),
+        (
+            HashAlgorithm::Sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            HashAlgorithm::Sha512,
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+             47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+        ),
+        (
+            HashAlgorithm::Keccak256,
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        ),
+    ];
+
+    #[test]
+    fn digests_the_empty_input() {
+        for (algorithm, expected) in EMPTY {
+            assert_eq!(algorithm.digest_hex(b""), expected, "{}", algorithm);
+            assert_eq!(algorithm.hasher().finalize_hex(), expected, "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn streams_to_the_same_digest() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+        for algorithm in HashAlgorithm::ALL {
+            let mut hasher = algorithm.hasher();
+            for piece in data.chunks(7) {
+                hasher.update(piece);
+            }
+            let streamed = hasher.finalize_hex();
+            assert_eq!(streamed, algorithm.digest_hex(data), "{}", algorithm);
+            assert_eq!(streamed.len(), algorithm.output_len() * 2, "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        // Test cases 2 and 6: a short key, and one longer than a block
+        assert_eq!(
+            hex::encode(hmac_sha256(
+                b"Jefe",
+                &[b"what do ya want ", b"for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex::encode(hmac_sha256(
+                &[0xaa; 131],
+                &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn parses_algorithm_names() {
+        for algorithm in HashAlgorithm::ALL {
+            assert_eq!(algorithm.name().parse::<HashAlgorithm>(), Ok(algorithm));
+        }
+        assert_eq!(
+            "SHA-256".parse::<HashAlgorithm>(),
+            Ok(HashAlgorithm::Sha256)
+        );
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
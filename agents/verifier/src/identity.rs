@@ -0,0 +1,115 @@
+//! Agent identity key
+//!
+//! The agent holds an ed25519 key used to sign what it sends to the
+//! coordinator. The key is taken from `AGENT_PRIVATE_KEY` (hex-encoded seed)
+//! if set, otherwise read from the file at `AGENT_KEY_PATH`, or generated
+//! and written there on first run.
+
+use crate::error::VerifierError;
+use ed25519_dalek::{Signer, SigningKey};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fs;
+use std::path::Path;
+
+/// Key file used when `AGENT_KEY_PATH` is not set
+const DEFAULT_KEY_PATH: &str = "verifier-agent.key";
+
+/// Signing identity of this agent
+pub struct AgentIdentity {
+    signing_key: SigningKey,
+}
+
+impl AgentIdentity {
+    /// Use `AGENT_PRIVATE_KEY`, else load the key from `AGENT_KEY_PATH`,
+    /// generating it on first run
+    pub fn from_env() -> Result<Self, VerifierError> {
+        if let Ok(key) = std::env::var("AGENT_PRIVATE_KEY") {
+            return Self::from_hex(&key).ok_or_else(|| {
+                VerifierError::InvalidConfig("AGENT_PRIVATE_KEY is not a hex-encoded 32-byte seed".into())
+            });
+        }
+        let path = std::env::var("AGENT_KEY_PATH").unwrap_or_else(|_| DEFAULT_KEY_PATH.to_string());
+        Self::load_or_generate(Path::new(&path))
+    }
+
+    /// Key from a hex-encoded seed, optionally `0x`-prefixed
+    pub fn from_hex(seed: &str) -> Option<Self> {
+        let seed = seed.trim();
+        let seed: [u8; 32] = hex::decode(seed.strip_prefix("0x").unwrap_or(seed))
+            .ok()?
+            .try_into()
+            .ok()?;
+        Some(Self {
+            signing_key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// Load a hex-encoded seed from `path`, or create and persist a new one
+    pub fn load_or_generate(path: &Path) -> Result<Self, VerifierError> {
+        if path.exists() {
+            let contents = fs::read_to_string(path).map_err(|e| {
+                VerifierError::Transport(format!("Failed to read agent key {}: {}", path.display(), e))
+            })?;
+            return Self::from_hex(&contents).ok_or_else(|| {
+                VerifierError::Transport(format!(
+                    "Agent key {} is not a hex-encoded 32-byte seed",
+                    path.display()
+                ))
+            });
+        }
+
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        write_private(path, &hex::encode(seed)).map_err(|e| {
+            VerifierError::Transport(format!("Failed to persist agent key {}: {}", path.display(), e))
+        })?;
+        tracing::info!(path = %path.display(), "Generated new agent key");
+
+        Ok(Self {
+            signing_key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// Fresh key held only in memory, for tests and throwaway agents
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self {
+            signing_key: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// Hex-encoded public key, as advertised to the coordinator
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().as_bytes())
+    }
+
+    /// Hex-encoded signature over `message`
+    pub fn sign_hex(&self, message: &[u8]) -> String {
+        hex::encode(self.signing_key.sign(message).to_bytes())
+    }
+}
+
+/// Write a file readable only by the owner where the platform supports it
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents.as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        fs::write(path, contents)
+    }
+}
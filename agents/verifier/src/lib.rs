@@ -0,0 +1,47 @@
+//! AetherSwarm Verifier Agent - EigenCloud TEE Integration
+//!
+//! Implements Trusted Execution Environment verification using EigenCompute.
+//! Verifies data integrity and produces cryptographic attestations.
+
+mod agent;
+pub mod attestation_cache;
+pub mod audit_log;
+pub mod breaker;
+pub mod canonical_json;
+pub mod cbor;
+pub mod cli;
+pub mod clock;
+pub mod compute_backend;
+pub mod config;
+pub mod consistency;
+pub mod decryption;
+pub mod eigencloud_sdk;
+pub mod error;
+pub mod hashing;
+pub mod identity;
+pub mod logging;
+pub mod merkle;
+pub mod message_auth;
+pub mod metrics;
+pub mod msgpack;
+pub mod onchain;
+pub mod outbound;
+pub mod outbox;
+pub mod protocol;
+pub mod quote;
+pub mod rate_limit;
+pub mod recent_results;
+pub mod rules;
+mod secp256k1;
+pub mod source_stats;
+pub mod tee_backend;
+pub mod tls;
+
+pub use agent::{VerifierAgent, VerifierAgentBuilder, VerifierConfig, VerifierMode};
+pub use config::Config;
+pub use eigencloud_sdk::{EigenCompute, EigenComputeConfig};
+pub use error::VerifierError;
+pub use hashing::HashAlgorithm;
+pub use identity::AgentIdentity;
+pub use protocol::{DataChunk, TeeAttestation, VerificationResult, VerifyTask};
+pub use tee_backend::TeeBackend;
@@ -0,0 +1,35 @@
+//! Log output setup
+//!
+//! `RUST_LOG` selects levels (default `info`); `LOG_FORMAT=json` switches
+//! from human-readable lines to one JSON object per event for log shippers.
+
+use std::io::{self, IsTerminal};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+/// Install the global tracing subscriber
+pub fn init() {
+    install(io::stdout, io::stdout().is_terminal());
+}
+
+/// Install the global tracing subscriber on stderr, for commands whose
+/// stdout is their output
+pub fn init_stderr() {
+    install(io::stderr, io::stderr().is_terminal());
+}
+
+fn install<W>(writer: W, ansi: bool)
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).with_span_list(false).init(),
+        _ => builder.init(),
+    }
+}
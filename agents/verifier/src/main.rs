@@ -1,249 +1,204 @@
-//! AetherSwarm Verifier Agent - EigenCloud TEE Integration
-//! 
-//! Implements Trusted Execution Environment verification using EigenCompute.
-//! Verifies data integrity and produces cryptographic attestations.
+//! Verifier Agent binary: merges command-line flags, the environment and an
+//! optional configuration file, then runs the agent until shutdown
 
-use futures_util::{SinkExt, StreamExt};
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::json;
 use std::env;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use verifier_agent::cli::{Cli, Invocation, USAGE};
+use verifier_agent::logging;
+use verifier_agent::metrics::{self, AuthState, METRICS};
+use verifier_agent::{
+    AgentIdentity, Config, EigenCompute, VerifierAgent, VerifierMode, VerifyTask,
+};
 
-mod eigencloud_sdk;
-use eigencloud_sdk::EigenCompute;
-
-/// TEE Attestation result from EigenCloud
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TeeAttestation {
-    /// Intel TDX or SGX attestation quote
-    pub quote: String,
-    /// Blake3 hash of verified data
-    pub data_hash: String,
-    /// Timestamp of attestation
-    pub timestamp: u64,
-    /// Validator public key
-    pub validator_pubkey: String,
-    /// Signature over attestation
-    pub signature: String,
-    /// Reproducibility score (0-100)
-    pub confidence_score: u8,
-}
-
-/// Verification task from coordinator
-#[derive(Debug, Deserialize)]
-pub struct VerifyTask {
-    #[serde(rename = "type")]
-    pub task_type: String,
-    #[serde(rename = "questId")]
-    pub quest_id: String,
-    pub data: Vec<DataChunk>,
-    #[serde(rename = "expectedHashes")]
-    pub expected_hashes: Vec<String>,
-}
-
-/// Data chunk to verify
-#[derive(Debug, Deserialize, Serialize)]
-pub struct DataChunk {
-    pub source: String,
-    pub data: Value,
-    pub hash: String,
-    pub timestamp: u64,
-}
-
-/// Verification result
-#[derive(Debug, Serialize)]
-pub struct VerificationResult {
-    #[serde(rename = "type")]
-    pub result_type: String,
-    #[serde(rename = "questId")]
-    pub quest_id: String,
-    #[serde(rename = "agentId")]
-    pub agent_id: String,
-    pub status: String,
-    pub attestation: TeeAttestation,
-    #[serde(rename = "verifiedChunks")]
-    pub verified_chunks: Vec<String>,
-    #[serde(rename = "failedChunks")]
-    pub failed_chunks: Vec<String>,
-}
-
-/// Verifier Agent implementation
-pub struct VerifierAgent {
-    agent_id: String,
-    coordinator_url: String,
-    eigen_compute: EigenCompute,
-}
-
-impl VerifierAgent {
-    pub fn new() -> Self {
-        // EigenCompute now uses ecloud CLI for auth (stored in OS keyring)
-        let coordinator_url = env::var("COORDINATOR_WS_URL")
-            .unwrap_or_else(|_| "ws://localhost:8080".to_string());
-        let agent_id = env::var("AGENT_ID")
-            .unwrap_or_else(|_| "verifier-001".to_string());
-
-        Self {
-            agent_id,
-            coordinator_url,
-            eigen_compute: EigenCompute::new(),
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = match Cli::parse(env::args().skip(1)) {
+        Ok(Invocation::Run(cli)) => cli,
+        Ok(Invocation::VerifyFile { cli, path, attest }) => {
+            return verify_file(&cli, &path, attest).await
         }
-    }
-
-    /// Verify data integrity using Blake3 hashing
-    fn verify_hash(&self, data: &Value, expected_hash: &str) -> bool {
-        let data_bytes = serde_json::to_vec(data).unwrap_or_default();
-        let computed_hash = blake3::hash(&data_bytes);
-        let computed_hex = computed_hash.to_hex().to_string();
-        
-        computed_hex == expected_hash
-    }
-
-    /// Perform TEE-attested verification
-    async fn verify_in_tee(&self, task: &VerifyTask) -> Result<VerificationResult, String> {
-        let mut verified_chunks = Vec::new();
-        let mut failed_chunks = Vec::new();
-
-        // Verify each data chunk
-        for chunk in &task.data {
-            if self.verify_hash(&chunk.data, &chunk.hash) {
-                verified_chunks.push(chunk.hash.clone());
-            } else {
-                failed_chunks.push(chunk.hash.clone());
-            }
+        Ok(Invocation::Help) => {
+            println!("{}", USAGE);
+            return Ok(());
         }
-
-        // Compute aggregate data hash
-        let mut hasher = blake3::Hasher::new();
-        for hash in &verified_chunks {
-            hasher.update(hash.as_bytes());
+        Ok(Invocation::Version) => {
+            println!("verifier-agent {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
         }
-        let aggregate_hash = hasher.finalize().to_hex().to_string();
-
-        // Get TEE attestation from EigenCloud
-        let attestation = self.eigen_compute
-            .execute_verification(
-                &aggregate_hash,
-                &verified_chunks,
-                task.quest_id.as_str(),
-            )
-            .await?;
-
-        let confidence = if failed_chunks.is_empty() { 100 } else { 
-            ((verified_chunks.len() as f32 / task.data.len() as f32) * 100.0) as u8
-        };
-
-        let status = if confidence >= 95 { "verified" } else { "partial" };
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
 
-        Ok(VerificationResult {
-            result_type: "task_result".to_string(),
-            quest_id: task.quest_id.clone(),
-            agent_id: self.agent_id.clone(),
-            status: status.to_string(),
-            attestation: TeeAttestation {
-                quote: attestation.quote,
-                data_hash: aggregate_hash,
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                validator_pubkey: attestation.validator_pubkey,
-                signature: attestation.signature,
-                confidence_score: confidence,
-            },
-            verified_chunks,
-            failed_chunks,
-        })
+    dotenv::dotenv().ok();
+    logging::init();
+
+    let config = cli.apply(Config::load_from(cli.config_path().as_deref())?);
+    let eigen_compute = EigenCompute::try_new(config.tee)?;
+    let backend = eigen_compute.backend().name();
+    let agent = Arc::new(
+        VerifierAgent::builder()
+            .config(config.agent)
+            .eigen_compute(eigen_compute)
+            .build()?,
+    );
+
+    // METRICS_ADDR picks the interface; METRICS_PORT alone binds loopback
+    let metrics_addr = match (env::var("METRICS_ADDR"), env::var("METRICS_PORT")) {
+        (Ok(addr), _) => Some(addr.parse::<SocketAddr>()?),
+        (Err(_), Ok(port)) => Some(SocketAddr::from(([127, 0, 0, 1], port.parse::<u16>()?))),
+        _ => None,
+    };
+    if let Some(addr) = metrics_addr {
+        info!(%addr, "Serving metrics");
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr).await {
+                error!(error = %e, "Metrics server failed");
+            }
+        });
     }
 
-    /// Handle incoming task from coordinator
-    async fn handle_task(&self, message: &str) -> Option<String> {
-        let task: Value = serde_json::from_str(message).ok()?;
-        let task_type = task.get("type")?.as_str()?;
-
-        match task_type {
-            "verify_task" => {
-                let verify_task: VerifyTask = serde_json::from_value(task.clone()).ok()?;
-                
-                println!("[Verifier] Received verification task for quest: {}", verify_task.quest_id);
-                
-                match self.verify_in_tee(&verify_task).await {
-                    Ok(result) => {
-                        println!(
-                            "[Verifier] Verification complete: {} verified, {} failed",
-                            result.verified_chunks.len(),
-                            result.failed_chunks.len()
-                        );
-                        serde_json::to_string(&result).ok()
-                    }
-                    Err(e) => {
-                        eprintln!("[Verifier] TEE verification failed: {}", e);
-                        let error_response = json!({
-                            "type": "task_result",
-                            "questId": verify_task.quest_id,
-                            "agentId": self.agent_id,
-                            "status": "error",
-                            "error": e
-                        });
-                        serde_json::to_string(&error_response).ok()
-                    }
-                }
-            }
-            "ping" => {
-                Some(json!({"type": "pong", "agentId": self.agent_id}).to_string())
-            }
-            _ => {
-                println!("[Verifier] Unknown task type: {}", task_type);
-                None
+    // Orchestrator probes come from outside the host, so HEALTH_PORT alone
+    // binds every interface; HEALTH_ADDR narrows it
+    let health_addr = match (env::var("HEALTH_ADDR"), env::var("HEALTH_PORT")) {
+        (Ok(addr), _) => Some(addr.parse::<SocketAddr>()?),
+        (Err(_), Ok(port)) => Some(SocketAddr::from(([0, 0, 0, 0], port.parse::<u16>()?))),
+        _ => None,
+    };
+    if let Some(addr) = health_addr {
+        info!(%addr, "Serving health probes");
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve_probes(addr).await {
+                error!(error = %e, "Health server failed");
             }
-        }
+        });
     }
 
-    /// Main agent loop
-    pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("[Verifier] Connecting to coordinator: {}", self.coordinator_url);
-
-        let (ws_stream, _) = connect_async(&self.coordinator_url).await?;
-        let (mut write, mut read) = ws_stream.split();
-
-        // Register with coordinator
-        let registration = json!({
-            "type": "register",
-            "role": "verifier",
-            "agentId": self.agent_id,
-            "capabilities": ["tee_attestation", "hash_verification", "data_integrity"]
+    // SIGHUP rereads the configuration file so the measurement allowlist
+    // can follow container image rotations without a restart
+    #[cfg(unix)]
+    {
+        let reload = Arc::clone(&agent);
+        let path = cli.config_path();
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("SIGHUP received, reloading configuration");
+                match Config::load_from(path.as_deref()) {
+                    Ok(config) => reload
+                        .tee()
+                        .set_allowed_measurements(&config.tee.allowed_measurements),
+                    Err(e) => error!(error = %e, "Failed to reload configuration"),
+                }
+            }
         });
+    }
 
-        write.send(Message::Text(registration.to_string().into())).await?;
-        println!("[Verifier] Registered as {}", self.agent_id);
-
-        // Listen for tasks
-        while let Some(msg) = read.next().await {
-            match msg {
-                Ok(Message::Text(text)) => {
-                    if let Some(response) = self.handle_task(&text).await {
-                        write.send(Message::Text(response.into())).await?;
+    // Without working credentials the first task would fail, possibly hours
+    // after registering, so don't register at all; hash-only agents never
+    // talk to EigenCloud
+    let hash_only = agent.mode() == VerifierMode::HashOnly;
+    if hash_only || agent.tee().is_dev_mode() {
+        METRICS.set_auth_state(AuthState::Skipped);
+    } else {
+        if let Err(e) = agent.tee().check_auth().await {
+            METRICS.set_auth_state(AuthState::Failed);
+            error!(backend, error = %e, "EigenCloud auth check failed, not registering");
+            return Err(e.into());
+        }
+        METRICS.set_auth_state(AuthState::Authenticated);
+        info!(backend, "EigenCloud authenticated");
+
+        // Keep /readyz honest if the credentials are revoked or expire
+        if let Some(interval) = agent.tee().auth_check_interval() {
+            let probe = Arc::clone(&agent);
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let state = match probe.tee().check_auth().await {
+                        Ok(_) => AuthState::Authenticated,
+                        Err(e) => {
+                            warn!(backend, error = %e, "EigenCloud auth check failed");
+                            AuthState::Failed
+                        }
+                    };
+                    if state != METRICS.auth_state() {
+                        let auth = state.as_str();
+                        info!(backend, auth, "EigenCloud auth state changed");
                     }
+                    METRICS.set_auth_state(state);
                 }
-                Ok(Message::Close(_)) => {
-                    println!("[Verifier] Connection closed");
-                    break;
-                }
-                Err(e) => {
-                    eprintln!("[Verifier] WebSocket error: {}", e);
-                    break;
-                }
-                _ => {}
-            }
+            });
         }
+    }
 
-        Ok(())
+    // With TEE_IMAGE set and no TEE_CONTAINER_URL, the container has to be
+    // up before tasks can be attested
+    if !hash_only {
+        agent.tee().provision().await?;
     }
+
+    let result = Arc::clone(&agent).run().await;
+    if let Err(e) = agent.tee().release().await {
+        error!(error = %e, "Failed to release the TEE");
+    }
+    result
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// `verify-file`: put one task from disk through the checks a coordinator's
+/// task would get and print the result, exiting 1 unless its chunks
+/// verified. Nothing is connected to or persisted, and logs go to stderr.
+async fn verify_file(
+    cli: &Cli,
+    path: &Path,
+    attest: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
-    
-    let agent = VerifierAgent::new();
-    agent.run().await
+    logging::init_stderr();
+
+    let mut config = cli.apply(Config::load_from(cli.config_path().as_deref())?);
+    config.agent.data_dir = PathBuf::new();
+    config.agent.attestation_log_path = None;
+    if attest {
+        config.agent.mode = VerifierMode::Attested;
+        config.tee.dev_mode = true;
+    } else {
+        config.agent.mode = VerifierMode::HashOnly;
+    }
+    let agent = VerifierAgent::builder()
+        .config(config.agent)
+        .eigen_compute(EigenCompute::try_new(config.tee)?)
+        .identity(AgentIdentity::generate())
+        .build()?;
+
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let task: VerifyTask = serde_json::from_str(&text)
+        .map_err(|e| format!("{} is not a verify_task: {}", path.display(), e))?;
+    let quest_id = task.quest_id.clone();
+    let result = match agent.verify(task).await {
+        Ok(result) => serde_json::to_value(&result)?,
+        // What a `task_failed` report would say
+        Err(e) => json!({
+            "questId": quest_id,
+            "status": "error",
+            "error": e.to_string(),
+            "code": e.code(),
+            "retryable": e.is_retryable(),
+        }),
+    };
+    println!("{}", serde_json::to_string_pretty(&result)?);
+
+    // Attestations made offline are always simulated
+    let status = result["status"].as_str().unwrap_or_default();
+    if !matches!(status, "verified" | "verified_simulated") {
+        std::process::exit(1);
+    }
+    Ok(())
 }
@@ -0,0 +1,267 @@
+//! Merkle tree over verified chunk hashes
+//!
+//! Leaves are the chunk hash strings, sorted and deduplicated so the root
+//! does not depend on submission order. Leaf and interior hashes are domain
+//! separated (`0x00 || hash`, `0x01 || left || right`) and an unpaired node
+//! is promoted to the next level unchanged rather than duplicated. Nodes
+//! are hashed with the task's algorithm, Blake3 by default.
+
+use crate::hashing::HashAlgorithm;
+use serde::{Deserialize, Serialize};
+
+/// Name recorded as `data_hash_algorithm` when `data_hash` is the root of a
+/// Blake3 tree
+pub const ALGORITHM: &str = "blake3-merkle";
+
+/// `data_hash_algorithm` for a tree hashed with `algorithm`
+pub fn algorithm_name(algorithm: HashAlgorithm) -> String {
+    format!("{}-merkle", algorithm)
+}
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Which side of the running hash a proof sibling sits on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// One step from a leaf towards the root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofStep {
+    /// Hex-encoded sibling node
+    pub hash: String,
+    pub side: Side,
+}
+
+/// Inclusion proof for a single chunk hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Chunk hash the proof is for
+    #[serde(rename = "chunkHash")]
+    pub chunk_hash: String,
+    /// Siblings from the leaf level upwards; promoted levels have no step
+    pub steps: Vec<ProofStep>,
+}
+
+/// Merkle tree kept level by level so proofs can be read off directly
+pub struct MerkleTree {
+    algorithm: HashAlgorithm,
+    leaves: Vec<String>,
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl MerkleTree {
+    /// Build a tree over `hashes`, ignoring order and duplicates
+    pub fn from_hashes(algorithm: HashAlgorithm, hashes: &[String]) -> Self {
+        let mut leaves = hashes.to_vec();
+        leaves.sort();
+        leaves.dedup();
+
+        let mut levels = Vec::new();
+        let mut level: Vec<Vec<u8>> = leaves.iter().map(|h| leaf_hash(algorithm, h)).collect();
+        while level.len() > 1 {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(algorithm, left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(std::mem::replace(&mut level, next));
+        }
+        levels.push(level);
+
+        Self {
+            algorithm,
+            leaves,
+            levels,
+        }
+    }
+
+    /// Hex-encoded root; an empty tree has the hash of no input
+    pub fn root_hex(&self) -> String {
+        match self.levels.last().and_then(|l| l.first()) {
+            Some(root) => hex::encode(root),
+            None => self.algorithm.digest_hex(&[]),
+        }
+    }
+
+    /// Inclusion proof for `chunk_hash`, or None if it is not a leaf
+    pub fn proof(&self, chunk_hash: &str) -> Option<MerkleProof> {
+        let mut index = self
+            .leaves
+            .binary_search_by(|leaf| leaf.as_str().cmp(chunk_hash))
+            .ok()?;
+
+        let mut steps = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(node) = level.get(sibling) {
+                steps.push(ProofStep {
+                    hash: hex::encode(node),
+                    side: if sibling < index { Side::Left } else { Side::Right },
+                });
+            }
+            index /= 2;
+        }
+
+        Some(MerkleProof {
+            chunk_hash: chunk_hash.to_string(),
+            steps,
+        })
+    }
+}
+
+/// Check that `proof` places its chunk hash under the hex-encoded `root`
+/// of a tree hashed with `algorithm`
+pub fn verify_proof(algorithm: HashAlgorithm, root: &str, proof: &MerkleProof) -> bool {
+    let mut acc = leaf_hash(algorithm, &proof.chunk_hash);
+    for step in &proof.steps {
+        let sibling = match hex::decode(&step.hash) {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        acc = match step.side {
+            Side::Left => node_hash(algorithm, &sibling, &acc),
+            Side::Right => node_hash(algorithm, &acc, &sibling),
+        };
+    }
+    hex::encode(acc).eq_ignore_ascii_case(root)
+}
+
+fn leaf_hash(algorithm: HashAlgorithm, chunk_hash: &str) -> Vec<u8> {
+    let mut hasher = algorithm.hasher();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(chunk_hash.as_bytes());
+    hasher.finalize()
+}
+
+fn node_hash(algorithm: HashAlgorithm, left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = algorithm.hasher();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(n: usize) -> Vec<String> {
+        (0..n)
+            .map(|i| HashAlgorithm::Sha256.digest_hex(&[i as u8]))
+            .collect()
+    }
+
+    /// Every leaf of a tree over `n` hashes proves against its root
+    fn assert_all_prove(algorithm: HashAlgorithm, n: usize) {
+        let leaves = hashes(n);
+        let tree = MerkleTree::from_hashes(algorithm, &leaves);
+        let root = tree.root_hex();
+        for leaf in &leaves {
+            let proof = tree.proof(leaf).unwrap();
+            assert!(verify_proof(algorithm, &root, &proof), "{} leaves", n);
+        }
+    }
+
+    #[test]
+    fn single_leaf_is_its_own_root() {
+        let leaves = hashes(1);
+        let tree = MerkleTree::from_hashes(HashAlgorithm::Blake3, &leaves);
+        let root = tree.root_hex();
+        assert_eq!(
+            root,
+            hex::encode(leaf_hash(HashAlgorithm::Blake3, &leaves[0]))
+        );
+
+        let proof = tree.proof(&leaves[0]).unwrap();
+        assert!(proof.steps.is_empty());
+        assert!(verify_proof(HashAlgorithm::Blake3, &root, &proof));
+    }
+
+    #[test]
+    fn proves_every_leaf_of_odd_sized_trees() {
+        for n in [3, 5, 7, 9, 33] {
+            assert_all_prove(HashAlgorithm::Blake3, n);
+        }
+    }
+
+    #[test]
+    fn proves_every_leaf_of_even_sized_trees() {
+        for n in [2, 4, 6, 16] {
+            assert_all_prove(HashAlgorithm::Blake3, n);
+        }
+    }
+
+    #[test]
+    fn proves_with_every_algorithm() {
+        for algorithm in [
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha512,
+            HashAlgorithm::Keccak256,
+        ] {
+            assert_all_prove(algorithm, 5);
+        }
+    }
+
+    #[test]
+    fn promotes_an_unpaired_node_without_a_step() {
+        let leaves = hashes(3);
+        let tree = MerkleTree::from_hashes(HashAlgorithm::Blake3, &leaves);
+        let mut sorted = leaves.clone();
+        sorted.sort();
+        // The third leaf has no sibling at the bottom level
+        assert_eq!(tree.proof(&sorted[2]).unwrap().steps.len(), 1);
+        assert_eq!(tree.proof(&sorted[0]).unwrap().steps.len(), 2);
+    }
+
+    #[test]
+    fn root_ignores_order_and_duplicates() {
+        let leaves = hashes(5);
+        let mut shuffled: Vec<String> = leaves.iter().rev().cloned().collect();
+        shuffled.push(leaves[2].clone());
+        assert_eq!(
+            MerkleTree::from_hashes(HashAlgorithm::Blake3, &leaves).root_hex(),
+            MerkleTree::from_hashes(HashAlgorithm::Blake3, &shuffled).root_hex()
+        );
+    }
+
+    #[test]
+    fn empty_tree_has_the_hash_of_no_input() {
+        let tree = MerkleTree::from_hashes(HashAlgorithm::Blake3, &[]);
+        assert_eq!(tree.root_hex(), HashAlgorithm::Blake3.digest_hex(&[]));
+    }
+
+    #[test]
+    fn rejects_tampered_and_foreign_proofs() {
+        let leaves = hashes(5);
+        let tree = MerkleTree::from_hashes(HashAlgorithm::Blake3, &leaves);
+        let root = tree.root_hex();
+        assert!(tree.proof("not a leaf").is_none());
+
+        let mut wrong_leaf = tree.proof(&leaves[0]).unwrap();
+        wrong_leaf.chunk_hash = leaves[1].clone();
+        assert!(!verify_proof(HashAlgorithm::Blake3, &root, &wrong_leaf));
+
+        let mut flipped = tree.proof(&leaves[0]).unwrap();
+        flipped.steps[0].side = match flipped.steps[0].side {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        };
+        assert!(!verify_proof(HashAlgorithm::Blake3, &root, &flipped));
+
+        let mut not_hex = tree.proof(&leaves[0]).unwrap();
+        not_hex.steps[0].hash = "zz".to_string();
+        assert!(!verify_proof(HashAlgorithm::Blake3, &root, &not_hex));
+
+        let proof = tree.proof(&leaves[0]).unwrap();
+        assert!(!verify_proof(HashAlgorithm::Sha256, &root, &proof));
+    }
+}
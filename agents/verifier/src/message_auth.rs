@@ -0,0 +1,206 @@
+//! Shared-token authentication with the coordinator
+//!
+//! The agent's ed25519 key proves which agent signed a result, but not that
+//! the coordinator ever admitted that agent. With `AGENT_AUTH_TOKEN` set the
+//! agent presents the token as `Authorization: Bearer <token>` on the
+//! WebSocket handshake, for an auth proxy in front of the coordinator, and
+//! as `authToken` in its registration.
+//!
+//! `MESSAGE_AUTH=hmac` additionally puts a `mac` field on every outbound
+//! message: the hex HMAC-SHA256 under the token of the canonical message
+//! without that field. Whenever a token is set, whatever `MESSAGE_AUTH`
+//! says, every inbound message must carry a `mac` checked the same way;
+//! one without it is rejected.
+
+use crate::error::VerifierError;
+use crate::hashing::hmac_sha256;
+use crate::protocol::{self, MAC_FIELD};
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, AUTHORIZATION};
+
+/// Whether outbound messages carry a MAC
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageAuth {
+    /// Only the handshake and registration carry the token
+    #[default]
+    None,
+    /// Every outbound message gets a `mac` field
+    Hmac,
+}
+
+impl MessageAuth {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageAuth::None => "none",
+            MessageAuth::Hmac => "hmac",
+        }
+    }
+}
+
+impl FromStr for MessageAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" | "" => Ok(MessageAuth::None),
+            "hmac" => Ok(MessageAuth::Hmac),
+            other => Err(format!("unsupported message auth: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for MessageAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How the agent authenticates itself and coordinator messages
+#[derive(Clone, Default)]
+pub struct CoordinatorAuth {
+    token: Option<String>,
+    /// Token as a ready `Authorization` header value
+    header: Option<HeaderValue>,
+    mac_outbound: bool,
+}
+
+impl CoordinatorAuth {
+    /// Fails if HMAC is asked for without a token, or the token can't be
+    /// sent in an HTTP header
+    pub fn new(token: Option<String>, message_auth: MessageAuth) -> Result<Self, VerifierError> {
+        let Some(token) = token else {
+            if message_auth == MessageAuth::Hmac {
+                return Err(VerifierError::InvalidConfig(
+                    "MESSAGE_AUTH=hmac needs an AGENT_AUTH_TOKEN".into(),
+                ));
+            }
+            return Ok(Self::default());
+        };
+        let mut header = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| {
+            VerifierError::InvalidConfig(
+                "AGENT_AUTH_TOKEN must be printable ASCII to go in a header".into(),
+            )
+        })?;
+        header.set_sensitive(true);
+        Ok(Self {
+            token: Some(token),
+            header: Some(header),
+            mac_outbound: message_auth == MessageAuth::Hmac,
+        })
+    }
+
+    /// Token for the registration payload
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Add the bearer token to the WebSocket handshake, if there is one
+    pub fn authorize(&self, request: &mut Request) {
+        if let Some(header) = &self.header {
+            request.headers_mut().insert(AUTHORIZATION, header.clone());
+        }
+    }
+
+    /// `text` with a `mac` field added when outbound MACs are on. Anything
+    /// that is not a JSON object goes out unchanged.
+    pub fn seal(&self, text: String) -> String {
+        let Some(token) = self.token.as_deref().filter(|_| self.mac_outbound) else {
+            return text;
+        };
+        let Ok(mut message @ Value::Object(_)) = serde_json::from_str::<Value>(&text) else {
+            return text;
+        };
+        let mac = hmac_sha256(token.as_bytes(), &[&protocol::mac_payload(&message)]);
+        message[MAC_FIELD] = json!(hex::encode(mac));
+        message.to_string()
+    }
+
+    /// Check the `mac` of an inbound message when there is a token to
+    /// check it against; a message without one fails
+    pub fn check(&self, message: &Value) -> Result<(), VerifierError> {
+        let Some(token) = self.token.as_deref() else {
+            return Ok(());
+        };
+        let mac = message
+            .get(MAC_FIELD)
+            .ok_or_else(|| VerifierError::MessageAuthFailed("message has no mac".into()))?;
+        let claimed = mac
+            .as_str()
+            .and_then(|mac| hex::decode(mac).ok())
+            .ok_or_else(|| VerifierError::MessageAuthFailed("mac is not a hex string".into()))?;
+        let expected = hmac_sha256(token.as_bytes(), &[&protocol::mac_payload(message)]);
+        // Compare without an early exit, so timing doesn't reveal how much
+        // of a forged MAC was right
+        let difference = claimed
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if claimed.len() != expected.len() || difference != 0 {
+            return Err(VerifierError::MessageAuthFailed(
+                "mac does not match AGENT_AUTH_TOKEN".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth(message_auth: MessageAuth) -> CoordinatorAuth {
+        CoordinatorAuth::new(Some("secret".to_string()), message_auth).unwrap()
+    }
+
+    fn sealed(message: &Value) -> Value {
+        serde_json::from_str(&auth(MessageAuth::Hmac).seal(message.to_string())).unwrap()
+    }
+
+    #[test]
+    fn accepts_a_sealed_message() {
+        let message = sealed(&json!({"type": "verify_task", "questId": "q1"}));
+        assert!(message[MAC_FIELD].is_string());
+        auth(MessageAuth::None).check(&message).unwrap();
+        auth(MessageAuth::Hmac).check(&message).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_message_without_a_mac_when_a_token_is_set() {
+        let message = json!({"type": "verify_task", "questId": "q1"});
+        for message_auth in [MessageAuth::None, MessageAuth::Hmac] {
+            let e = auth(message_auth).check(&message).unwrap_err();
+            assert!(matches!(e, VerifierError::MessageAuthFailed(_)), "{}", e);
+        }
+        assert!(auth(MessageAuth::None)
+            .check(&json!(["not", "an", "object"]))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_non_string_and_wrong_macs() {
+        let message = sealed(&json!({"type": "verify_task", "questId": "q1"}));
+        for mac in [json!(null), json!(42), json!("not hex"), json!("abcd")] {
+            let mut forged = message.clone();
+            forged[MAC_FIELD] = mac;
+            assert!(auth(MessageAuth::None).check(&forged).is_err());
+        }
+        let mut tampered = message.clone();
+        tampered["questId"] = json!("q2");
+        assert!(auth(MessageAuth::None).check(&tampered).is_err());
+        let other = CoordinatorAuth::new(Some("other".to_string()), MessageAuth::None).unwrap();
+        assert!(other.check(&message).is_err());
+    }
+
+    #[test]
+    fn checks_nothing_without_a_token() {
+        let auth = CoordinatorAuth::new(None, MessageAuth::None).unwrap();
+        auth.check(&json!({"type": "verify_task"})).unwrap();
+        auth.check(&json!({"type": "verify_task", "mac": "junk"}))
+            .unwrap();
+        assert_eq!(auth.seal("{}".to_string()), "{}");
+        assert!(CoordinatorAuth::new(None, MessageAuth::Hmac).is_err());
+    }
+}
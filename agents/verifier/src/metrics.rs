@@ -0,0 +1,334 @@
+//! Prometheus metrics and health endpoint for the Verifier Agent
+//!
+//! Metrics live in a process-wide registry of atomics so any module can
+//! record without threading handles around. `serve` exposes them at
+//! `/metrics` (Prometheus text format) alongside the orchestrator probes:
+//! `/healthz` answers whenever the process is up, `/readyz` only while the
+//! agent is connected to the coordinator and ecloud auth has been verified.
+//! `serve_probes` offers the probes alone, for a port reachable from outside.
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, Ordering};
+use std::time::Duration;
+
+/// Process-wide metrics registry
+pub static METRICS: Metrics = Metrics::new();
+
+/// Upper bounds (seconds) of the latency histogram buckets
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Cumulative latency histogram
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    last_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            buckets: [ZERO; LATENCY_BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+            last_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one observation
+    pub fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            if secs <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.last_micros
+            .store(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Most recent observation, if there has been one
+    pub fn last(&self) -> Option<Duration> {
+        if self.count.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let micros = self.last_micros.load(Ordering::Relaxed);
+        Some(Duration::from_micros(micros))
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// ecloud authentication state as last observed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AuthState {
+    Unknown = 0,
+    Authenticated = 1,
+    Failed = 2,
+    /// Dev mode never talks to EigenCloud
+    Skipped = 3,
+}
+
+impl AuthState {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => AuthState::Authenticated,
+            2 => AuthState::Failed,
+            3 => AuthState::Skipped,
+            _ => AuthState::Unknown,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuthState::Unknown => "unknown",
+            AuthState::Authenticated => "authenticated",
+            AuthState::Failed => "failed",
+            AuthState::Skipped => "skipped",
+        }
+    }
+}
+
+/// Counters, gauges and histograms exported by the agent
+pub struct Metrics {
+    pub tasks_received: AtomicU64,
+    /// Tasks reported to the coordinator, whatever the outcome
+    pub tasks_processed: AtomicU64,
+    /// Reported tasks that errored, timed out or came out `failed`
+    pub tasks_failed: AtomicU64,
+    pub chunks_verified: AtomicU64,
+    pub chunks_failed: AtomicU64,
+    /// Tasks whose TEE attestation could not be obtained or verified
+    pub attestation_errors: AtomicU64,
+    /// Attestations reused from the cache instead of calling the TEE
+    pub attestation_cache_hits: AtomicU64,
+    /// Tasks reported as `timeout`
+    pub tasks_timed_out: AtomicU64,
+    /// Tasks rejected as `busy` because the queue was full
+    pub tasks_rejected_busy: AtomicU64,
+    /// Repeated tasks answered from the stored report or ignored while the
+    /// original was still running
+    pub tasks_duplicate: AtomicU64,
+    /// Messages dropped from a full outbound queue under `drop-oldest`
+    pub outbound_dropped: AtomicU64,
+    /// Results that could not be written to the attestation log
+    pub attestation_log_errors: AtomicU64,
+    pub ws_reconnects: AtomicU64,
+    pub tasks_in_flight: AtomicI64,
+    /// End-to-end time to verify a task, hashing and attestation included
+    pub verification_latency: Histogram,
+    pub tee_latency: Histogram,
+    connected: AtomicBool,
+    ever_connected: AtomicBool,
+    auth_state: AtomicU8,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            tasks_received: AtomicU64::new(0),
+            tasks_processed: AtomicU64::new(0),
+            tasks_failed: AtomicU64::new(0),
+            chunks_verified: AtomicU64::new(0),
+            chunks_failed: AtomicU64::new(0),
+            attestation_errors: AtomicU64::new(0),
+            attestation_cache_hits: AtomicU64::new(0),
+            tasks_timed_out: AtomicU64::new(0),
+            tasks_rejected_busy: AtomicU64::new(0),
+            tasks_duplicate: AtomicU64::new(0),
+            outbound_dropped: AtomicU64::new(0),
+            attestation_log_errors: AtomicU64::new(0),
+            ws_reconnects: AtomicU64::new(0),
+            tasks_in_flight: AtomicI64::new(0),
+            verification_latency: Histogram::new(),
+            tee_latency: Histogram::new(),
+            connected: AtomicBool::new(false),
+            ever_connected: AtomicBool::new(false),
+            auth_state: AtomicU8::new(AuthState::Unknown as u8),
+        }
+    }
+
+    /// Record the coordinator connection going up or down
+    pub fn set_connected(&self, connected: bool) {
+        if connected && self.ever_connected.swap(true, Ordering::Relaxed) {
+            self.ws_reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Count a task as in flight until the returned guard is dropped, which
+    /// also covers tasks aborted mid-await
+    pub fn track_in_flight(&'static self) -> InFlightGuard {
+        self.tasks_in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard(self)
+    }
+
+    pub fn set_auth_state(&self, state: AuthState) {
+        self.auth_state.store(state as u8, Ordering::Relaxed);
+    }
+
+    pub fn auth_state(&self) -> AuthState {
+        AuthState::from_u8(self.auth_state.load(Ordering::Relaxed))
+    }
+
+    /// Connected to the coordinator with ecloud auth verified, or not
+    /// needed. An auth check still running counts as not ready.
+    pub fn is_ready(&self) -> bool {
+        let auth_ok = matches!(
+            self.auth_state(),
+            AuthState::Authenticated | AuthState::Skipped
+        );
+        self.is_connected() && auth_ok
+    }
+
+    /// Snapshot of the task counters and TEE state
+    pub fn stats(&self) -> AgentStats {
+        AgentStats {
+            tasks_processed: self.tasks_processed.load(Ordering::Relaxed),
+            tasks_failed: self.tasks_failed.load(Ordering::Relaxed),
+            last_tee_latency_ms: self.tee_latency.last().map(|d| d.as_millis() as u64),
+            auth_state: self.auth_state().as_str(),
+        }
+    }
+
+    /// Render all metrics in Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("verifier_tasks_received_total", "Verification tasks received", &self.tasks_received),
+            ("verifier_tasks_processed_total", "Verification tasks reported", &self.tasks_processed),
+            ("verifier_tasks_failed_total", "Reported tasks that did not verify", &self.tasks_failed),
+            ("verifier_chunks_verified_total", "Chunks that passed verification", &self.chunks_verified),
+            ("verifier_chunks_failed_total", "Chunks that failed verification", &self.chunks_failed),
+            ("verifier_attestation_errors_total", "Tasks whose TEE attestation failed", &self.attestation_errors),
+            ("verifier_attestation_cache_hits_total", "Attestations reused from the cache", &self.attestation_cache_hits),
+            ("verifier_tasks_timed_out_total", "Verification tasks that ran out of time", &self.tasks_timed_out),
+            ("verifier_tasks_rejected_busy_total", "Verification tasks rejected because the queue was full", &self.tasks_rejected_busy),
+            ("verifier_tasks_duplicate_total", "Repeated verification tasks that were not run again", &self.tasks_duplicate),
+            ("verifier_outbound_dropped_total", "Messages dropped from the full outbound queue", &self.outbound_dropped),
+            ("verifier_attestation_log_errors_total", "Results that could not be written to the attestation log", &self.attestation_log_errors),
+            ("verifier_ws_reconnects_total", "Coordinator WebSocket reconnects", &self.ws_reconnects),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        let _ = writeln!(out, "# HELP verifier_tasks_in_flight Verification tasks currently running");
+        let _ = writeln!(out, "# TYPE verifier_tasks_in_flight gauge");
+        let _ = writeln!(
+            out,
+            "verifier_tasks_in_flight {}",
+            self.tasks_in_flight.load(Ordering::Relaxed)
+        );
+
+        self.verification_latency.render(
+            &mut out,
+            "verifier_verification_duration_seconds",
+            "Latency of whole verification tasks",
+        );
+        self.tee_latency.render(
+            &mut out,
+            "verifier_tee_call_duration_seconds",
+            "Latency of TEE attestation calls",
+        );
+        out
+    }
+}
+
+/// Task counters and TEE state at one moment, as the coordinator's
+/// `status_request` reports them
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentStats {
+    pub tasks_processed: u64,
+    pub tasks_failed: u64,
+    /// Duration of the most recent TEE call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_tee_latency_ms: Option<u64>,
+    /// ecloud authentication state as last observed
+    pub auth_state: &'static str,
+}
+
+/// Decrements the in-flight gauge on drop
+pub struct InFlightGuard(&'static Metrics);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.tasks_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Serve `/metrics`, `/healthz` and `/readyz` until the server fails
+pub async fn serve(addr: SocketAddr) -> Result<(), hyper::Error> {
+    let make_svc =
+        make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(|req| handle(req, true))) });
+    Server::try_bind(&addr)?.serve(make_svc).await
+}
+
+/// Serve only `/healthz` and `/readyz` until the server fails
+pub async fn serve_probes(addr: SocketAddr) -> Result<(), hyper::Error> {
+    let make_svc =
+        make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(|req| handle(req, false))) });
+    Server::try_bind(&addr)?.serve(make_svc).await
+}
+
+async fn handle(req: Request<Body>, with_metrics: bool) -> Result<Response<Body>, Infallible> {
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") if with_metrics => Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::from(METRICS.render())),
+        (&Method::GET, "/healthz") => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(Body::from(r#"{"status":"ok"}"#)),
+        (&Method::GET, "/readyz") => {
+            let ready = METRICS.is_ready();
+            let body = serde_json::json!({
+                "status": if ready { "ready" } else { "not_ready" },
+                "connected": METRICS.is_connected(),
+                "auth": METRICS.auth_state().as_str(),
+            });
+            Response::builder()
+                .status(if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE })
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    Ok(response.unwrap_or_default())
+}
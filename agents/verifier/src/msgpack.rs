@@ -0,0 +1,465 @@
+//! MessagePack encoding of coordinator messages
+//!
+//! Binary frames carry the same messages as text frames, MessagePack-encoded
+//! instead of JSON. Both go through [`serde_json::Value`], so the protocol
+//! structs and their `#[serde(rename)]`s serve both encodings unchanged:
+//! a message decoded from MessagePack deserializes exactly as its JSON
+//! twin would.
+//!
+//! Only what JSON can express is understood: nil, booleans, integers,
+//! floats, strings, arrays and maps with string keys. `bin` and `ext`
+//! values are rejected, as are non-finite floats. Integers keep their
+//! integer type and floats theirs, so chunk data hashes the same either
+//! way.
+
+use serde_json::{Map, Number, Value};
+
+/// Deepest nesting decoded, as for serde_json
+const MAX_DEPTH: usize = 128;
+
+/// MessagePack encoding of `value`
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => {
+            write_len(out, s.len(), 0xa0, 31, [0xd9, 0xda, 0xdb]);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            write_len(out, items.len(), 0x90, 15, [0, 0xdc, 0xdd]);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            write_len(out, map.len(), 0x80, 15, [0, 0xde, 0xdf]);
+            for (key, item) in map {
+                write_value(out, &Value::String(key.clone()));
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+/// Length header: the fix form up to `fix_max`, else the smallest of the
+/// 8, 16 and 32-bit forms (a zero marker has no such form)
+fn write_len(out: &mut Vec<u8>, len: usize, fix: u8, fix_max: usize, markers: [u8; 3]) {
+    if len <= fix_max {
+        out.push(fix | len as u8);
+    } else if len <= u8::MAX as usize && markers[0] != 0 {
+        out.extend_from_slice(&[markers[0], len as u8]);
+    } else if len <= u16::MAX as usize {
+        out.push(markers[1]);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(markers[2]);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+fn write_number(out: &mut Vec<u8>, n: &Number) {
+    if let Some(u) = n.as_u64() {
+        match u {
+            0..=0x7f => out.push(u as u8),
+            0x80..=0xff => out.extend_from_slice(&[0xcc, u as u8]),
+            0x100..=0xffff => {
+                out.push(0xcd);
+                out.extend_from_slice(&(u as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(0xce);
+                out.extend_from_slice(&(u as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(0xcf);
+                out.extend_from_slice(&u.to_be_bytes());
+            }
+        }
+    } else if let Some(i) = n.as_i64() {
+        // Only negative values get here
+        if i >= -32 {
+            out.push(i as u8);
+        } else if i >= i8::MIN as i64 {
+            out.extend_from_slice(&[0xd0, i as u8]);
+        } else if i >= i16::MIN as i64 {
+            out.push(0xd1);
+            out.extend_from_slice(&(i as i16).to_be_bytes());
+        } else if i >= i32::MIN as i64 {
+            out.push(0xd2);
+            out.extend_from_slice(&(i as i32).to_be_bytes());
+        } else {
+            out.push(0xd3);
+            out.extend_from_slice(&i.to_be_bytes());
+        }
+    } else {
+        out.push(0xcb);
+        out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+    }
+}
+
+/// Decode one MessagePack value filling all of `bytes`
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let value = reader.value(0)?;
+    if reader.pos != bytes.len() {
+        return Err(format!(
+            "{} trailing bytes after the message",
+            bytes.len() - reader.pos
+        ));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("message ends early at byte {}", self.bytes.len()))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<usize, String> {
+        Ok(u16::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("nested deeper than {}", MAX_DEPTH));
+        }
+        let at = self.pos;
+        let marker = self.u8()?;
+        let value = match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth)?,
+            0x90..=0x9f => self.seq((marker & 0x0f) as usize, depth)?,
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize)?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xca => float(f32::from_be_bytes(self.array()?) as f64)?,
+            0xcb => float(f64::from_be_bytes(self.array()?))?,
+            0xcc => Value::from(self.u8()?),
+            0xcd => Value::from(u16::from_be_bytes(self.array()?)),
+            0xce => Value::from(u32::from_be_bytes(self.array()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.array()?)),
+            0xd0 => Value::from(self.u8()? as i8),
+            0xd1 => Value::from(i16::from_be_bytes(self.array()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.array()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.array()?)),
+            0xd9 => {
+                let len = self.u8()? as usize;
+                self.str(len)?
+            }
+            0xda => {
+                let len = self.u16()?;
+                self.str(len)?
+            }
+            0xdb => {
+                let len = self.u32()?;
+                self.str(len)?
+            }
+            0xdc => {
+                let len = self.u16()?;
+                self.seq(len, depth)?
+            }
+            0xdd => {
+                let len = self.u32()?;
+                self.seq(len, depth)?
+            }
+            0xde => {
+                let len = self.u16()?;
+                self.map(len, depth)?
+            }
+            0xdf => {
+                let len = self.u32()?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+            0xc4..=0xc6 => return Err(format!("binary value at byte {}", at)),
+            0xc7..=0xc9 | 0xd4..=0xd8 => return Err(format!("extension value at byte {}", at)),
+            0xc1 => return Err(format!("invalid marker 0xc1 at byte {}", at)),
+        };
+        Ok(value)
+    }
+
+    fn str(&mut self, len: usize) -> Result<Value, String> {
+        let at = self.pos;
+        let bytes = self.take(len)?;
+        std::str::from_utf8(bytes)
+            .map(|s| Value::String(s.to_string()))
+            .map_err(|_| format!("string at byte {} is not UTF-8", at))
+    }
+
+    fn seq(&mut self, len: usize, depth: usize) -> Result<Value, String> {
+        // Every element takes at least a byte, so a forged length can't
+        // reserve more than the message could hold
+        let mut items = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let at = self.pos;
+            let Value::String(key) = self.value(depth + 1)? else {
+                return Err(format!("map key at byte {} is not a string", at));
+            };
+            let value = self.value(depth + 1)?;
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+fn float(f: f64) -> Result<Value, String> {
+    Number::from_f64(f)
+        .map(Value::Number)
+        .ok_or_else(|| format!("{} has no JSON form", f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canonical_json::HashMode;
+    use crate::{
+        AgentIdentity, EigenCompute, EigenComputeConfig, HashAlgorithm, VerifierAgent,
+        VerifierConfig, VerifierMode, VerifyTask,
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn round_trip(value: &Value) -> Value {
+        decode(&encode(value)).unwrap()
+    }
+
+    fn task_json() -> Value {
+        let data = json!({ "price": 101.25, "volume": 70000, "delta": -3, "pair": "ETH/USD" });
+        let hash = HashAlgorithm::default().digest_hex(&HashMode::Canonical.encode(&data).unwrap());
+        json!({
+            "type": "verify_task",
+            "questId": "q1",
+            "data": [
+                { "source": "a", "hash": hash, "data": data, "timestamp": 1 },
+                { "source": "b", "hash": "00", "data": { "price": null }, "timestamp": 1 },
+            ],
+            "expectedHashes": [hash, "00"],
+            "minConfidence": 50,
+            "sourceWeights": { "a": 1.5, "b": 0.25 },
+        })
+    }
+
+    #[test]
+    fn verify_task_decodes_like_its_json_twin() {
+        let json = task_json();
+        let decoded = round_trip(&json);
+        assert_eq!(decoded, json);
+        let from_msgpack: VerifyTask = serde_json::from_value(decoded).unwrap();
+        let from_json: VerifyTask = serde_json::from_str(&json.to_string()).unwrap();
+        assert_eq!(from_msgpack.quest_id, from_json.quest_id);
+        assert_eq!(
+            format!("{:?}", from_msgpack.data),
+            format!("{:?}", from_json.data)
+        );
+        assert_eq!(from_msgpack.expected_hashes, from_json.expected_hashes);
+        assert_eq!(from_msgpack.min_confidence, from_json.min_confidence);
+        assert_eq!(from_msgpack.source_weights, from_json.source_weights);
+    }
+
+    #[tokio::test]
+    async fn verification_result_round_trips_like_json() {
+        let agent = VerifierAgent::builder()
+            .config(VerifierConfig {
+                mode: VerifierMode::HashOnly,
+                max_chunk_age: None,
+                data_dir: PathBuf::new(),
+                ..VerifierConfig::default()
+            })
+            .eigen_compute(EigenCompute::new(EigenComputeConfig::default()))
+            .identity(AgentIdentity::generate())
+            .build()
+            .unwrap();
+        let task = serde_json::from_value(task_json()).unwrap();
+        let result = serde_json::to_value(agent.verify(task).await.unwrap()).unwrap();
+        let via_json: Value = serde_json::from_str(&result.to_string()).unwrap();
+        assert_eq!(round_trip(&result), via_json);
+    }
+
+    #[test]
+    fn integers_use_the_smallest_form() {
+        let cases: [(i128, &[u8]); 18] = [
+            (0, &[0x00]),
+            (0x7f, &[0x7f]),
+            (0x80, &[0xcc, 0x80]),
+            (0xff, &[0xcc, 0xff]),
+            (0x100, &[0xcd, 0x01, 0x00]),
+            (0xffff, &[0xcd, 0xff, 0xff]),
+            (0x1_0000, &[0xce, 0x00, 0x01, 0x00, 0x00]),
+            (0xffff_ffff, &[0xce, 0xff, 0xff, 0xff, 0xff]),
+            (0x1_0000_0000, &[0xcf, 0, 0, 0, 0x01, 0, 0, 0, 0]),
+            (
+                u64::MAX as i128,
+                &[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (-1, &[0xff]),
+            (-32, &[0xe0]),
+            (-33, &[0xd0, 0xdf]),
+            (-128, &[0xd0, 0x80]),
+            (-129, &[0xd1, 0xff, 0x7f]),
+            (-32_769, &[0xd2, 0xff, 0xff, 0x7f, 0xff]),
+            (
+                i32::MIN as i128 - 1,
+                &[0xd3, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xff, 0xff, 0xff],
+            ),
+            (i64::MIN as i128, &[0xd3, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+        ];
+        for (n, bytes) in cases {
+            let value: Value = serde_json::from_str(&n.to_string()).unwrap();
+            assert_eq!(encode(&value), bytes, "{}", n);
+            assert_eq!(decode(bytes).unwrap(), value, "{}", n);
+        }
+    }
+
+    #[test]
+    fn floats_stay_floats() {
+        for f in [0.0, 0.5, -1.25, 1e300, f64::MIN_POSITIVE] {
+            let value = json!(f);
+            let bytes = encode(&value);
+            assert_eq!(bytes[0], 0xcb);
+            assert_eq!(round_trip(&value), value);
+        }
+        // 1.0 is a float, not the integer 1
+        assert!(round_trip(&json!(1.0)).is_f64());
+        let mut single = vec![0xca];
+        single.extend_from_slice(&1.5f32.to_be_bytes());
+        assert_eq!(decode(&single).unwrap(), json!(1.5));
+    }
+
+    #[test]
+    fn lengths_use_the_smallest_form() {
+        let strings: [(usize, &[u8]); 7] = [
+            (0, &[0xa0]),
+            (31, &[0xbf]),
+            (32, &[0xd9, 32]),
+            (255, &[0xd9, 0xff]),
+            (256, &[0xda, 0x01, 0x00]),
+            (65_535, &[0xda, 0xff, 0xff]),
+            (65_536, &[0xdb, 0x00, 0x01, 0x00, 0x00]),
+        ];
+        for (len, header) in strings {
+            let value = Value::String("x".repeat(len));
+            let bytes = encode(&value);
+            assert_eq!(&bytes[..header.len()], header, "str of {}", len);
+            assert_eq!(bytes.len(), header.len() + len);
+            assert_eq!(decode(&bytes).unwrap(), value);
+        }
+
+        // Arrays and maps have no 8-bit form
+        let containers: [(usize, &[u8], &[u8]); 5] = [
+            (15, &[0x9f], &[0x8f]),
+            (16, &[0xdc, 0x00, 0x10], &[0xde, 0x00, 0x10]),
+            (255, &[0xdc, 0x00, 0xff], &[0xde, 0x00, 0xff]),
+            (65_535, &[0xdc, 0xff, 0xff], &[0xde, 0xff, 0xff]),
+            (65_536, &[0xdd, 0, 0x01, 0, 0], &[0xdf, 0, 0x01, 0, 0]),
+        ];
+        for (len, array_header, map_header) in containers {
+            let array = Value::Array(vec![Value::Null; len]);
+            let bytes = encode(&array);
+            assert_eq!(
+                &bytes[..array_header.len()],
+                array_header,
+                "array of {}",
+                len
+            );
+            assert_eq!(decode(&bytes).unwrap(), array);
+
+            let map: Map<String, Value> = (0..len).map(|i| (i.to_string(), Value::Null)).collect();
+            let map = Value::Object(map);
+            let bytes = encode(&map);
+            assert_eq!(&bytes[..map_header.len()], map_header, "map of {}", len);
+            assert_eq!(decode(&bytes).unwrap(), map);
+        }
+    }
+
+    #[test]
+    fn rejects_what_json_cannot_express() {
+        // bin 8, 16 and 32, and the ext families
+        for bytes in [
+            &[0xc4, 0x01, 0xaa][..],
+            &[0xc5, 0x00, 0x01, 0xaa],
+            &[0xc6, 0x00, 0x00, 0x00, 0x01, 0xaa],
+            &[0xd4, 0x01, 0xaa],
+            &[0xc7, 0x01, 0x01, 0xaa],
+        ] {
+            assert!(decode(bytes).is_err(), "{:02x?}", bytes);
+        }
+        let mut nan = vec![0xcb];
+        nan.extend_from_slice(&f64::NAN.to_be_bytes());
+        assert!(decode(&nan).unwrap_err().contains("no JSON form"));
+        // { 1: nil }
+        assert!(decode(&[0x81, 0x01, 0xc0])
+            .unwrap_err()
+            .contains("not a string"));
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let bytes = encode(&task_json());
+        for end in 0..bytes.len() {
+            assert!(decode(&bytes[..end]).is_err(), "cut at {}", end);
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let cases: [&[u8]; 7] = [
+            &[0xc1],
+            // Trailing byte
+            &[0xc0, 0xc0],
+            // Not UTF-8
+            &[0xa2, 0xff, 0xfe],
+            // Lengths far beyond the message
+            &[0xdb, 0xff, 0xff, 0xff, 0xff],
+            &[0xdd, 0xff, 0xff, 0xff, 0xff],
+            &[0xdf, 0xff, 0xff, 0xff, 0xff],
+            &[0xdc, 0xff, 0xff, 0xc0],
+        ];
+        for bytes in cases {
+            assert!(decode(bytes).is_err(), "{:02x?}", bytes);
+        }
+        let deep = vec![0x91; MAX_DEPTH + 2];
+        assert!(decode(&deep).unwrap_err().contains("nested deeper"));
+    }
+}
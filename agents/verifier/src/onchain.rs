@@ -0,0 +1,284 @@
+//! On-chain settlement of verification results
+//!
+//! A settlement contract can't parse JSON, so a task asking for
+//! `"settlement": "evm"` also gets its attestation as
+//! `abi.encode(bytes32 dataHash, uint64 timestamp, bytes32 questIdHash,
+//! uint8 confidence)`, where `questIdHash` is the Keccak-256 of the quest
+//! ID's UTF-8 bytes. The contract recomputes the Keccak-256 digest of that
+//! encoding and, when the agent has a `SETTLEMENT_KEY`, checks the EIP-191
+//! signature over it with `ecrecover`.
+
+use crate::error::VerifierError;
+use crate::protocol::{OnchainSettlement, TeeAttestation};
+use crate::secp256k1::{self, SecretKey};
+use sha3::{Digest, Keccak256};
+
+/// Length of the encoding: four static words
+pub const ENCODED_LEN: usize = 4 * 32;
+
+/// What `personal_sign` puts before a 32-byte message
+const EIP191_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n32";
+
+/// Added to the recovery id to get the `v` that `ecrecover` takes
+const V_OFFSET: u8 = 27;
+
+/// The attestation fields a settlement contract checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettlementAttestation {
+    pub data_hash: [u8; 32],
+    pub timestamp: u64,
+    pub quest_id_hash: [u8; 32],
+    pub confidence: u8,
+}
+
+impl SettlementAttestation {
+    /// Fields of `attestation` for `quest_id`. The data hash has to fit a
+    /// `bytes32`, which rules out SHA-512 trees.
+    pub fn new(quest_id: &str, attestation: &TeeAttestation) -> Result<Self, VerifierError> {
+        let data_hash = hex::decode(&attestation.data_hash)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| {
+                VerifierError::MalformedMessage(format!(
+                    "{} data hash does not fit bytes32",
+                    attestation.data_hash_algorithm
+                ))
+            })?;
+        Ok(Self {
+            data_hash,
+            timestamp: attestation.timestamp,
+            quest_id_hash: keccak256(quest_id.as_bytes()),
+            confidence: attestation.confidence_score,
+        })
+    }
+
+    /// `abi.encode(dataHash, timestamp, questIdHash, confidence)`
+    pub fn abi_encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[..32].copy_from_slice(&self.data_hash);
+        out[56..64].copy_from_slice(&self.timestamp.to_be_bytes());
+        out[64..96].copy_from_slice(&self.quest_id_hash);
+        out[127] = self.confidence;
+        out
+    }
+
+    /// Inverse of [`abi_encode`](Self::abi_encode); the padding of the
+    /// integer words must be zero, as `abi.decode` requires
+    pub fn abi_decode(bytes: &[u8]) -> Result<Self, VerifierError> {
+        let malformed = |detail: &str| {
+            VerifierError::MalformedMessage(format!("settlement encoding {}", detail))
+        };
+        if bytes.len() != ENCODED_LEN {
+            return Err(malformed("is not 128 bytes"));
+        }
+        if bytes[32..56].iter().any(|&b| b != 0) {
+            return Err(malformed("has a timestamp wider than uint64"));
+        }
+        if bytes[96..127].iter().any(|&b| b != 0) {
+            return Err(malformed("has a confidence wider than uint8"));
+        }
+        Ok(Self {
+            data_hash: bytes[..32].try_into().unwrap(),
+            timestamp: u64::from_be_bytes(bytes[56..64].try_into().unwrap()),
+            quest_id_hash: bytes[64..96].try_into().unwrap(),
+            confidence: bytes[127],
+        })
+    }
+
+    /// Keccak-256 of the encoding; this is what gets signed
+    pub fn digest(&self) -> [u8; 32] {
+        keccak256(&self.abi_encode())
+    }
+}
+
+/// secp256k1 key that signs settlement digests
+pub struct SettlementKey {
+    secret: SecretKey,
+    address: [u8; 20],
+}
+
+impl SettlementKey {
+    /// Key from a hex-encoded 32-byte secret, optionally `0x`-prefixed
+    pub fn from_hex(secret: &str) -> Option<Self> {
+        let secret = secret.trim();
+        let bytes: [u8; 32] = hex::decode(secret.strip_prefix("0x").unwrap_or(secret))
+            .ok()?
+            .try_into()
+            .ok()?;
+        let secret = SecretKey::from_bytes(&bytes)?;
+        let address = address_of(&secret.public_key());
+        Some(Self { secret, address })
+    }
+
+    /// `0x`-prefixed address `ecrecover` returns for this key's signatures
+    pub fn address(&self) -> String {
+        format!("0x{}", hex::encode(self.address))
+    }
+
+    /// EIP-191 signature over `digest`: `r || s || v`, `v` being 27 or 28
+    pub fn sign(&self, digest: &[u8; 32]) -> [u8; 65] {
+        let mut signature = self.secret.sign_prehash(&eip191_hash(digest));
+        signature[64] += V_OFFSET;
+        signature
+    }
+}
+
+/// `0x`-prefixed address that produced an EIP-191 `signature` over
+/// `digest`, if the signature is well formed
+pub fn recover_signer(digest: &[u8; 32], signature: &[u8]) -> Option<String> {
+    let mut signature: [u8; 65] = signature.try_into().ok()?;
+    signature[64] = signature[64].checked_sub(V_OFFSET)?;
+    let public_key = secp256k1::recover(&eip191_hash(digest), &signature)?;
+    Some(format!("0x{}", hex::encode(address_of(&public_key))))
+}
+
+/// Settlement block for a result attesting `attestation`, signed if there
+/// is a key
+pub fn settle(
+    quest_id: &str,
+    attestation: &TeeAttestation,
+    key: Option<&SettlementKey>,
+) -> Result<OnchainSettlement, VerifierError> {
+    let settlement = SettlementAttestation::new(quest_id, attestation)?;
+    let digest = settlement.digest();
+    Ok(OnchainSettlement {
+        encoded: format!("0x{}", hex::encode(settlement.abi_encode())),
+        digest: format!("0x{}", hex::encode(digest)),
+        signature: key.map(|key| format!("0x{}", hex::encode(key.sign(&digest)))),
+        signer: key.map(SettlementKey::address),
+    })
+}
+
+fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    Keccak256::digest(bytes).into()
+}
+
+/// What `personal_sign` actually signs for a 32-byte message
+fn eip191_hash(digest: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(EIP191_PREFIX);
+    hasher.update(digest);
+    hasher.finalize().into()
+}
+
+/// Last 20 bytes of the Keccak-256 of an uncompressed public key
+fn address_of(public_key: &[u8; 64]) -> [u8; 20] {
+    keccak256(public_key)[12..].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attestation() -> SettlementAttestation {
+        SettlementAttestation {
+            data_hash: [0xab; 32],
+            timestamp: 1_700_000_000,
+            quest_id_hash: keccak256(b""),
+            confidence: 90,
+        }
+    }
+
+    #[test]
+    fn encodes_four_static_words() {
+        let encoded = hex::encode(attestation().abi_encode());
+        assert_eq!(
+            encoded,
+            [
+                "abababababababababababababababababababababababababababababababab",
+                "000000000000000000000000000000000000000000000000000000006553f100",
+                "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+                "000000000000000000000000000000000000000000000000000000000000005a",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn decode_inverts_encode() {
+        let attestation = attestation();
+        let decoded = SettlementAttestation::abi_decode(&attestation.abi_encode()).unwrap();
+        assert_eq!(decoded, attestation);
+
+        let extremes = SettlementAttestation {
+            data_hash: [0xff; 32],
+            timestamp: u64::MAX,
+            quest_id_hash: [0; 32],
+            confidence: u8::MAX,
+        };
+        let decoded = SettlementAttestation::abi_decode(&extremes.abi_encode()).unwrap();
+        assert_eq!(decoded, extremes);
+    }
+
+    #[test]
+    fn decode_rejects_bad_lengths_and_padding() {
+        let encoded = attestation().abi_encode();
+        assert!(SettlementAttestation::abi_decode(&encoded[..127]).is_err());
+        assert!(SettlementAttestation::abi_decode(&[encoded.as_slice(), &[0]].concat()).is_err());
+
+        let mut wide_timestamp = encoded;
+        wide_timestamp[55] = 1;
+        assert!(SettlementAttestation::abi_decode(&wide_timestamp).is_err());
+
+        let mut wide_confidence = encoded;
+        wide_confidence[126] = 1;
+        assert!(SettlementAttestation::abi_decode(&wide_confidence).is_err());
+    }
+
+    #[test]
+    fn derives_known_addresses() {
+        let one = "0000000000000000000000000000000000000000000000000000000000000001";
+        assert_eq!(
+            SettlementKey::from_hex(one).unwrap().address(),
+            "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+        let prefixed = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        assert_eq!(
+            SettlementKey::from_hex(prefixed).unwrap().address(),
+            "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+        );
+        assert!(SettlementKey::from_hex("0x1234").is_none());
+    }
+
+    #[test]
+    fn recovers_known_signatures() {
+        // RFC 6979 signatures over 0x11 * 32 with keys 1 and
+        // 0x4c08...2318, as `ecrecover` takes them
+        let digest = [0x11; 32];
+        let known = [
+            (
+                "625704c33dc9ef0c81f521cfac3a731f33800a8a7a99341b638d8e0c64607a3c\
+                 285d904e2355a2c7da1b4e53673028fcd2de2553f845475df3e191d9c4a87437\
+                 1c",
+                "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf",
+            ),
+            (
+                "3134afcb504290ed4fb6c1cb52415a0496b48591a0266c12090588bad7c5d145\
+                 4b78db58ef88d8d2bd746a26a4a0186c264f4dff2fc7ee5de3a5c845cd3664eb\
+                 1c",
+                "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23",
+            ),
+        ];
+        for (signature, signer) in known {
+            let signature = hex::decode(signature).unwrap();
+            assert_eq!(recover_signer(&digest, &signature).as_deref(), Some(signer));
+        }
+    }
+
+    #[test]
+    fn signatures_recover_to_the_key_address() {
+        let key = SettlementKey::from_hex(
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .unwrap();
+        let digest = attestation().digest();
+        let signature = key.sign(&digest);
+        assert!(signature[64] == 27 || signature[64] == 28);
+        assert_eq!(recover_signer(&digest, &signature), Some(key.address()));
+
+        let mut other = digest;
+        other[0] ^= 1;
+        assert_ne!(recover_signer(&other, &signature), Some(key.address()));
+        assert_eq!(recover_signer(&digest, &signature[..64]), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Bounded queue of messages waiting for the coordinator connection
+//!
+//! Verification jobs and the read loop put messages here, and the writer of
+//! whichever connection is up sends them. The queue outlives individual
+//! connections, so results finished while reconnecting go out once the
+//! agent is back.
+//!
+//! `OUTBOUND_QUEUE_CAPACITY` bounds it. What happens to a result that
+//! arrives when it is full depends on `OUTBOUND_OVERFLOW`:
+//!
+//! - `block` (the default) holds the verification job until the writer
+//!   catches up, so a stalled connection slows verification down
+//! - `drop-oldest` evicts the oldest queued message, so verification keeps
+//!   going. Dropped results stay in the outbox and are replayed on the next
+//!   connection.
+//!
+//! Control replies from the read loop never wait. Under `block` they are
+//! queued past the bound rather than stall heartbeats.
+//!
+//! While the queue is full the writer is evidently not keeping up, so new
+//! tasks are nacked with `task_rejected` (`busy`) instead of adding to the
+//! backlog.
+
+use crate::metrics::METRICS;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+/// What a full queue does with another result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for room
+    #[default]
+    Block,
+    /// Evict the oldest queued message
+    DropOldest,
+}
+
+impl OverflowPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Block => "block",
+            OverflowPolicy::DropOldest => "drop-oldest",
+        }
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop-oldest" | "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            other => Err(format!("unsupported outbound overflow policy: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+struct Inner {
+    messages: VecDeque<Message>,
+    closed: bool,
+}
+
+/// Bounded multi-producer queue drained by the connection writer
+pub struct OutboundQueue {
+    capacity: usize,
+    policy: OverflowPolicy,
+    inner: Mutex<Inner>,
+    /// Signalled when a message is queued or the queue is closed
+    readable: Notify,
+    /// Signalled when the writer takes a message
+    writable: Notify,
+}
+
+impl OutboundQueue {
+    /// Queue holding `capacity` messages, at least one
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            inner: Mutex::new(Inner {
+                messages: VecDeque::new(),
+                closed: false,
+            }),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        }
+    }
+
+    /// Whether the queue holds `capacity` messages or more
+    pub fn is_full(&self) -> bool {
+        self.inner.lock().unwrap().messages.len() >= self.capacity
+    }
+
+    /// Queue a message without waiting; a full queue evicts its oldest
+    /// message under `drop-oldest` and goes over its bound under `block`
+    pub fn push(&self, message: Message) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return;
+        }
+        if inner.messages.len() >= self.capacity && self.policy == OverflowPolicy::DropOldest {
+            inner.messages.pop_front();
+            METRICS.outbound_dropped.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                capacity = self.capacity,
+                "Outbound queue full, dropped the oldest message"
+            );
+        }
+        inner.messages.push_back(message);
+        drop(inner);
+        self.readable.notify_one();
+    }
+
+    /// Queue a result, waiting for room under `block`
+    pub async fn send(&self, message: Message) {
+        if self.policy == OverflowPolicy::Block {
+            loop {
+                // Register before checking, so a take in between still wakes us
+                let room = self.writable.notified();
+                {
+                    let inner = self.inner.lock().unwrap();
+                    if inner.closed || inner.messages.len() < self.capacity {
+                        break;
+                    }
+                }
+                room.await;
+            }
+        }
+        self.push(message);
+    }
+
+    /// Next message to write, or None once the queue is closed and empty
+    pub async fn recv(&self) -> Option<Message> {
+        loop {
+            let ready = self.readable.notified();
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(message) = inner.messages.pop_front() {
+                    drop(inner);
+                    self.writable.notify_one();
+                    return Some(message);
+                }
+                if inner.closed {
+                    return None;
+                }
+            }
+            ready.await;
+        }
+    }
+
+    /// Accept no more messages; the writer finishes what is queued and
+    /// then sees the end, and blocked senders are let go
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+        self.readable.notify_waiters();
+        self.writable.notify_waiters();
+    }
+}
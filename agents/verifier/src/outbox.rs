@@ -0,0 +1,215 @@
+//! Persistent outbox for task reports
+//!
+//! A result sent just before the connection drops is lost unless the agent
+//! keeps it. Each report is written to `<dir>/<blake3(questId)>.json` before
+//! it is sent and removed once the coordinator answers with `result_ack`;
+//! whatever is left is replayed after the next registration. A quest has at
+//! most one pending report, so a resend overwrites rather than accumulates.
+//! Without a directory the reports are held in memory instead, which covers
+//! reconnects but not restarts.
+//!
+//! Disk errors are logged and otherwise ignored: the outbox only improves
+//! delivery, it never holds up a result.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    quest_id: String,
+    /// Unix seconds when the report was stored
+    stored_at: u64,
+    /// The report exactly as sent, signature included
+    message: String,
+}
+
+/// Report awaiting acknowledgement
+pub struct PendingReport {
+    pub quest_id: String,
+    pub message: String,
+}
+
+/// Unacknowledged task reports, on disk or in memory
+pub struct Outbox {
+    /// Where reports are written; `None` keeps them in `memory` only
+    dir: Option<PathBuf>,
+    /// Reports in the order they were stored, when there is no directory
+    memory: Mutex<Vec<Entry>>,
+    /// Reports older than this are dropped instead of replayed
+    retention: Duration,
+    /// Only the newest reports are kept; zero disables the outbox
+    max_entries: usize,
+}
+
+impl Outbox {
+    pub fn new(dir: Option<PathBuf>, retention: Duration, max_entries: usize) -> Self {
+        Self {
+            dir,
+            memory: Mutex::new(Vec::new()),
+            retention,
+            max_entries,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_entries > 0
+    }
+
+    /// Persist `message` as the pending report for `quest_id`
+    pub fn store(&self, quest_id: &str, message: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        let entry = Entry {
+            quest_id: quest_id.to_string(),
+            stored_at: unix_now(),
+            message: message.to_string(),
+        };
+        let Some(dir) = &self.dir else {
+            let mut memory = self.memory.lock().unwrap();
+            memory.retain(|pending| pending.quest_id != quest_id);
+            memory.push(entry);
+            if memory.len() > self.max_entries {
+                let excess = memory.len() - self.max_entries;
+                for dropped in memory.drain(..excess) {
+                    warn!(quest_id = %dropped.quest_id, "Dropping result over outbox limit");
+                }
+            }
+            return;
+        };
+        if let Err(e) = write_entry(dir, &entry) {
+            warn!(quest_id, error = %e, dir = %dir.display(), "Failed to persist result");
+        }
+    }
+
+    /// Forget the report for `quest_id` once the coordinator has it
+    pub fn ack(&self, quest_id: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        let Some(dir) = &self.dir else {
+            let mut memory = self.memory.lock().unwrap();
+            let before = memory.len();
+            memory.retain(|pending| pending.quest_id != quest_id);
+            if memory.len() < before {
+                debug!(quest_id, "Result acknowledged");
+            } else {
+                debug!(quest_id, "Acknowledgement for unknown result");
+            }
+            return;
+        };
+        match fs::remove_file(path_for(dir, quest_id)) {
+            Ok(()) => debug!(quest_id, "Result acknowledged"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!(quest_id, "Acknowledgement for unknown result")
+            }
+            Err(e) => warn!(quest_id, error = %e, "Failed to remove acknowledged result"),
+        }
+    }
+
+    /// Unacknowledged reports, oldest first. Expired, unreadable and
+    /// over-limit entries are deleted along the way.
+    pub fn pending(&self) -> Vec<PendingReport> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        let cutoff = unix_now().saturating_sub(self.retention.as_secs());
+        let Some(dir) = &self.dir else {
+            let mut memory = self.memory.lock().unwrap();
+            memory.retain(|entry| {
+                let keep = entry.stored_at >= cutoff;
+                if !keep {
+                    warn!(quest_id = %entry.quest_id, "Dropping result past retention");
+                }
+                keep
+            });
+            return memory
+                .iter()
+                .map(|entry| PendingReport {
+                    quest_id: entry.quest_id.clone(),
+                    message: entry.message.clone(),
+                })
+                .collect();
+        };
+        let dir = match fs::read_dir(dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                warn!(error = %e, dir = %dir.display(), "Failed to read outbox");
+                return Vec::new();
+            }
+        };
+
+        let mut entries = Vec::new();
+        for path in dir.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let entry = fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<Entry>(&bytes).ok());
+            match entry {
+                Some(entry) if entry.stored_at >= cutoff => entries.push((path, entry)),
+                Some(entry) => {
+                    warn!(quest_id = %entry.quest_id, "Dropping result past retention");
+                    remove(&path);
+                }
+                None => {
+                    warn!(path = %path.display(), "Dropping unreadable outbox entry");
+                    remove(&path);
+                }
+            }
+        }
+
+        entries.sort_by_key(|(_, entry)| entry.stored_at);
+        if entries.len() > self.max_entries {
+            let excess = entries.len() - self.max_entries;
+            for (path, entry) in entries.drain(..excess) {
+                warn!(quest_id = %entry.quest_id, "Dropping result over outbox limit");
+                remove(&path);
+            }
+        }
+
+        entries
+            .into_iter()
+            .map(|(_, entry)| PendingReport {
+                quest_id: entry.quest_id,
+                message: entry.message,
+            })
+            .collect()
+    }
+}
+
+fn path_for(dir: &Path, quest_id: &str) -> PathBuf {
+    // Quest IDs are coordinator-chosen; hash them into safe file names
+    let name = blake3::hash(quest_id.as_bytes()).to_hex();
+    dir.join(format!("{}.json", name))
+}
+
+/// Write via a temporary file so a crash never leaves half an entry
+fn write_entry(dir: &Path, entry: &Entry) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = path_for(dir, &entry.quest_id);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(entry)?)?;
+    fs::rename(&tmp, &path)
+}
+
+fn remove(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        warn!(path = %path.display(), error = %e, "Failed to remove outbox entry");
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
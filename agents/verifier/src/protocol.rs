@@ -0,0 +1,1184 @@
+//! Coordinator protocol messages and helpers
+//!
+//! Signed messages carry a hex ed25519 `signature` field computed over the
+//! canonical JSON (RFC 8785) of the message with that field removed.
+
+use crate::canonical_json::{canonicalize, HashMode};
+use crate::decryption::ChunkKey;
+use crate::eigencloud_sdk::TeeType;
+use crate::error::VerifierError;
+use crate::merkle::MerkleProof;
+use crate::metrics::AgentStats;
+use crate::source_stats::SourceReliability;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// TEE Attestation result from EigenCloud
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeeAttestation {
+    /// Intel TDX or SGX attestation quote
+    pub quote: String,
+    /// `quote` is a dev-mode stand-in or hash-only placeholder, not
+    /// produced by TEE hardware
+    #[serde(default)]
+    pub simulated: bool,
+    /// Reused from a recent identical verification rather than freshly
+    /// produced; the timestamp and result signature are still new
+    #[serde(default)]
+    pub cached: bool,
+    /// TEE hardware that produced `quote`, for routing verification
+    pub tee_type: TeeType,
+    /// TD measurement from the parsed TDX quote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mrtd: Option<String>,
+    /// Aggregate hash of verified data
+    pub data_hash: String,
+    /// Algorithm used to compute `data_hash`
+    pub data_hash_algorithm: String,
+    /// When the attestation was made, in Unix seconds: the TEE container's
+    /// own time when it reports one, else `receivedAt`
+    pub timestamp: u64,
+    /// When the agent received the attestation, by its own clock
+    #[serde(rename = "receivedAt", default)]
+    pub received_at: u64,
+    /// Validator public key
+    pub validator_pubkey: String,
+    /// Signature over attestation
+    pub signature: String,
+    /// Challenge nonce from the task, or a fresh one from the agent, bound
+    /// into `signature`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Reproducibility score (0-100)
+    pub confidence_score: u8,
+}
+
+/// Protocol version sent in `register`, raised when coordinators need to
+/// tell agents that understand a change from those that don't
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// `reason` of a registration refused because another agent holds the
+/// same `agentId`
+pub const DUPLICATE_AGENT_ID: &str = "duplicate_agent_id";
+
+/// How messages are framed on a coordinator connection. The agent offers
+/// [`Encoding::ALL`] in `register` and the coordinator picks one in
+/// `register_ack`; until then, and if it never picks, everything is JSON.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Text frames of JSON
+    #[default]
+    Json,
+    /// Binary frames of MessagePack, see [`crate::msgpack`]
+    Msgpack,
+    /// Binary frames of CBOR, see [`crate::cbor`]
+    Cbor,
+}
+
+impl Encoding {
+    /// Every encoding the agent speaks
+    pub const ALL: [Encoding; 3] = [Encoding::Json, Encoding::Msgpack, Encoding::Cbor];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::Msgpack => "msgpack",
+            Encoding::Cbor => "cbor",
+        }
+    }
+}
+
+/// Message received from the coordinator
+#[derive(Debug)]
+pub enum CoordinatorMessage {
+    VerifyTask(Box<VerifyTask>),
+    /// Several tasks attested together
+    VerifyBatch(VerifyBatch),
+    Ping,
+    /// Drop a queued or running verification task
+    CancelTask {
+        quest_id: String,
+    },
+    /// Ask for a [`StatusReport`]
+    StatusRequest,
+    /// Acknowledgement of our `register`, possibly making this coordinator
+    /// the agent's primary. Sent as `register_ack`, or `registered` by
+    /// older coordinators.
+    Registered {
+        primary: Option<bool>,
+        /// Encoding picked from those offered; JSON when absent
+        encoding: Option<Encoding>,
+        /// Capabilities the coordinator will make use of; `None` accepts
+        /// everything offered
+        accepted_capabilities: Option<Vec<String>>,
+        /// Protocol version the coordinator speaks
+        protocol_version: Option<u32>,
+    },
+    /// The coordinator refused our `register`, e.g. because another agent
+    /// holds the same `agentId`
+    RegisterRejected {
+        reason: String,
+    },
+    /// This coordinator becomes, or stops being, the one the agent takes
+    /// tasks from in active-active mode
+    Primary {
+        primary: bool,
+    },
+    /// The coordinator has stored our report for this quest
+    ResultAck {
+        quest_id: String,
+    },
+    /// Runtime settings pushed by the coordinator; absent fields are left
+    /// unchanged
+    ConfigUpdate {
+        allowed_measurements: Option<Vec<String>>,
+    },
+    /// Drain, deregister and exit, as on SIGTERM
+    Shutdown {
+        reason: Option<String>,
+    },
+    /// A message type this agent does not know, kept whole for reporting
+    Unknown { raw: Value },
+}
+
+/// Wire form of the message types the agent understands
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TaggedCoordinatorMessage {
+    VerifyTask(Box<VerifyTask>),
+    VerifyBatch(VerifyBatch),
+    Ping,
+    CancelTask {
+        #[serde(rename = "questId")]
+        quest_id: String,
+    },
+    StatusRequest,
+    #[serde(alias = "register_ack")]
+    Registered {
+        /// Absent means accepted
+        #[serde(default)]
+        accepted: Option<bool>,
+        #[serde(default)]
+        reason: Option<String>,
+        #[serde(default)]
+        primary: Option<bool>,
+        #[serde(default)]
+        encoding: Option<Encoding>,
+        #[serde(rename = "acceptedCapabilities", default)]
+        accepted_capabilities: Option<Vec<String>>,
+        #[serde(rename = "protocolVersion", default)]
+        protocol_version: Option<u32>,
+    },
+    Primary {
+        primary: bool,
+    },
+    ResultAck {
+        #[serde(rename = "questId")]
+        quest_id: String,
+    },
+    ConfigUpdate {
+        #[serde(rename = "allowedMeasurements", default)]
+        allowed_measurements: Option<Vec<String>>,
+    },
+    Shutdown {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl<'de> Deserialize<'de> for CoordinatorMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Value::deserialize(deserializer)?;
+        let tagged = TaggedCoordinatorMessage::deserialize(&raw).map_err(de::Error::custom)?;
+        Ok(match tagged {
+            TaggedCoordinatorMessage::VerifyTask(task) => CoordinatorMessage::VerifyTask(task),
+            TaggedCoordinatorMessage::VerifyBatch(batch) => CoordinatorMessage::VerifyBatch(batch),
+            TaggedCoordinatorMessage::Ping => CoordinatorMessage::Ping,
+            TaggedCoordinatorMessage::CancelTask { quest_id } => {
+                CoordinatorMessage::CancelTask { quest_id }
+            }
+            TaggedCoordinatorMessage::StatusRequest => CoordinatorMessage::StatusRequest,
+            TaggedCoordinatorMessage::Registered {
+                accepted: Some(false),
+                reason,
+                ..
+            } => CoordinatorMessage::RegisterRejected {
+                reason: reason.unwrap_or_else(|| "unspecified".to_string()),
+            },
+            TaggedCoordinatorMessage::Registered {
+                primary,
+                encoding,
+                accepted_capabilities,
+                protocol_version,
+                ..
+            } => CoordinatorMessage::Registered {
+                primary,
+                encoding,
+                accepted_capabilities,
+                protocol_version,
+            },
+            TaggedCoordinatorMessage::Primary { primary } => {
+                CoordinatorMessage::Primary { primary }
+            }
+            TaggedCoordinatorMessage::ResultAck { quest_id } => {
+                CoordinatorMessage::ResultAck { quest_id }
+            }
+            TaggedCoordinatorMessage::ConfigUpdate { allowed_measurements } => {
+                CoordinatorMessage::ConfigUpdate { allowed_measurements }
+            }
+            TaggedCoordinatorMessage::Shutdown { reason } => {
+                CoordinatorMessage::Shutdown { reason }
+            }
+            TaggedCoordinatorMessage::Other => CoordinatorMessage::Unknown { raw },
+        })
+    }
+}
+
+/// Message sent to the coordinator
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentMessage {
+    /// Signed with the agent key so the coordinator can check that the
+    /// sender holds `pubkey`; `nonce` and `timestamp` keep a captured
+    /// registration from being replayed
+    Register {
+        role: String,
+        #[serde(rename = "agentId")]
+        agent_id: String,
+        pubkey: String,
+        capabilities: Vec<String>,
+        /// Agent build, from `CARGO_PKG_VERSION`
+        version: String,
+        /// Names of the hash algorithms chunks may use
+        #[serde(rename = "supportedHashAlgos")]
+        supported_hash_algos: Vec<String>,
+        /// Message encodings the agent can use, preferred first
+        encodings: Vec<Encoding>,
+        /// [`PROTOCOL_VERSION`]
+        #[serde(rename = "protocolVersion")]
+        protocol_version: u32,
+        /// Random hex, fresh for every connection
+        nonce: String,
+        /// Unix seconds when the registration was signed
+        timestamp: u64,
+        /// Shared coordinator token, when the agent has one
+        #[serde(rename = "authToken", skip_serializing_if = "Option::is_none")]
+        auth_token: Option<String>,
+    },
+    /// Last message before a clean exit, so the coordinator stops
+    /// dispatching to the agent
+    Deregister {
+        #[serde(rename = "agentId")]
+        agent_id: String,
+    },
+    Pong(HealthSummary),
+    /// Health and task counts, sent every status interval
+    Status(StatusUpdate),
+    /// Reply to `status_request`
+    StatusResponse(StatusReport),
+    TaskResult(Box<VerificationResult>),
+    /// A task that ended without a result
+    #[serde(rename = "task_result")]
+    TaskFailed(TaskFailure),
+    /// A task turned away before it was queued, for the coordinator to
+    /// reschedule
+    TaskRejected {
+        #[serde(rename = "questId")]
+        quest_id: String,
+        #[serde(rename = "agentId")]
+        agent_id: String,
+        /// "busy", or "not_primary" for a task from a standby coordinator
+        reason: String,
+        /// Tasks waiting for a concurrency slot when this one arrived
+        #[serde(rename = "queueDepth")]
+        queue_depth: usize,
+    },
+    /// A coordinator message the agent could not act on
+    Error {
+        #[serde(rename = "agentId")]
+        agent_id: String,
+        code: String,
+        error: String,
+        /// `type` of the offending message, if it had one
+        #[serde(rename = "messageType", skip_serializing_if = "Option::is_none")]
+        message_type: Option<String>,
+    },
+}
+
+impl AgentMessage {
+    /// Serialize for the wire
+    pub fn to_json(&self) -> Result<String, VerifierError> {
+        serde_json::to_string(self).map_err(|e| VerifierError::SerializationFailed(e.to_string()))
+    }
+}
+
+/// Agent health, shared by pongs and status messages so the coordinator can
+/// route work away from a degraded agent
+#[derive(Debug, Serialize)]
+pub struct HealthSummary {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    pub degraded: bool,
+    #[serde(rename = "teeBreaker")]
+    pub tee_breaker: String,
+    /// Verification tasks queued or running
+    #[serde(rename = "inFlight")]
+    pub in_flight: usize,
+    /// Tasks waiting for a concurrency slot
+    #[serde(rename = "queueDepth")]
+    pub queue_depth: usize,
+    #[serde(rename = "uptimeSecs")]
+    pub uptime_secs: u64,
+}
+
+/// Periodic `status` report for live per-agent stats
+#[derive(Debug, Serialize)]
+pub struct StatusUpdate {
+    #[serde(flatten)]
+    pub health: HealthSummary,
+    /// Tasks reported, whatever the outcome, as counted by
+    /// `verifier_tasks_processed_total`
+    #[serde(rename = "tasksCompleted")]
+    pub tasks_completed: u64,
+    /// Reported tasks that errored, timed out or came out `failed`, as
+    /// counted by `verifier_tasks_failed_total`
+    #[serde(rename = "tasksFailed")]
+    pub tasks_failed: u64,
+}
+
+/// Detailed agent state for coordinator dashboards
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    #[serde(flatten)]
+    pub health: HealthSummary,
+    /// Agent build, from `CARGO_PKG_VERSION`
+    pub version: String,
+    #[serde(rename = "devMode")]
+    pub dev_mode: bool,
+    /// `attested` or `hash-only`
+    pub mode: String,
+    /// Quests queued or running, oldest first
+    #[serde(rename = "inFlightQuests")]
+    pub in_flight_quests: Vec<String>,
+    #[serde(flatten)]
+    pub stats: AgentStats,
+    /// Verified and failed chunk counts of every source seen, kept across
+    /// restarts
+    #[serde(rename = "sourceReliability")]
+    pub source_reliability: Vec<SourceReliability>,
+}
+
+/// Task outcome other than a verification result
+#[derive(Debug, Serialize)]
+pub struct TaskFailure {
+    #[serde(rename = "questId")]
+    pub quest_id: String,
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// "error", "attestation_rejected", "aborted" or "cancelled"
+    pub status: String,
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retryable: Option<bool>,
+    /// MRTD of a quote rejected by the measurement allowlist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measurement: Option<String>,
+}
+
+/// Verification task from coordinator
+#[derive(Debug, Deserialize)]
+pub struct VerifyTask {
+    #[serde(rename = "questId")]
+    pub quest_id: String,
+    pub data: Vec<TaskChunk>,
+    /// Coordinator-provided hashes, one for each entry in `data`; only
+    /// chunks listed here can be verified
+    #[serde(rename = "expectedHashes", deserialize_with = "deserialize_hashes")]
+    pub expected_hashes: Vec<String>,
+    /// Byte encoding of chunk data before hashing ("canonical" or "raw")
+    #[serde(rename = "hashMode", default)]
+    pub hash_mode: HashMode,
+    /// Algorithm for chunks that don't declare their own and for the
+    /// aggregate Merkle tree; chunks declaring another one fail. Unset, each
+    /// chunk uses its own and the tree is Blake3.
+    #[serde(rename = "hashAlgo", default)]
+    pub hash_algo: Option<String>,
+    /// Confidence (0-100) needed for `verified`; overrides the agent's
+    /// `VERIFIER_CONFIDENCE_THRESHOLD`. Out-of-range values are clamped.
+    #[serde(
+        rename = "minConfidence",
+        default,
+        deserialize_with = "deserialize_confidence"
+    )]
+    pub min_confidence: Option<u8>,
+    /// Confidence below which the result is `failed` rather than `partial`;
+    /// overrides `VERIFIER_CONFIDENCE_FLOOR`. Out-of-range values are clamped.
+    #[serde(
+        rename = "confidenceFloor",
+        default,
+        deserialize_with = "deserialize_confidence"
+    )]
+    pub confidence_floor: Option<u8>,
+    /// Attach a Merkle inclusion proof for every verified chunk
+    #[serde(rename = "includeProofs", default)]
+    pub include_proofs: bool,
+    /// Challenge the attestation must echo, so it can't be replayed for
+    /// another request
+    #[serde(default)]
+    pub nonce: Option<String>,
+    /// `ignore` skips the timestamp checks for this task
+    #[serde(default)]
+    pub freshness: Freshness,
+    /// Overrides the agent's `MAX_CHUNK_AGE_SECS`; zero turns the age
+    /// check off
+    #[serde(rename = "maxChunkAgeSecs", default)]
+    pub max_chunk_age_secs: Option<u64>,
+    /// Overrides the agent's `MAX_CLOCK_SKEW_SECS`
+    #[serde(rename = "maxClockSkewSecs", default)]
+    pub max_clock_skew_secs: Option<u64>,
+    /// Overrides the agent's `TASK_TIMEOUT_SECS`; zero lifts the limit
+    #[serde(rename = "timeoutSecs", default)]
+    pub timeout_secs: Option<u64>,
+    /// Unix seconds by which the result is needed, however long the
+    /// timeout
+    #[serde(default)]
+    pub deadline: Option<u64>,
+    /// `evm` also delivers the attestation ABI-encoded for a settlement
+    /// contract
+    #[serde(default)]
+    pub settlement: Option<Settlement>,
+    /// Hex ed25519 public key of each source that signs its data. A chunk
+    /// from a listed source must carry a signature that verifies under this
+    /// key, whatever `pubkey` it brings along.
+    #[serde(rename = "sourceKeys", default)]
+    pub source_keys: HashMap<String, String>,
+    /// Secret for this quest's encrypted chunks, in place of the agent's
+    /// `CHUNK_DECRYPTION_KEY`
+    #[serde(rename = "decryptionKey", default)]
+    pub decryption_key: Option<ChunkKey>,
+    /// Weight of each source's chunks in the confidence score; unlisted
+    /// sources weigh [`DEFAULT_SOURCE_WEIGHT`]. Weights must be finite and
+    /// not negative.
+    #[serde(
+        rename = "sourceWeights",
+        default,
+        deserialize_with = "deserialize_weights"
+    )]
+    pub source_weights: HashMap<String, f64>,
+    /// Check that the verified chunks agree on a numeric value
+    #[serde(default)]
+    pub consistency: Option<ConsistencyCheck>,
+    /// Assertions about each verified chunk's data
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// Weight of a source the task's `sourceWeights` does not list
+pub const DEFAULT_SOURCE_WEIGHT: f64 = 1.0;
+
+impl VerifyTask {
+    /// Weight of `source`'s chunks in this task's confidence score
+    pub fn source_weight(&self, source: &str) -> f64 {
+        self.source_weights
+            .get(source)
+            .copied()
+            .unwrap_or(DEFAULT_SOURCE_WEIGHT)
+    }
+}
+
+/// Form of a result a chain can check, besides the signed JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Settlement {
+    /// ABI encoding, Keccak-256 digest and EIP-191 signature; see
+    /// [`crate::onchain`]
+    Evm,
+}
+
+/// Whether chunk timestamps are checked against the local clock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Freshness {
+    /// Chunks outside the freshness window fail as `stale` or
+    /// `future_timestamp`
+    #[default]
+    Enforce,
+    /// Timestamps are reported but not checked
+    Ignore,
+}
+
+/// Cross-source agreement on one numeric field of every verified chunk's
+/// data; see [`crate::consistency`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsistencyCheck {
+    /// JSON pointer into each chunk's `data`, e.g. `/price`
+    pub pointer: String,
+    /// How far a value may be from the aggregate before it is an outlier
+    pub tolerance: Tolerance,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    /// Share of the confidence score, 0-100, given to the share of chunks
+    /// within tolerance; overrides `CONSISTENCY_WEIGHT`. Out-of-range
+    /// values are clamped.
+    #[serde(default, deserialize_with = "deserialize_confidence")]
+    pub weight: Option<u8>,
+}
+
+/// Largest allowed distance from the aggregate, e.g. `{"percent": 0.5}`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tolerance {
+    /// In the value's own units
+    Absolute(f64),
+    /// Percent of the aggregate
+    Percent(f64),
+}
+
+/// How the chunks' values are combined into the one they are held to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    #[default]
+    Median,
+}
+
+/// Assertion about the value at one place in every verified chunk's data;
+/// see [`crate::rules`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// JSON pointer into each chunk's `data`, e.g. `/volume`; empty for
+    /// the data as a whole
+    pub pointer: String,
+    #[serde(flatten)]
+    pub check: RuleCheck,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+/// What a rule asserts, named by `op`, e.g. `{"op": "range", "min": 0}`.
+/// Bounds are inclusive and either may be left out, not both.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum RuleCheck {
+    /// Something is at the pointer, `null` included
+    Exists,
+    /// The value is of a JSON type
+    Type {
+        #[serde(rename = "type")]
+        kind: JsonType,
+    },
+    /// The value is a number, or a string holding one, within bounds
+    Range { min: Option<f64>, max: Option<f64> },
+    /// The value is a string the pattern matches; it is searched for
+    /// anywhere unless anchored with `^` and `$`
+    Regex { pattern: String },
+    /// The value is a string, array or object whose characters, items or
+    /// entries number within bounds
+    Len {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+}
+
+/// JSON type a `type` rule asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonType {
+    Null,
+    Boolean,
+    Number,
+    /// A number without a fractional part
+    Integer,
+    String,
+    Array,
+    Object,
+}
+
+/// What breaking a rule costs a chunk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The chunk fails as `rule_violation`
+    #[default]
+    Fail,
+    /// The chunk still verifies but counts for less in the confidence
+    /// score, by `RULE_WARNING_PENALTY`
+    Warn,
+}
+
+/// Tasks verified together under one attestation of the Merkle root of
+/// their aggregate hashes, saving a TEE call per quest. Each task is still
+/// answered with its own `task_result`.
+#[derive(Debug, Deserialize)]
+pub struct VerifyBatch {
+    #[serde(rename = "batchId")]
+    pub batch_id: String,
+    pub tasks: Vec<BatchEntry>,
+    /// Challenge the shared attestation must echo; tasks in a batch can't
+    /// bring their own
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+/// Entry of a batch's `tasks` array. A malformed task fails on its own
+/// instead of rejecting the batch.
+#[derive(Debug)]
+pub enum BatchEntry {
+    Valid(Box<VerifyTask>),
+    Invalid { quest_id: String, error: String },
+}
+
+impl BatchEntry {
+    pub fn quest_id(&self) -> &str {
+        match self {
+            BatchEntry::Valid(task) => &task.quest_id,
+            BatchEntry::Invalid { quest_id, .. } => quest_id,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BatchEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Value::deserialize(deserializer)?;
+        let quest_id = raw
+            .get("questId")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        Ok(match VerifyTask::deserialize(raw) {
+            Ok(task) => BatchEntry::Valid(Box::new(task)),
+            Err(e) => BatchEntry::Invalid {
+                quest_id,
+                error: e.to_string(),
+            },
+        })
+    }
+}
+
+/// Hex digests are compared as lowercase, whatever case they were sent in
+fn deserialize_hash<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer).map(|hash| hash.to_ascii_lowercase())
+}
+
+fn deserialize_hashes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Vec::<String>::deserialize(deserializer).map(|hashes| {
+        hashes
+            .iter()
+            .map(|hash| hash.to_ascii_lowercase())
+            .collect()
+    })
+}
+
+fn deserialize_confidence<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(clamp_confidence)
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+fn deserialize_weights<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, f64>, D::Error> {
+    let weights = HashMap::<String, f64>::deserialize(deserializer)?;
+    match weights.iter().find(|(_, w)| !w.is_finite() || **w < 0.0) {
+        Some((source, weight)) => Err(de::Error::custom(format!(
+            "weight {} of source {} is not a finite, non-negative number",
+            weight, source
+        ))),
+        None => Ok(weights),
+    }
+}
+
+/// Parse a confidence percentage, clamping it to 0-100
+pub fn parse_confidence(raw: &str) -> Result<u8, String> {
+    let value: f64 = raw
+        .trim()
+        .parse()
+        .map_err(|_| format!("{:?} is not a number", raw))?;
+    clamp_confidence(value)
+}
+
+fn clamp_confidence(value: f64) -> Result<u8, String> {
+    if value.is_nan() {
+        return Err("confidence must be a number between 0 and 100".into());
+    }
+    Ok(value.clamp(0.0, 100.0).round() as u8)
+}
+
+/// Data chunk to verify
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DataChunk {
+    pub source: String,
+    /// Inline data; unused when `data_ref` is set
+    #[serde(default)]
+    pub data: Value,
+    /// Data too large to inline, downloaded and hashed as raw bytes
+    #[serde(rename = "dataRef", alias = "data_ref", default, skip_serializing_if = "Option::is_none")]
+    pub data_ref: Option<DataRef>,
+    /// Hex digest, lowercased when read
+    #[serde(deserialize_with = "deserialize_hash")]
+    pub hash: String,
+    /// When the data was fetched, in Unix seconds
+    pub timestamp: u64,
+    /// Algorithm that produced `hash` ("blake3", "sha256", "sha512" or
+    /// "keccak256"); the task's `hashAlgo` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    /// Hex ed25519 signature by the source over the bytes that are hashed:
+    /// the encoded inline data, or the referenced data as downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Hex ed25519 key for `signature` when the task has none for `source`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
+    /// Set when `data` is a base64 ciphertext; `hash` and `signature` are
+    /// then over the plaintext bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<ChunkEncryption>,
+}
+
+/// How a chunk's data was encrypted to the verifier
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ChunkEncryption {
+    /// Only "aes-256-gcm"
+    pub alg: String,
+    /// Base64 96-bit nonce
+    pub nonce: String,
+    /// Base64 additional authenticated data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aad: Option<String>,
+}
+
+/// Location of a chunk's data
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DataRef {
+    pub url: String,
+    /// Expected length in bytes; a download of any other length fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// Entry of a task's `data` array. One malformed chunk (for instance a
+/// producer's fetch error) fails on its own instead of rejecting the task.
+#[derive(Debug, Clone)]
+pub enum TaskChunk {
+    Valid(DataChunk),
+    /// Whatever identifies the entry, and why it did not parse
+    Invalid {
+        source: String,
+        hash: String,
+        error: String,
+    },
+}
+
+impl<'de> Deserialize<'de> for TaskChunk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Value::deserialize(deserializer)?;
+        let field = |name: &str| {
+            raw.get(name)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let (source, hash) = (field("source"), field("hash").to_ascii_lowercase());
+        Ok(match DataChunk::deserialize(raw) {
+            Ok(chunk) => TaskChunk::Valid(chunk),
+            Err(e) => TaskChunk::Invalid {
+                source,
+                hash,
+                error: e.to_string(),
+            },
+        })
+    }
+}
+
+/// Per-chunk verification outcome
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStatus {
+    Verified,
+    Failed,
+    Unexpected,
+}
+
+/// Why a chunk did not verify
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// Data does not hash to the claimed hash
+    HashMismatch,
+    /// Claimed hash algorithm is not supported
+    UnsupportedAlgorithm,
+    /// Timestamp older than the maximum chunk age
+    Stale,
+    /// Timestamp too far ahead of the agent's clock
+    FutureTimestamp,
+    /// Hash checks out but the coordinator did not expect it
+    MissingExpectedHash,
+    /// Chunk is not a well-formed data chunk
+    DeserializeError,
+    /// Serialized data exceeds the agent's chunk size limit
+    TooLarge,
+    /// Data could not be encoded for hashing, or referenced data is empty
+    MalformedData,
+    /// Referenced data could not be downloaded
+    FetchFailed,
+    /// Encrypted data could not be decrypted, or there is no key for it
+    DecryptFailed,
+    /// Data breaks one of the task's `fail` rules
+    RuleViolation,
+}
+
+/// Outcome of a chunk's source signature check, independent of its hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    Valid,
+    /// Malformed, made with another key, or with no key to check it against
+    Invalid,
+    /// The task has a key for the chunk's source but the chunk is unsigned
+    Missing,
+}
+
+/// Per-chunk verification detail
+#[derive(Debug, Serialize)]
+pub struct ChunkResult {
+    pub hash: String,
+    pub source: String,
+    /// Empty for chunks that could not be parsed
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub algorithm: String,
+    pub status: ChunkStatus,
+    /// What the data actually hashed to, when it differs from `hash`
+    #[serde(rename = "computedHash", skip_serializing_if = "Option::is_none")]
+    pub computed_hash: Option<String>,
+    /// Why the chunk did not verify
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<FailureReason>,
+    /// Human-readable explanation of `reason`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Identical copies of this chunk dropped from the task
+    #[serde(skip_serializing_if = "is_zero")]
+    pub duplicates: usize,
+    /// Absent for unsigned chunks from sources without a key. Does not
+    /// affect `status`.
+    #[serde(rename = "signatureStatus", skip_serializing_if = "Option::is_none")]
+    pub signature_status: Option<SignatureStatus>,
+    /// Why the signature is not valid
+    #[serde(rename = "signatureDetail", skip_serializing_if = "Option::is_none")]
+    pub signature_detail: Option<String>,
+    /// Verified chunks only, when the task asked for a consistency check.
+    /// Does not affect `status`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<ChunkConsistency>,
+    /// Task rules the data broke, in rule order; only verified chunks
+    /// with inline data are checked
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+}
+
+/// A task rule a chunk's data broke
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    /// Index of the rule in the task's `rules`
+    pub rule: usize,
+    pub severity: Severity,
+    pub detail: String,
+}
+
+/// How a verified chunk's value compares with the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsistencyStatus {
+    /// Within tolerance of the aggregate
+    Consistent,
+    /// Further from the aggregate than the tolerance allows
+    Outlier,
+    /// Nothing at the pointer
+    MissingValue,
+    /// The pointer leads to something other than a number or a numeric
+    /// string
+    NonNumeric,
+    /// The data is encrypted or referenced, not inline, so can't be read
+    Unavailable,
+}
+
+/// A chunk's part in the task's consistency check
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkConsistency {
+    pub status: ConsistencyStatus,
+    /// Value at the pointer, when there is a numeric one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    /// `value` minus the aggregate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deviation: Option<f64>,
+}
+
+/// Outcome of a task's consistency check
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsistencyReport {
+    pub pointer: String,
+    pub aggregation: Aggregation,
+    pub tolerance: Tolerance,
+    /// Aggregate of the values read; absent when no chunk had one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<f64>,
+    /// Verified chunks checked, whether or not a value could be read
+    pub checked: usize,
+    pub consistent: usize,
+    pub outliers: usize,
+    /// Chunks with no numeric value at the pointer, or none readable
+    pub unreadable: usize,
+    /// Share of checked chunks within tolerance, 0-100
+    pub score: u8,
+    /// Share of the confidence score the check was given, 0-100
+    pub weight: u8,
+    /// Each source's values, aggregated the same way, against the
+    /// aggregate of all of them, by source name
+    #[serde(rename = "sourceDeviations")]
+    pub source_deviations: Vec<SourceDeviation>,
+}
+
+/// How far one source is from the aggregate
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceDeviation {
+    pub source: String,
+    pub value: f64,
+    /// `value` minus the aggregate
+    pub deviation: f64,
+    /// Whether `deviation` is beyond the tolerance
+    pub outlier: bool,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// A chunk that did not verify, as listed in `failureDetails`
+#[derive(Debug, Serialize)]
+pub struct ChunkFailure {
+    pub hash: String,
+    pub source: String,
+    pub reason: FailureReason,
+    pub detail: String,
+    /// Index of the rule broken, for a `rule_violation`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<usize>,
+}
+
+/// How one source's chunks fared in a task
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceBreakdown {
+    pub source: String,
+    /// Distinct chunks that verified
+    pub verified: usize,
+    /// Distinct chunks that failed or were unexpected
+    pub failed: usize,
+    /// Weight the source carried in the confidence score
+    pub weight: f64,
+}
+
+/// Verification result, sent as a signed `task_result`
+#[derive(Debug, Serialize)]
+pub struct VerificationResult {
+    #[serde(rename = "questId")]
+    pub quest_id: String,
+    /// Same for every delivery of this outcome, so the coordinator can drop
+    /// replays; see [`idempotency_key`]
+    #[serde(rename = "idempotencyKey")]
+    pub idempotency_key: String,
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// `verified`, `partial` or `failed`, by confidence against the
+    /// thresholds below, or `timeout` if the task ran out of time. A
+    /// result that would be `verified` on a simulated attestation is
+    /// `verified_simulated` instead.
+    pub status: String,
+    /// Confidence that was needed for `verified`
+    #[serde(rename = "minConfidence")]
+    pub min_confidence: u8,
+    /// Confidence below which the result is `failed`
+    #[serde(rename = "confidenceFloor")]
+    pub confidence_floor: u8,
+    /// Unweighted confidence, the share of expected hashes verified, as
+    /// scored before `sourceWeights` and rule warnings;
+    /// `attestation.confidence_score` is weighted. Absent when the task timed out.
+    #[serde(rename = "rawConfidence", skip_serializing_if = "Option::is_none")]
+    pub raw_confidence: Option<u8>,
+    /// Absent when the task timed out before it was attested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<TeeAttestation>,
+    /// Whether the attestation is bound to a nonce, the coordinator's or a
+    /// fresh one of the agent's
+    #[serde(rename = "replayProtected")]
+    pub replay_protected: bool,
+    /// Deprecated hash lists, superseded by `chunkResults` and kept for one
+    /// more protocol version
+    #[serde(rename = "verifiedChunks")]
+    pub verified_chunks: Vec<String>,
+    /// Chunks whose data does not hash to their claimed hash
+    #[serde(rename = "failedChunks")]
+    pub failed_chunks: Vec<String>,
+    /// Chunks that hash correctly but were not in the coordinator's expected set
+    #[serde(rename = "unexpectedChunks")]
+    pub unexpected_chunks: Vec<String>,
+    /// Outcome of every distinct submitted chunk, in input order
+    #[serde(rename = "chunkResults")]
+    pub chunk_results: Vec<ChunkResult>,
+    /// Cause of every failed or unexpected chunk, in input order
+    #[serde(rename = "failureDetails")]
+    pub failure_details: Vec<ChunkFailure>,
+    /// Verified and failed chunks per source, by source name
+    #[serde(rename = "sourceBreakdown")]
+    pub source_breakdown: Vec<SourceBreakdown>,
+    /// Set when the task asked for a consistency check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<ConsistencyReport>,
+    /// Inclusion proofs against `attestation.data_hash`, one per verified
+    /// chunk hash, when the task asked for them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proofs: Option<Vec<MerkleProof>>,
+    /// Oldest and newest timestamps among the well-formed chunks, whether
+    /// or not they verified
+    #[serde(rename = "minChunkTimestamp", skip_serializing_if = "Option::is_none")]
+    pub min_chunk_timestamp: Option<u64>,
+    #[serde(rename = "maxChunkTimestamp", skip_serializing_if = "Option::is_none")]
+    pub max_chunk_timestamp: Option<u64>,
+    /// Set when the quest was attested as part of a batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch: Option<BatchInclusion>,
+    /// Set when the task asked for `"settlement": "evm"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub onchain: Option<OnchainSettlement>,
+}
+
+/// Attestation in the form an EVM settlement contract checks
+#[derive(Debug, Serialize)]
+pub struct OnchainSettlement {
+    /// `abi.encode(dataHash, timestamp, questIdHash, confidence)`, 0x-hex
+    pub encoded: String,
+    /// Keccak-256 of `encoded`, 0x-hex
+    pub digest: String,
+    /// `r || s || v` over the EIP-191 prefixed digest, 0x-hex; only when
+    /// the agent has a `SETTLEMENT_KEY`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Address `ecrecover` yields for `signature`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+}
+
+/// Link from a batched quest to the shared attestation. There,
+/// `data_hash` is the batch root; `proof` shows this quest's own aggregate
+/// hash (its `chunkHash`, the root `proofs` refer to) is under it.
+#[derive(Debug, Serialize)]
+pub struct BatchInclusion {
+    #[serde(rename = "batchId")]
+    pub batch_id: String,
+    /// Quests attested together, this one included
+    pub size: usize,
+    pub proof: MerkleProof,
+    /// `data_hash_algorithm` of this quest's own tree, whose root is
+    /// `proof.chunkHash`
+    #[serde(rename = "aggregateAlgorithm")]
+    pub aggregate_algorithm: String,
+}
+
+/// `<questId>:<dataHash>`: a replayed result repeats it, while a fresh
+/// verification of the quest over different data does not
+pub fn idempotency_key(quest_id: &str, data_hash: &str) -> String {
+    format!("{}:{}", quest_id, data_hash)
+}
+
+/// Field that carries a message signature
+pub const SIGNATURE_FIELD: &str = "signature";
+
+/// Field that carries a message's HMAC; see [`crate::message_auth`]
+pub const MAC_FIELD: &str = "mac";
+
+/// Flag on a report resent for a repeated `verify_task`, set after the
+/// report was signed
+pub const DUPLICATE_FIELD: &str = "duplicate";
+
+/// Bytes covered by a message signature: the canonical message minus its
+/// `signature` field and the `duplicate` flag and `mac` added after signing
+pub fn signing_payload(message: &Value) -> Vec<u8> {
+    match message {
+        Value::Object(map) => {
+            let mut unsigned = map.clone();
+            unsigned.remove(SIGNATURE_FIELD);
+            unsigned.remove(DUPLICATE_FIELD);
+            unsigned.remove(MAC_FIELD);
+            canonicalize(&Value::Object(unsigned))
+        }
+        other => canonicalize(other),
+    }
+}
+
+/// Bytes covered by a message MAC: the canonical message minus its `mac`
+/// field, signature included
+pub fn mac_payload(message: &Value) -> Vec<u8> {
+    match message {
+        Value::Object(map) => {
+            let mut unmacked = map.clone();
+            unmacked.remove(MAC_FIELD);
+            canonicalize(&Value::Object(unmacked))
+        }
+        other => canonicalize(other),
+    }
+}
+
+/// Check a source's hex ed25519 `signature` over `bytes` under `pubkey`
+pub fn verify_chunk_signature(bytes: &[u8], signature: &str, pubkey: &str) -> Result<(), String> {
+    let signature_bytes: [u8; 64] = hex::decode(signature.strip_prefix("0x").unwrap_or(signature))
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| "malformed signature".to_string())?;
+    let pubkey_bytes: [u8; 32] = hex::decode(pubkey.strip_prefix("0x").unwrap_or(pubkey))
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| "malformed public key".to_string())?;
+    let pubkey = VerifyingKey::from_bytes(&pubkey_bytes)
+        .map_err(|e| format!("invalid public key: {}", e))?;
+
+    pubkey
+        .verify(bytes, &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| "signature does not match data".to_string())
+}
+
+/// Check that a serialized `VerificationResult` was signed by `pubkey`
+/// (hex-encoded ed25519 public key)
+pub fn verify_result_signature(result_json: &str, pubkey: &str) -> Result<(), VerifierError> {
+    let result: Value = serde_json::from_str(result_json)
+        .map_err(|e| VerifierError::SignatureInvalid(format!("result is not JSON: {}", e)))?;
+
+    let signature_hex = result
+        .get(SIGNATURE_FIELD)
+        .and_then(Value::as_str)
+        .ok_or_else(|| VerifierError::SignatureInvalid("result is not signed".into()))?;
+    let signature_bytes: [u8; 64] = hex::decode(signature_hex)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| VerifierError::SignatureInvalid("malformed signature".into()))?;
+
+    let pubkey_bytes: [u8; 32] = hex::decode(pubkey.strip_prefix("0x").unwrap_or(pubkey))
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| VerifierError::SignatureInvalid("malformed public key".into()))?;
+    let pubkey = VerifyingKey::from_bytes(&pubkey_bytes)
+        .map_err(|e| VerifierError::SignatureInvalid(format!("invalid public key: {}", e)))?;
+
+    pubkey
+        .verify(&signing_payload(&result), &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| VerifierError::SignatureInvalid("signature does not match result".into()))
+}
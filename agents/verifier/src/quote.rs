@@ -0,0 +1,263 @@
+//! Intel TDX quote parsing
+//!
+//! The TEE container returns its hardware quote as hex or base64. Before an
+//! attestation is accepted we decode the quote, read the TD report out of it
+//! and check that the report data is the SHA-512 of the canonical JSON
+//! `{"dataHash", "nonce", "validatorPubkey"}`, the nonce only present when
+//! one was sent. A container therefore can't pair a genuine quote with a
+//! different result, replay it against a new challenge, or have it vouch
+//! for a key other than the one that signed the attestation. The quote's
+//! certificate chain is not checked here, and SGX deployments forward their
+//! quotes unparsed.
+//!
+//! Layout (Intel TDX DCAP quote v4/v5):
+//!
+//! | offset | size | field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 48   | header: version, key type, TEE type ... |
+//! | 48     | 584  | TD report body (v4)                     |
+//! | 48     | 6    | body type and size, then the body (v5)  |
+//!
+//! Within the body MRTD sits at 136, RTMR0-3 at 328 and the 64-byte report
+//! data at 520. Simulated dev-mode quotes are recognised by their prefix
+//! and never parsed.
+
+use crate::canonical_json::canonicalize;
+use crate::eigencloud_sdk::TeeType;
+use crate::error::VerifierError;
+use base64::Engine;
+use sha2::{Digest, Sha512};
+
+/// Prefix of simulated quotes, e.g. `DEV_TDX_QUOTE_<hex>`
+const DEV_QUOTE_PREFIX: &str = "DEV_";
+const DEV_QUOTE_MARKER: &str = "_QUOTE_";
+
+/// Stand-in quote on results from a hash-only agent, which attests nothing
+pub const PLACEHOLDER_QUOTE: &str = "HASH_ONLY_NO_ATTESTATION";
+
+const HEADER_LEN: usize = 48;
+/// TD 1.0 report body; TD 1.5 bodies append fields after the report data
+const TD_REPORT_LEN: usize = 584;
+/// v5 prefixes the body with a 2-byte type and 4-byte size
+const V5_BODY_PREFIX_LEN: usize = 6;
+
+/// `tee_type` header value for TDX quotes
+const TEE_TYPE_TDX: u32 = 0x81;
+/// `tee_type` header value for SGX quotes
+const TEE_TYPE_SGX: u32 = 0x00;
+
+const MRTD_OFFSET: usize = 136;
+const RTMR_OFFSET: usize = 328;
+const REPORT_DATA_OFFSET: usize = 520;
+const MEASUREMENT_LEN: usize = 48;
+const REPORT_DATA_LEN: usize = 64;
+
+/// Fields of a TDX quote that matter to the verifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedQuote {
+    /// Quote format version (4 or 5)
+    pub version: u16,
+    pub tee_type: TeeType,
+    /// Hex measurement of the initial TD contents
+    pub mrtd: String,
+    /// Hex runtime measurement registers RTMR0-3
+    pub rtmrs: [String; 4],
+    /// Data the TD bound into the report
+    pub report_data: [u8; REPORT_DATA_LEN],
+}
+
+/// Whether `quote` is a simulated dev-mode quote or the hash-only
+/// placeholder
+pub fn is_simulated(quote: &str) -> bool {
+    quote == PLACEHOLDER_QUOTE
+        || (quote.starts_with(DEV_QUOTE_PREFIX) && quote.contains(DEV_QUOTE_MARKER))
+}
+
+impl ParsedQuote {
+    /// Decode a hex or base64 quote string and parse it
+    pub fn parse(quote: &str) -> Result<Self, VerifierError> {
+        let bytes = decode(quote).ok_or_else(|| invalid("quote is neither hex nor base64".into()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parse a raw quote
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VerifierError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(invalid(format!("quote is {} bytes, shorter than its header", bytes.len())));
+        }
+        let version = u16::from_le_bytes([bytes[0], bytes[1]]);
+        let tee_type = match u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) {
+            TEE_TYPE_TDX => TeeType::Tdx,
+            TEE_TYPE_SGX => return Err(invalid("SGX quotes are not supported".to_string())),
+            other => return Err(invalid(format!("unknown TEE type {:#x}", other))),
+        };
+
+        let body_offset = match version {
+            4 => HEADER_LEN,
+            5 => HEADER_LEN + V5_BODY_PREFIX_LEN,
+            other => return Err(invalid(format!("unsupported quote version {}", other))),
+        };
+        let body = bytes
+            .get(body_offset..body_offset + TD_REPORT_LEN)
+            .ok_or_else(|| invalid(format!("quote is {} bytes, too short for a TD report", bytes.len())))?;
+
+        let measurement = |offset: usize| hex::encode(&body[offset..offset + MEASUREMENT_LEN]);
+        let mut report_data = [0u8; REPORT_DATA_LEN];
+        report_data.copy_from_slice(&body[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + REPORT_DATA_LEN]);
+
+        Ok(Self {
+            version,
+            tee_type,
+            mrtd: measurement(MRTD_OFFSET),
+            rtmrs: [0, 1, 2, 3].map(|i| measurement(RTMR_OFFSET + i * MEASUREMENT_LEN)),
+            report_data,
+        })
+    }
+
+    /// Check that the report data binds `data_hash`, the challenge `nonce`
+    /// and the `pubkey` that signed the attestation, all 64 bytes of it
+    pub fn check_report_data(
+        &self,
+        data_hash: &str,
+        nonce: Option<&str>,
+        pubkey: &[u8; 32],
+    ) -> Result<(), VerifierError> {
+        if hex::decode(data_hash).is_err() {
+            return Err(invalid("attested data hash is not hex".to_string()));
+        }
+        if self.report_data != report_data(data_hash, nonce, pubkey) {
+            return Err(invalid(
+                "quote report data does not bind the attested data hash, nonce and key".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Report data a TEE container puts in its quote for an attestation of
+/// `data_hash` answering `nonce`, signed with `pubkey`
+pub fn report_data(
+    data_hash: &str,
+    nonce: Option<&str>,
+    pubkey: &[u8; 32],
+) -> [u8; REPORT_DATA_LEN] {
+    let mut binding = serde_json::json!({
+        "dataHash": data_hash.to_ascii_lowercase(),
+        "validatorPubkey": hex::encode(pubkey),
+    });
+    if let Some(nonce) = nonce {
+        binding["nonce"] = serde_json::json!(nonce);
+    }
+    Sha512::digest(canonicalize(&binding)).into()
+}
+
+/// Hex (optionally 0x-prefixed) or standard base64
+fn decode(quote: &str) -> Option<Vec<u8>> {
+    let trimmed = quote.trim();
+    hex::decode(trimmed.strip_prefix("0x").unwrap_or(trimmed))
+        .ok()
+        .or_else(|| base64::engine::general_purpose::STANDARD.decode(trimmed).ok())
+}
+
+fn invalid(detail: String) -> VerifierError {
+    VerifierError::AttestationInvalid(detail)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// TDX quote of `version` carrying `report_data`, MRTD all 0x11
+    pub(crate) fn tdx_quote(version: u16, report_data: &[u8]) -> Vec<u8> {
+        let body_offset = if version == 5 {
+            HEADER_LEN + V5_BODY_PREFIX_LEN
+        } else {
+            HEADER_LEN
+        };
+        let mut bytes = vec![0u8; body_offset + TD_REPORT_LEN];
+        bytes[0..2].copy_from_slice(&version.to_le_bytes());
+        bytes[4..8].copy_from_slice(&TEE_TYPE_TDX.to_le_bytes());
+        let body = &mut bytes[body_offset..];
+        body[MRTD_OFFSET..MRTD_OFFSET + MEASUREMENT_LEN].fill(0x11);
+        body[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + report_data.len()]
+            .copy_from_slice(report_data);
+        bytes
+    }
+
+    const HASH: &str = "aa000000000000000000000000000000000000000000000000000000000000bb";
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn parses_v4_and_v5_quotes() {
+        for version in [4, 5] {
+            let quote = ParsedQuote::from_bytes(&tdx_quote(version, &[0xab; 4])).unwrap();
+            assert_eq!(quote.version, version);
+            assert_eq!(quote.tee_type, TeeType::Tdx);
+            assert_eq!(quote.mrtd, "11".repeat(MEASUREMENT_LEN));
+            assert_eq!(&quote.report_data[..4], &[0xab; 4]);
+        }
+    }
+
+    #[test]
+    fn decodes_hex_and_base64() {
+        let bytes = tdx_quote(4, &[1, 2, 3]);
+        let from_hex = ParsedQuote::parse(&format!("0x{}", hex::encode(&bytes))).unwrap();
+        let from_base64 =
+            ParsedQuote::parse(&base64::engine::general_purpose::STANDARD.encode(&bytes)).unwrap();
+        assert_eq!(from_hex, from_base64);
+    }
+
+    #[test]
+    fn rejects_short_sgx_and_unknown_quotes() {
+        assert!(ParsedQuote::from_bytes(&[0; 10]).is_err());
+        assert!(ParsedQuote::from_bytes(&tdx_quote(4, &[])[..HEADER_LEN + 100]).is_err());
+        let mut sgx = tdx_quote(4, &[]);
+        sgx[4..8].copy_from_slice(&TEE_TYPE_SGX.to_le_bytes());
+        assert!(ParsedQuote::from_bytes(&sgx).is_err());
+        let mut v3 = tdx_quote(4, &[]);
+        v3[0..2].copy_from_slice(&3u16.to_le_bytes());
+        assert!(ParsedQuote::from_bytes(&v3).is_err());
+    }
+
+    #[test]
+    fn accepts_report_data_binding_hash_nonce_and_key() {
+        for nonce in [None, Some("n1")] {
+            let bound = report_data(HASH, nonce, &KEY);
+            let quote = ParsedQuote::from_bytes(&tdx_quote(4, &bound)).unwrap();
+            quote.check_report_data(HASH, nonce, &KEY).unwrap();
+            quote
+                .check_report_data(&HASH.to_ascii_uppercase(), nonce, &KEY)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_report_data_that_is_only_the_hash() {
+        let hash = hex::decode(HASH).unwrap();
+        let quote = ParsedQuote::from_bytes(&tdx_quote(4, &hash)).unwrap();
+        assert!(quote.check_report_data(HASH, None, &KEY).is_err());
+    }
+
+    #[test]
+    fn rejects_report_data_for_another_hash_nonce_or_key() {
+        let bound = report_data(HASH, Some("n1"), &KEY);
+        let quote = ParsedQuote::from_bytes(&tdx_quote(4, &bound)).unwrap();
+        let other_hash = "cc".repeat(32);
+        assert!(quote
+            .check_report_data(&other_hash, Some("n1"), &KEY)
+            .is_err());
+        assert!(quote.check_report_data(HASH, Some("n2"), &KEY).is_err());
+        assert!(quote.check_report_data(HASH, None, &KEY).is_err());
+        assert!(quote.check_report_data(HASH, Some("n1"), &[8; 32]).is_err());
+        assert!(quote
+            .check_report_data("not hex", Some("n1"), &KEY)
+            .is_err());
+    }
+
+    #[test]
+    fn recognises_simulated_quotes() {
+        assert!(is_simulated("DEV_TDX_QUOTE_abcd"));
+        assert!(is_simulated(PLACEHOLDER_QUOTE));
+        assert!(!is_simulated(&hex::encode(tdx_quote(4, &[]))));
+    }
+}
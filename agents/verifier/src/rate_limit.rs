@@ -0,0 +1,63 @@
+//! Token bucket pacing for TEE attestation calls
+//!
+//! The bucket holds up to `burst` tokens and refills at `rate` per second.
+//! Each call takes one token; when the bucket is empty the caller reserves
+//! the next token anyway and sleeps until it would have arrived, so waiting
+//! callers are released one by one at the configured rate, in arrival order.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Inner {
+    /// Goes negative while callers are waiting on reserved tokens
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket rate limiter
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    inner: Mutex<Inner>,
+}
+
+impl TokenBucket {
+    /// Bucket that starts full. `rate` must be positive; a zero burst is
+    /// treated as one.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate,
+            burst,
+            inner: Mutex::new(Inner {
+                tokens: burst,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token, returning how long the caller has to wait before it
+    /// may proceed
+    pub fn reserve(&self) -> Duration {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(inner.refilled_at).as_secs_f64() * self.rate;
+        inner.tokens = (inner.tokens + refill).min(self.burst);
+        inner.refilled_at = now;
+        inner.tokens -= 1.0;
+        if inner.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-inner.tokens / self.rate)
+        }
+    }
+
+    /// Wait until a token is available and take it
+    pub async fn acquire(&self) -> Duration {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+}
@@ -0,0 +1,95 @@
+//! Reports of recently completed quests
+//!
+//! A coordinator that reconnects re-sends every task it holds no result
+//! for, including ones the agent finished while the link was down. Keeping
+//! the report, signed as it was sent, lets a repeated `verify_task` be
+//! answered straight away instead of paying for another TEE attestation.
+//! Entries expire after `ttl`; when full, the least recently used entry is
+//! evicted.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry {
+    report: String,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+struct Inner {
+    entries: HashMap<String, Entry>,
+    /// Monotonic use counter for LRU ordering
+    clock: u64,
+}
+
+/// LRU of serialized reports keyed by quest ID
+pub struct RecentResults {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl RecentResults {
+    /// A capacity of zero disables the window
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// The report sent for `quest_id`, if it is still in the window
+    pub fn get(&self, quest_id: &str) -> Option<String> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let entry = inner.entries.get_mut(quest_id)?;
+        if entry.stored_at.elapsed() >= self.ttl {
+            inner.entries.remove(quest_id);
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.report.clone())
+    }
+
+    pub fn insert(&self, quest_id: &str, report: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let ttl = self.ttl;
+        inner
+            .entries
+            .retain(|_, entry| entry.stored_at.elapsed() < ttl);
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(quest_id) {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.entries.insert(
+            quest_id.to_string(),
+            Entry {
+                report: report.to_string(),
+                stored_at: Instant::now(),
+                last_used: clock,
+            },
+        );
+    }
+}
@@ -0,0 +1,432 @@
+//! Declarative assertions about chunk data
+//!
+//! Hash checks show each chunk is what its source sent, not that what it
+//! sent makes sense: a negative volume or an empty list of trades hashes
+//! as well as anything. A task's `rules` each name a JSON pointer and an
+//! operator, and every verified chunk's inline data is held to all of them.
+//! A chunk breaking a `fail` rule fails as `rule_violation`; one breaking
+//! only `warn` rules still verifies, but covers less of its hash in the
+//! confidence score.
+//!
+//! Referenced data is raw bytes and encrypted data is not kept once
+//! hashed, so chunks carrying either are not held to the rules.
+
+use crate::protocol::{JsonType, Rule, RuleCheck, Severity, Violation};
+use regex_automata::meta::Regex;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Most rules one task may carry
+pub const MAX_RULES: usize = 64;
+
+/// Largest NFA a `regex` pattern may compile to, in bytes
+const MAX_PATTERN_SIZE: usize = 1 << 20;
+
+/// Why a task's rules can't be checked
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("{count} rules (max {max})")]
+    TooMany { count: usize, max: usize },
+    #[error("rule {index}: pointer {pointer:?} is not a JSON pointer; it must start with /")]
+    InvalidPointer { index: usize, pointer: String },
+    #[error("rule {index}: {reason}")]
+    InvalidBounds { index: usize, reason: String },
+    #[error("rule {index}: invalid pattern: {reason}")]
+    InvalidPattern { index: usize, reason: String },
+}
+
+/// A task's rules, compiled once and checked against every chunk
+pub struct RuleSet {
+    rules: Vec<Compiled>,
+}
+
+struct Compiled {
+    pointer: String,
+    severity: Severity,
+    test: Test,
+}
+
+enum Test {
+    Exists,
+    Type(JsonType),
+    Range { min: f64, max: f64 },
+    Regex(Box<Regex>),
+    Len { min: usize, max: usize },
+}
+
+impl RuleSet {
+    /// Reject rules that could never be met or read, and compile the rest
+    pub fn compile(rules: &[Rule]) -> Result<Self, RuleError> {
+        if rules.len() > MAX_RULES {
+            return Err(RuleError::TooMany {
+                count: rules.len(),
+                max: MAX_RULES,
+            });
+        }
+        let rules = rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                if !rule.pointer.is_empty() && !rule.pointer.starts_with('/') {
+                    return Err(RuleError::InvalidPointer {
+                        index,
+                        pointer: rule.pointer.clone(),
+                    });
+                }
+                let bounds = |reason: &str| RuleError::InvalidBounds {
+                    index,
+                    reason: reason.to_string(),
+                };
+                let test = match &rule.check {
+                    RuleCheck::Exists => Test::Exists,
+                    RuleCheck::Type { kind } => Test::Type(*kind),
+                    RuleCheck::Range {
+                        min: None,
+                        max: None,
+                    } => return Err(bounds("range needs a min or a max")),
+                    RuleCheck::Range { min, max } => {
+                        let min = min.unwrap_or(f64::NEG_INFINITY);
+                        let max = max.unwrap_or(f64::INFINITY);
+                        if min.is_nan() || max.is_nan() || min > max {
+                            return Err(bounds("range min is above its max"));
+                        }
+                        Test::Range { min, max }
+                    }
+                    RuleCheck::Regex { pattern } => Regex::builder()
+                        .configure(Regex::config().nfa_size_limit(Some(MAX_PATTERN_SIZE)))
+                        .build(pattern)
+                        .map(|regex| Test::Regex(Box::new(regex)))
+                        .map_err(|e| RuleError::InvalidPattern {
+                            index,
+                            reason: e.to_string(),
+                        })?,
+                    RuleCheck::Len {
+                        min: None,
+                        max: None,
+                    } => return Err(bounds("len needs a min or a max")),
+                    RuleCheck::Len { min, max } => {
+                        let min = min.unwrap_or(0);
+                        let max = max.unwrap_or(usize::MAX);
+                        if min > max {
+                            return Err(bounds("len min is above its max"));
+                        }
+                        Test::Len { min, max }
+                    }
+                };
+                Ok(Compiled {
+                    pointer: rule.pointer.clone(),
+                    severity: rule.severity,
+                    test,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Every rule `data` breaks, in rule order
+    pub fn check(&self, data: &Value) -> Vec<Violation> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter_map(|(rule, compiled)| {
+                let detail = compiled.check(data).err()?;
+                Some(Violation {
+                    rule,
+                    severity: compiled.severity,
+                    detail,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Compiled {
+    fn check(&self, data: &Value) -> Result<(), String> {
+        let place = format!("data{}", self.pointer);
+        let value = data.pointer(&self.pointer);
+        let Some(value) = value else {
+            return Err(format!("nothing at {}", place));
+        };
+        match &self.test {
+            Test::Exists => Ok(()),
+            Test::Type(kind) if is_type(value, *kind) => Ok(()),
+            Test::Type(kind) => Err(format!(
+                "{} is {}, not {}",
+                place,
+                type_name(value),
+                kind_name(*kind)
+            )),
+            Test::Range { min, max } => {
+                let number = match value {
+                    Value::Number(n) => n.as_f64(),
+                    Value::String(s) => s.trim().parse::<f64>().ok(),
+                    _ => None,
+                }
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("{} is {}, not a number", place, type_name(value)))?;
+                if number < *min {
+                    Err(format!("{} at {} is below {}", number, place, min))
+                } else if number > *max {
+                    Err(format!("{} at {} is above {}", number, place, max))
+                } else {
+                    Ok(())
+                }
+            }
+            Test::Regex(regex) => match value {
+                Value::String(s) if regex.is_match(s.as_str()) => Ok(()),
+                // The data itself stays out of the result
+                Value::String(_) => Err(format!("{} does not match the pattern", place)),
+                _ => Err(format!("{} is {}, not a string", place, type_name(value))),
+            },
+            Test::Len { min, max } => {
+                let len = match value {
+                    Value::String(s) => s.chars().count(),
+                    Value::Array(items) => items.len(),
+                    Value::Object(entries) => entries.len(),
+                    _ => {
+                        return Err(format!(
+                            "{} is {}, which has no length",
+                            place,
+                            type_name(value)
+                        ))
+                    }
+                };
+                if len < *min {
+                    Err(format!("length {} of {} is below {}", len, place, min))
+                } else if len > *max {
+                    Err(format!("length {} of {} is above {}", len, place, max))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+fn is_type(value: &Value, kind: JsonType) -> bool {
+    match kind {
+        JsonType::Null => value.is_null(),
+        JsonType::Boolean => value.is_boolean(),
+        JsonType::Number => value.is_number(),
+        JsonType::Integer => value.is_i64() || value.is_u64(),
+        JsonType::String => value.is_string(),
+        JsonType::Array => value.is_array(),
+        JsonType::Object => value.is_object(),
+    }
+}
+
+fn kind_name(kind: JsonType) -> &'static str {
+    match kind {
+        JsonType::Null => "null",
+        JsonType::Boolean => "a boolean",
+        JsonType::Number => "a number",
+        JsonType::Integer => "an integer",
+        JsonType::String => "a string",
+        JsonType::Array => "an array",
+        JsonType::Object => "an object",
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Rule;
+    use serde_json::json;
+
+    fn compile(rules: Value) -> Result<RuleSet, RuleError> {
+        RuleSet::compile(&serde_json::from_value::<Vec<Rule>>(rules).unwrap())
+    }
+
+    /// What `data` breaks of the single `rule`, if anything
+    fn broken(rule: Value, data: Value) -> Option<String> {
+        let violations = compile(json!([rule])).unwrap().check(&data);
+        assert!(violations.len() <= 1);
+        violations.into_iter().next().map(|v| v.detail)
+    }
+
+    #[test]
+    fn exists_rule() {
+        let rule = json!({ "pointer": "/a", "op": "exists" });
+        assert_eq!(broken(rule.clone(), json!({ "a": null })), None);
+        assert_eq!(broken(rule.clone(), json!({ "a": [] })), None);
+        assert_eq!(
+            broken(rule.clone(), json!({ "b": 1 })).unwrap(),
+            "nothing at data/a"
+        );
+        assert_eq!(broken(rule, json!([1])).unwrap(), "nothing at data/a");
+        // The empty pointer is the data as a whole
+        assert_eq!(
+            broken(json!({ "pointer": "", "op": "exists" }), json!(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn type_rule() {
+        let rule = |kind: &str| json!({ "pointer": "/a", "op": "type", "type": kind });
+        for (kind, value) in [
+            ("null", json!(null)),
+            ("boolean", json!(false)),
+            ("number", json!(1.5)),
+            ("number", json!(2)),
+            ("integer", json!(-2)),
+            ("string", json!("")),
+            ("array", json!([])),
+            ("object", json!({})),
+        ] {
+            assert_eq!(broken(rule(kind), json!({ "a": value })), None, "{}", kind);
+        }
+        assert_eq!(
+            broken(rule("number"), json!({ "a": "1" })).unwrap(),
+            "data/a is a string, not a number"
+        );
+        assert_eq!(
+            broken(rule("integer"), json!({ "a": 1.5 })).unwrap(),
+            "data/a is a number, not an integer"
+        );
+        assert_eq!(
+            broken(rule("object"), json!({ "a": [] })).unwrap(),
+            "data/a is an array, not an object"
+        );
+        assert_eq!(
+            broken(rule("null"), json!({})).unwrap(),
+            "nothing at data/a"
+        );
+    }
+
+    #[test]
+    fn range_rule() {
+        let rule = json!({ "pointer": "/price", "op": "range", "min": 0, "max": 10 });
+        for value in [json!(0), json!(10), json!(5.5), json!(" 7 ")] {
+            assert_eq!(broken(rule.clone(), json!({ "price": value })), None);
+        }
+        assert_eq!(
+            broken(rule.clone(), json!({ "price": -1 })).unwrap(),
+            "-1 at data/price is below 0"
+        );
+        assert_eq!(
+            broken(rule.clone(), json!({ "price": "10.5" })).unwrap(),
+            "10.5 at data/price is above 10"
+        );
+        for (value, kind) in [
+            (json!("cheap"), "a string"),
+            (json!("NaN"), "a string"),
+            (json!(true), "a boolean"),
+            (json!([1]), "an array"),
+        ] {
+            assert_eq!(
+                broken(rule.clone(), json!({ "price": value })).unwrap(),
+                format!("data/price is {}, not a number", kind)
+            );
+        }
+
+        let open_above = json!({ "pointer": "/v", "op": "range", "min": 0 });
+        assert_eq!(broken(open_above.clone(), json!({ "v": 1e300 })), None);
+        assert!(broken(open_above, json!({ "v": -0.5 })).is_some());
+    }
+
+    #[test]
+    fn regex_rule() {
+        let rule = json!({ "pointer": "/pair", "op": "regex", "pattern": "^[A-Z]+-[A-Z]+$" });
+        assert_eq!(broken(rule.clone(), json!({ "pair": "BTC-USD" })), None);
+        // The detail leaves the data out
+        assert_eq!(
+            broken(rule.clone(), json!({ "pair": "btc-usd" })).unwrap(),
+            "data/pair does not match the pattern"
+        );
+        assert_eq!(
+            broken(rule, json!({ "pair": 42 })).unwrap(),
+            "data/pair is a number, not a string"
+        );
+
+        // Unanchored patterns match anywhere
+        let unanchored = json!({ "pointer": "/s", "op": "regex", "pattern": "USD" });
+        assert_eq!(broken(unanchored, json!({ "s": "BTC-USD-PERP" })), None);
+    }
+
+    #[test]
+    fn len_rule() {
+        let rule = json!({ "pointer": "/v", "op": "len", "min": 2, "max": 3 });
+        for value in [
+            json!("héé"),
+            json!([1, 2]),
+            json!({ "x": 1, "y": 2, "z": 3 }),
+        ] {
+            assert_eq!(broken(rule.clone(), json!({ "v": value })), None);
+        }
+        assert_eq!(
+            broken(rule.clone(), json!({ "v": [] })).unwrap(),
+            "length 0 of data/v is below 2"
+        );
+        assert_eq!(
+            broken(rule.clone(), json!({ "v": "abcd" })).unwrap(),
+            "length 4 of data/v is above 3"
+        );
+        for (value, kind) in [(json!(12), "a number"), (json!(null), "null")] {
+            assert_eq!(
+                broken(rule.clone(), json!({ "v": value })).unwrap(),
+                format!("data/v is {}, which has no length", kind)
+            );
+        }
+    }
+
+    #[test]
+    fn reports_each_broken_rule_with_its_index_and_severity() {
+        let rules = compile(json!([
+            { "pointer": "/a", "op": "exists" },
+            { "pointer": "/b", "op": "type", "type": "string", "severity": "warn" },
+            { "pointer": "/c", "op": "exists" },
+        ]))
+        .unwrap();
+        let violations = rules.check(&json!({ "a": 1, "b": 2 }));
+        let broken: Vec<_> = violations.iter().map(|v| (v.rule, v.severity)).collect();
+        assert_eq!(broken, [(1, Severity::Warn), (2, Severity::Fail)]);
+        assert!(rules.check(&json!({ "a": 1, "b": "x", "c": 3 })).is_empty());
+    }
+
+    #[test]
+    fn refuses_rules_that_cannot_be_checked() {
+        let too_many = vec![json!({ "pointer": "/a", "op": "exists" }); MAX_RULES + 1];
+        assert!(matches!(
+            compile(json!(too_many)),
+            Err(RuleError::TooMany { count: 65, max: 64 })
+        ));
+        assert!(compile(json!([])).unwrap().is_empty());
+
+        let refused = |rule: Value| compile(json!([{ "pointer": "/a", "op": "exists" }, rule]));
+        assert!(matches!(
+            refused(json!({ "pointer": "a", "op": "exists" })),
+            Err(RuleError::InvalidPointer { index: 1, .. })
+        ));
+        for rule in [
+            json!({ "pointer": "/a", "op": "range" }),
+            json!({ "pointer": "/a", "op": "range", "min": 2, "max": 1 }),
+            json!({ "pointer": "/a", "op": "len" }),
+            json!({ "pointer": "/a", "op": "len", "min": 2, "max": 1 }),
+        ] {
+            assert!(matches!(
+                refused(rule),
+                Err(RuleError::InvalidBounds { index: 1, .. })
+            ));
+        }
+        assert!(matches!(
+            refused(json!({ "pointer": "/a", "op": "regex", "pattern": "(" })),
+            Err(RuleError::InvalidPattern { index: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+//! secp256k1 ECDSA for settlement signatures
+//!
+//! Signs a 32-byte digest the way Ethereum's `ecrecover` expects and
+//! recovers the signer again, with the curve arithmetic left to OpenSSL.
+//! Signatures carry `s` in the lower half of the order (EIP-2) and a
+//! recovery id. OpenSSL doesn't say which of the two points with the
+//! nonce's x coordinate it signed with, so the id is found by recovering
+//! with each and keeping the one that gives back the signing key.
+
+use openssl::bn::{BigNum, BigNumContext, BigNumRef};
+use openssl::ec::{EcGroup, EcGroupRef, EcKey, EcPoint, EcPointRef, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+
+fn curve() -> EcGroup {
+    EcGroup::from_curve_name(Nid::SECP256K1).expect("OpenSSL supports secp256k1")
+}
+
+fn order(group: &EcGroupRef, ctx: &mut BigNumContext) -> BigNum {
+    let mut n = BigNum::new().expect("bignum allocation");
+    group.order(&mut n, ctx).expect("secp256k1 has an order");
+    n
+}
+
+/// Whether `v` is in 1..n
+fn in_scalar_range(v: &BigNumRef, n: &BigNumRef) -> bool {
+    v.num_bits() > 0 && v < n
+}
+
+/// Uncompressed point without the `0x04` prefix: `x || y`
+fn uncompressed(
+    group: &EcGroupRef,
+    point: &EcPointRef,
+    ctx: &mut BigNumContext,
+) -> Option<[u8; 64]> {
+    let bytes = point
+        .to_bytes(group, PointConversionForm::UNCOMPRESSED, ctx)
+        .ok()?;
+    bytes.get(1..)?.try_into().ok()
+}
+
+/// secp256k1 private key
+pub struct SecretKey {
+    key: EcKey<Private>,
+    public_key: [u8; 64],
+}
+
+impl SecretKey {
+    /// Key from its big-endian scalar; `None` unless it is in 1..n
+    pub fn from_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        let group = curve();
+        let mut ctx = BigNumContext::new().ok()?;
+        let d = BigNum::from_slice(bytes).ok()?;
+        if !in_scalar_range(&d, &order(&group, &mut ctx)) {
+            return None;
+        }
+        let mut point = EcPoint::new(&group).ok()?;
+        point.mul_generator2(&group, &d, &mut ctx).ok()?;
+        let public_key = uncompressed(&group, &point, &mut ctx)?;
+        let key = EcKey::from_private_components(&group, &d, &point).ok()?;
+        Some(Self { key, public_key })
+    }
+
+    /// Uncompressed public key without the `0x04` prefix: `x || y`
+    pub fn public_key(&self) -> [u8; 64] {
+        self.public_key
+    }
+
+    /// Sign a 32-byte digest as `r || s || recovery id`, with `s` in the
+    /// lower half of the order
+    pub fn sign_prehash(&self, digest: &[u8; 32]) -> [u8; 65] {
+        // A nonce point whose x coordinate is at or above n needs a
+        // recovery id `ecrecover` can't take; it happens with negligible
+        // probability, and signing again picks another nonce
+        loop {
+            if let Some(signature) = self.try_sign(digest) {
+                return signature;
+            }
+        }
+    }
+
+    fn try_sign(&self, digest: &[u8; 32]) -> Option<[u8; 65]> {
+        let mut ctx = BigNumContext::new().expect("bignum allocation");
+        let n = order(self.key.group(), &mut ctx);
+        let signature = EcdsaSig::sign(digest, &self.key).expect("ECDSA signing");
+        let mut half = BigNum::new().expect("bignum allocation");
+        half.rshift1(&n).expect("bignum shift");
+        let s = if signature.s() > &half {
+            let mut low = BigNum::new().expect("bignum allocation");
+            low.checked_sub(&n, signature.s())
+                .expect("bignum subtraction");
+            low
+        } else {
+            signature.s().to_owned().expect("bignum copy")
+        };
+
+        let mut out = [0u8; 65];
+        out[..32].copy_from_slice(&signature.r().to_vec_padded(32).expect("r fits 32 bytes"));
+        out[32..64].copy_from_slice(&s.to_vec_padded(32).expect("s fits 32 bytes"));
+        (0..2).find_map(|recovery_id| {
+            out[64] = recovery_id;
+            (recover(digest, &out) == Some(self.public_key)).then_some(out)
+        })
+    }
+}
+
+/// Public key (`x || y`) that produced `signature` (`r || s || recovery
+/// id`, the id 0 or 1) over `digest`, if the signature is well formed
+pub fn recover(digest: &[u8; 32], signature: &[u8; 65]) -> Option<[u8; 64]> {
+    let recovery_id = signature[64];
+    if recovery_id > 1 {
+        return None;
+    }
+    let group = curve();
+    let mut ctx = BigNumContext::new().ok()?;
+    let n = order(&group, &mut ctx);
+    let r = BigNum::from_slice(&signature[..32]).ok()?;
+    let s = BigNum::from_slice(&signature[32..64]).ok()?;
+    if !in_scalar_range(&r, &n) || !in_scalar_range(&s, &n) {
+        return None;
+    }
+
+    // The nonce point, from its x coordinate and the parity of y
+    let mut compressed = [0u8; 33];
+    compressed[0] = 0x02 | recovery_id;
+    compressed[1..].copy_from_slice(&signature[..32]);
+    let nonce_point = EcPoint::from_bytes(&group, &compressed, &mut ctx).ok()?;
+
+    // Q = r^-1 (s R - z G)
+    let z = BigNum::from_slice(digest).ok()?;
+    let mut r_inv = BigNum::new().ok()?;
+    r_inv.mod_inverse(&r, &n, &mut ctx).ok()?;
+    let zero = BigNum::new().ok()?;
+    let mut neg_z = BigNum::new().ok()?;
+    neg_z.mod_sub(&zero, &z, &n, &mut ctx).ok()?;
+    let mut u1 = BigNum::new().ok()?;
+    u1.mod_mul(&neg_z, &r_inv, &n, &mut ctx).ok()?;
+    let mut u2 = BigNum::new().ok()?;
+    u2.mod_mul(&s, &r_inv, &n, &mut ctx).ok()?;
+    let mut q = EcPoint::new(&group).ok()?;
+    q.mul_full(&group, &u1, &nonce_point, &u2, &mut ctx).ok()?;
+    if q.is_infinity(&group) {
+        return None;
+    }
+    uncompressed(&group, &q, &mut ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(hex_secret: &str) -> SecretKey {
+        SecretKey::from_bytes(&hex::decode(hex_secret).unwrap().try_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn key_one_has_the_generator_as_public_key() {
+        let one = key("0000000000000000000000000000000000000000000000000000000000000001");
+        assert_eq!(
+            hex::encode(one.public_key()),
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+             483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"
+        );
+    }
+
+    #[test]
+    fn rejects_secrets_outside_the_order() {
+        assert!(SecretKey::from_bytes(&[0; 32]).is_none());
+        let n = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+        assert!(SecretKey::from_bytes(&hex::decode(n).unwrap().try_into().unwrap()).is_none());
+    }
+
+    #[test]
+    fn recovers_the_signer() {
+        let key = key("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        for digest in [[0u8; 32], [0x11; 32], [0xff; 32]] {
+            let signature = key.sign_prehash(&digest);
+            assert_eq!(recover(&digest, &signature), Some(key.public_key()));
+        }
+    }
+
+    #[test]
+    fn signs_with_low_s() {
+        let key = key("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        let half = "7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0";
+        for i in 0..32u8 {
+            let signature = key.sign_prehash(&[i; 32]);
+            assert!(hex::encode(&signature[32..64]).as_str() <= half);
+            assert!(signature[64] <= 1);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let key = key("0000000000000000000000000000000000000000000000000000000000000001");
+        let digest = [0x11; 32];
+        let good = key.sign_prehash(&digest);
+
+        let mut bad_id = good;
+        bad_id[64] = 2;
+        assert_eq!(recover(&digest, &bad_id), None);
+
+        let mut zero_r = good;
+        zero_r[..32].fill(0);
+        assert_eq!(recover(&digest, &zero_r), None);
+
+        let mut max_s = good;
+        max_s[32..64].fill(0xff);
+        assert_eq!(recover(&digest, &max_s), None);
+
+        let mut flipped = good;
+        flipped[64] ^= 1;
+        assert_ne!(recover(&digest, &flipped), Some(key.public_key()));
+    }
+}
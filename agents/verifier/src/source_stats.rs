@@ -0,0 +1,160 @@
+//! Per-source reliability, kept across restarts
+//!
+//! Every distinct chunk the agent checks counts toward its `source`: a
+//! verified chunk as verified, anything else as failed. The table is
+//! written to `<data_dir>/source_stats.json` after each task and read back
+//! on startup, so a source's record survives restarts. Without a path it
+//! lives in memory only.
+//!
+//! Source names come from producers, so the table is capped; past
+//! [`MAX_SOURCES`] the source seen least recently is forgotten. Disk errors
+//! are logged and otherwise ignored, as for the outbox.
+
+use crate::protocol::{ChunkResult, ChunkStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Most sources tracked at once
+pub const MAX_SOURCES: usize = 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    verified: u64,
+    failed: u64,
+    /// Unix seconds of the last task with a chunk from this source
+    last_seen: u64,
+}
+
+/// One row of the reliability table, as reported in `status_response`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceReliability {
+    pub source: String,
+    pub verified: u64,
+    pub failed: u64,
+    /// Share of this source's chunks that verified, 0 to 1
+    pub reliability: f64,
+    pub last_seen: u64,
+}
+
+/// Verified and failed chunk counts per source
+pub struct SourceStats {
+    /// Where the table is persisted; `None` keeps it in memory only
+    path: Option<PathBuf>,
+    table: Mutex<HashMap<String, Record>>,
+}
+
+impl SourceStats {
+    /// Table stored at `path`, starting from what is already there
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let table = path.as_deref().map(load).unwrap_or_default();
+        if !table.is_empty() {
+            debug!(sources = table.len(), "Loaded source statistics");
+        }
+        Self {
+            path,
+            table: Mutex::new(table),
+        }
+    }
+
+    /// Count the outcome of a task's chunks. Chunks without a source are
+    /// skipped.
+    pub fn record(&self, results: &[ChunkResult]) {
+        if results.iter().all(|result| result.source.is_empty()) {
+            return;
+        }
+        let now = unix_now();
+        let mut table = self.table.lock().unwrap();
+        for result in results.iter().filter(|result| !result.source.is_empty()) {
+            let record = table.entry(result.source.clone()).or_default();
+            if result.status == ChunkStatus::Verified {
+                record.verified += 1;
+            } else {
+                record.failed += 1;
+            }
+            record.last_seen = now;
+        }
+        while table.len() > MAX_SOURCES {
+            let Some(oldest) = table
+                .iter()
+                .min_by_key(|(_, record)| record.last_seen)
+                .map(|(source, _)| source.clone())
+            else {
+                break;
+            };
+            table.remove(&oldest);
+        }
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = serde_json::to_vec(&*table)
+            .map_err(io::Error::from)
+            .and_then(|bytes| write_table(path, &bytes));
+        if let Err(e) = written {
+            warn!(error = %e, path = %path.display(), "Failed to persist source statistics");
+        }
+    }
+
+    /// Every tracked source, by name
+    pub fn snapshot(&self) -> Vec<SourceReliability> {
+        let table = self.table.lock().unwrap();
+        let mut rows: Vec<_> = table
+            .iter()
+            .map(|(source, record)| {
+                let total = record.verified + record.failed;
+                SourceReliability {
+                    source: source.clone(),
+                    verified: record.verified,
+                    failed: record.failed,
+                    reliability: if total == 0 {
+                        0.0
+                    } else {
+                        record.verified as f64 / total as f64
+                    },
+                    last_seen: record.last_seen,
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| a.source.cmp(&b.source));
+        rows
+    }
+}
+
+fn load(path: &Path) -> HashMap<String, Record> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            warn!(error = %e, path = %path.display(), "Failed to read source statistics");
+            return HashMap::new();
+        }
+    };
+    serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        warn!(error = %e, path = %path.display(), "Ignoring unreadable source statistics");
+        HashMap::new()
+    })
+}
+
+/// Write via a temporary file so a crash never leaves half a table
+fn write_table(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
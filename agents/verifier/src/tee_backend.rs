@@ -0,0 +1,86 @@
+//! Confidential-compute providers that attest verification results
+//!
+//! The agent reaches its TEE only through a [`TeeBackend`].
+//! [`EigenCompute`](crate::eigencloud_sdk::EigenCompute) is the EigenCloud
+//! one and the default; another provider is a type implementing the trait,
+//! handed to [`VerifierAgentBuilder::tee_backend`](crate::VerifierAgentBuilder::tee_backend).
+//!
+//! Only attesting and checking attestations are required. The rest default
+//! to a provider with no circuit breaker, measurement allowlist, account or
+//! container to manage.
+
+use crate::breaker::BreakerState;
+use crate::compute_backend::BackendFuture;
+use crate::eigencloud_sdk::{AttestationResponse, TeeType};
+use crate::error::VerifierError;
+use crate::quote::ParsedQuote;
+use std::time::Duration;
+
+/// TEE provider the agent gets attestations from
+pub trait TeeBackend: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// TEE hardware attestations come from
+    fn tee_type(&self) -> TeeType;
+
+    /// Whether attestations are simulated instead of hardware-backed
+    fn is_dev_mode(&self) -> bool {
+        false
+    }
+
+    /// Nonce to challenge the TEE with, given the one the task asked for
+    fn challenge_nonce(&self, requested: Option<&str>) -> Option<String> {
+        requested.map(str::to_string)
+    }
+
+    /// Attest `data_hash`, the aggregate of `verified_hashes` under
+    /// `data_hash_algorithm`, echoing `nonce` if there is one
+    fn execute_verification<'a>(
+        &'a self,
+        data_hash: &'a str,
+        data_hash_algorithm: &'a str,
+        verified_hashes: &'a [String],
+        quest_id: &'a str,
+        nonce: Option<&'a str>,
+    ) -> BackendFuture<'a, AttestationResponse>;
+
+    /// Check that `attestation` covers `data_hash` and `nonce`, returning
+    /// the parsed quote if there is one to report
+    fn verify_attestation(
+        &self,
+        attestation: &AttestationResponse,
+        data_hash: &str,
+        nonce: Option<&str>,
+    ) -> Result<Option<ParsedQuote>, VerifierError>;
+
+    /// State of the provider's circuit breaker; anything but closed means
+    /// degraded
+    fn breaker_state(&self) -> BreakerState {
+        BreakerState::Closed
+    }
+
+    /// Replace the accepted measurements; an empty list accepts any
+    fn set_allowed_measurements(&self, _measurements: &[String]) {}
+
+    /// Account the provider is authenticated as; checked before the agent
+    /// registers
+    fn check_auth(&self) -> BackendFuture<'_, String> {
+        Box::pin(async move { Ok(self.name().to_string()) })
+    }
+
+    /// How often to re-check auth once the agent is running, if at all
+    fn auth_check_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Bring up whatever attestations need before the first task
+    fn provision(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Tear down what `provision` brought up, on shutdown
+    fn release(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
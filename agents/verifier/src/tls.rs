@@ -0,0 +1,191 @@
+//! TLS for the coordinator connection
+//!
+//! `wss://` coordinators are verified against the system roots. A
+//! self-hosted coordinator behind an internal CA can add that CA's PEM
+//! bundle with `COORDINATOR_CA_CERT` (or `COORDINATOR_CA_FILE`), and
+//! `COORDINATOR_PIN_SHA256` (or `COORDINATOR_CERT_PIN`) pins the
+//! coordinator's leaf certificate by the SHA-256 of its DER encoding (the
+//! value `openssl x509 -noout -fingerprint -sha256` prints, colons
+//! optional). The pin is checked right after the TLS handshake, before the
+//! WebSocket upgrade request is sent.
+//!
+//! The TEE container client takes the same kind of bundle and pin from
+//! `TEE_CA_FILE` and `TEE_CERT_PIN`; see [`crate::eigencloud_sdk`].
+//!
+//! Failures to reach the host are reported as transport errors, failed
+//! certificate checks as [`VerifierError::TlsValidation`] or
+//! [`VerifierError::CertificatePinMismatch`], so the two are told apart.
+
+use crate::error::VerifierError;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::{client_async, connect_async, MaybeTlsStream, WebSocketStream};
+
+/// WebSocket connection to the coordinator
+pub type CoordinatorStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const PEM_END: &str = "-----END CERTIFICATE-----";
+const WSS_DEFAULT_PORT: u16 = 443;
+
+/// How the agent connects to the coordinator
+#[derive(Clone, Default)]
+pub struct CoordinatorTls {
+    /// Set only when the defaults are customised
+    connector: Option<tokio_native_tls::TlsConnector>,
+    pin: Option<[u8; 32]>,
+}
+
+impl CoordinatorTls {
+    /// Trust the certificates in `ca_cert` in addition to the system roots,
+    /// and require the leaf certificate to hash to `pin_sha256`
+    pub fn new(ca_cert: Option<&Path>, pin_sha256: Option<&str>) -> Result<Self, VerifierError> {
+        if ca_cert.is_none() && pin_sha256.is_none() {
+            return Ok(Self::default());
+        }
+
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(path) = ca_cert {
+            for cert in read_certificates(path)? {
+                builder.add_root_certificate(cert);
+            }
+        }
+        let connector = builder
+            .build()
+            .map_err(|e| VerifierError::InvalidConfig(format!("coordinator TLS setup failed: {}", e)))?;
+        let pin = pin_sha256
+            .map(|pin| parse_pin(pin, "COORDINATOR_PIN_SHA256"))
+            .transpose()?;
+
+        Ok(Self {
+            connector: Some(connector.into()),
+            pin,
+        })
+    }
+
+    /// Whether a CA or pin was configured
+    pub fn is_customised(&self) -> bool {
+        self.connector.is_some()
+    }
+
+    /// Open a WebSocket with the handshake `request`, applying the custom
+    /// CA and pin if any
+    pub async fn connect(&self, request: Request) -> Result<CoordinatorStream, VerifierError> {
+        let host = request
+            .uri()
+            .host()
+            .map(|h| h.trim_start_matches('[').trim_end_matches(']').to_string())
+            .ok_or_else(|| {
+                VerifierError::Transport(format!("coordinator URL {} has no host", request.uri()))
+            })?;
+        let Some(connector) = &self.connector else {
+            let (ws_stream, _) = connect_async(request).await.map_err(|e| match e {
+                WsError::Tls(e) => VerifierError::TlsValidation(format!(
+                    "TLS handshake with coordinator {} failed: {}",
+                    host, e
+                )),
+                WsError::Io(e) => network(&host, e),
+                e => transport(e),
+            })?;
+            return Ok(ws_stream);
+        };
+        let port = request.uri().port_u16().unwrap_or(WSS_DEFAULT_PORT);
+
+        let tcp = TcpStream::connect((host.as_str(), port))
+            .await
+            .map_err(|e| network(&host, e))?;
+        let _ = tcp.set_nodelay(true);
+        let tls = connector.connect(&host, tcp).await.map_err(|e| {
+            VerifierError::TlsValidation(format!(
+                "TLS handshake with coordinator {} failed: {}",
+                host, e
+            ))
+        })?;
+
+        if let Some(pin) = &self.pin {
+            let der = tls
+                .get_ref()
+                .peer_certificate()
+                .ok()
+                .flatten()
+                .and_then(|cert| cert.to_der().ok());
+            check_pin(der.as_deref(), pin).map_err(|detail| {
+                VerifierError::CertificatePinMismatch(format!("coordinator {} {}", host, detail))
+            })?;
+        }
+
+        let (ws_stream, _) = client_async(request, MaybeTlsStream::NativeTls(tls))
+            .await
+            .map_err(transport)?;
+        Ok(ws_stream)
+    }
+}
+
+/// Every certificate in a PEM file; native-tls reads one per call
+pub(crate) fn read_certificates(
+    path: &Path,
+) -> Result<Vec<native_tls::Certificate>, VerifierError> {
+    let pem = fs::read_to_string(path).map_err(|e| {
+        VerifierError::InvalidConfig(format!("failed to read CA certificate {}: {}", path.display(), e))
+    })?;
+
+    let mut certs = Vec::new();
+    let mut rest = pem.as_str();
+    while let Some(end) = rest.find(PEM_END) {
+        let (block, tail) = rest.split_at(end + PEM_END.len());
+        let cert = native_tls::Certificate::from_pem(block.as_bytes()).map_err(|e| {
+            VerifierError::InvalidConfig(format!("invalid CA certificate in {}: {}", path.display(), e))
+        })?;
+        certs.push(cert);
+        rest = tail;
+    }
+    if certs.is_empty() {
+        return Err(VerifierError::InvalidConfig(format!(
+            "no PEM certificates in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Hex SHA-256 fingerprint, optionally colon-separated, from the variable
+/// `name`
+pub(crate) fn parse_pin(pin: &str, name: &str) -> Result<[u8; 32], VerifierError> {
+    let digits: String = pin.trim().chars().filter(|c| *c != ':').collect();
+    hex::decode(&digits)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            VerifierError::InvalidConfig(format!(
+                "{} must be a hex SHA-256 fingerprint, got {:?}",
+                name, pin
+            ))
+        })
+}
+
+/// Check a DER leaf certificate against a pin; the error says what the
+/// server presented instead
+pub(crate) fn check_pin(der: Option<&[u8]>, pin: &[u8; 32]) -> Result<(), String> {
+    let der = der.ok_or_else(|| "presented no certificate".to_string())?;
+    let actual: [u8; 32] = Sha256::digest(der).into();
+    if actual != *pin {
+        return Err(format!(
+            "presented a certificate with SHA-256 {}, expected {}",
+            hex::encode(actual),
+            hex::encode(pin)
+        ));
+    }
+    Ok(())
+}
+
+/// The host could not be reached at all, as opposed to a TLS failure
+fn network(host: &str, e: impl std::fmt::Display) -> VerifierError {
+    VerifierError::Transport(format!("network error reaching {}: {}", host, e))
+}
+
+fn transport(e: impl std::fmt::Display) -> VerifierError {
+    VerifierError::Transport(e.to_string())
+}
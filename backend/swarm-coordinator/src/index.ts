@@ -136,17 +136,38 @@ class DiscoveryRegistryClient {
 
 // --- Agent Connection Management ---
 
+// Latest periodic `status` report from an agent
+interface AgentStatus {
+    tasksCompleted: number;
+    tasksFailed: number;
+    inFlight: number;
+    uptimeSecs: number;
+    degraded: boolean;
+    receivedAt: number;
+}
+
 interface ConnectedAgent {
     ws: WebSocket;
     role: 'scout' | 'verifier' | 'synthesizer';
     agentId: string;
     address?: string;
     capabilities?: string[];
+    version?: string;
+    supportedHashAlgos?: string[];
     discoveryInfo?: AgentInfo;  // Info from ERC-8004 registry
+    status?: AgentStatus;
 }
 
 const connectedAgents: Map<string, ConnectedAgent> = new Map();
 
+const KNOWN_ROLES = ['scout', 'verifier', 'synthesizer'];
+
+// Sent in register_ack; agents record it alongside the accepted capabilities
+const PROTOCOL_VERSION = 1;
+
+// Offered by verifiers but never used here, so left out of the ack
+const UNUSED_CAPABILITIES = ['msgpack', 'cbor', 'batch_verification'];
+
 // --- Quest State Machine ---
 
 interface QuestState {
@@ -189,29 +210,96 @@ wss.on('connection', (ws: WebSocket) => {
             const message = JSON.parse(data.toString());
 
             if (message.type === 'register') {
-                agentId = message.agentId || `${message.role}-${Date.now()}`;
+                const requestedId: string = message.agentId || `${message.role}-${Date.now()}`;
+
+                if (!KNOWN_ROLES.includes(message.role)) {
+                    ws.send(JSON.stringify({
+                        type: 'register_ack',
+                        accepted: false,
+                        reason: `unsupported role: ${message.role}`
+                    }));
+                    return;
+                }
+                const existing = connectedAgents.get(requestedId);
+                if (existing && existing.ws !== ws && existing.ws.readyState === WebSocket.OPEN) {
+                    // Verifiers retry under a fresh agentId
+                    ws.send(JSON.stringify({
+                        type: 'register_ack',
+                        accepted: false,
+                        reason: 'duplicate_agent_id'
+                    }));
+                    return;
+                }
 
+                agentId = requestedId;
                 const agent: ConnectedAgent = {
                     ws,
                     role: message.role,
                     agentId: agentId as string,
                     address: message.address,
-                    capabilities: message.capabilities
+                    capabilities: message.capabilities,
+                    version: message.version,
+                    supportedHashAlgos: message.supportedHashAlgos
                 };
 
                 connectedAgents.set(agentId as string, agent);
-                console.log(`[Coordinator] Agent registered: ${agentId} (${message.role})`);
+                console.log(`[Coordinator] Agent registered: ${agentId} (${message.role}${message.version ? ` v${message.version}` : ''})`);
 
                 // Send confirmation
                 ws.send(JSON.stringify({
-                    type: 'registered',
+                    type: 'register_ack',
                     agentId,
+                    // This coordinator runs alone, so it is always the one
+                    // agents in active-active mode take tasks from
+                    primary: true,
+                    // Verifiers also offer msgpack and cbor; this coordinator only
+                    // speaks JSON text frames and sends tasks one by one
+                    encoding: 'json',
+                    acceptedCapabilities: (message.capabilities || []).filter(
+                        (c: string) => !UNUSED_CAPABILITIES.includes(c)
+                    ),
+                    protocolVersion: PROTOCOL_VERSION,
                     message: 'Successfully registered with coordinator'
                 }));
             }
 
+            if (message.type === 'deregister') {
+                // The agent is draining; keep the socket for its last
+                // results but stop dispatching to it
+                if (agentId && connectedAgents.get(agentId)?.ws === ws) {
+                    connectedAgents.delete(agentId);
+                    console.log(`[Coordinator] Agent deregistered: ${agentId}`);
+                }
+            }
+
+            if (message.type === 'status') {
+                const agent = agentId ? connectedAgents.get(agentId) : undefined;
+                if (agent && agent.ws === ws) {
+                    agent.status = {
+                        tasksCompleted: message.tasksCompleted ?? 0,
+                        tasksFailed: message.tasksFailed ?? 0,
+                        inFlight: message.inFlight ?? 0,
+                        uptimeSecs: message.uptimeSecs ?? 0,
+                        degraded: !!message.degraded,
+                        receivedAt: Date.now()
+                    };
+                }
+            }
+
+            if (message.type === 'task_rejected') {
+                handleTaskRejected(message);
+            }
+
             if (message.type === 'task_result') {
                 await handleTaskResult(message);
+
+                // Acknowledge even duplicates so agents can drop their stored copy
+                if (message.questId) {
+                    ws.send(JSON.stringify({
+                        type: 'result_ack',
+                        questId: message.questId
+                    }));
+                }
             }
 
         } catch (error) {
@@ -220,7 +308,7 @@ wss.on('connection', (ws: WebSocket) => {
     });
 
     ws.on('close', () => {
-        if (agentId) {
+        if (agentId && connectedAgents.get(agentId)?.ws === ws) {
             connectedAgents.delete(agentId);
             console.log(`[Coordinator] Agent disconnected: ${agentId}`);
         }
@@ -240,19 +328,31 @@ async function handleTaskResult(result: any): Promise<void> {
 
     console.log(`[Coordinator] Received ${status} result for quest ${questId}`);
 
-    // Clear timeout
-    if (quest.timeout) {
-        clearTimeout(quest.timeout);
-    }
+    // Only a result that advances the quest ends its stage timeout
+    const clearQuestTimeout = () => {
+        if (quest.timeout) {
+            clearTimeout(quest.timeout);
+        }
+    };
 
     if (quest.status === 'scouting' && status === 'complete') {
+        clearQuestTimeout();
+
         // Store scout results and move to verification
         quest.scoutResults = result.results;
         quest.status = 'verifying';
 
         await dispatchVerificationTask(quest);
     }
-    else if (quest.status === 'verifying' && (status === 'verified' || status === 'partial')) {
+    else if (quest.status === 'verifying' && (status === 'verified' || status === 'verified_simulated' || status === 'partial')) {
+        clearQuestTimeout();
+
+        // Dev-mode and hash-only verifiers attest nothing in hardware; the
+        // attestation keeps `simulated: true` for whoever consumes it
+        if (status === 'verified_simulated') {
+            console.warn(`[Coordinator] Quest ${questId} verified with a simulated attestation`);
+        }
+
         // Store attestation and move to synthesis
         quest.verificationAttestation = result.attestation;
         quest.status = 'synthesizing';
@@ -260,6 +360,8 @@ async function handleTaskResult(result: any): Promise<void> {
         await dispatchSynthesisTask(quest);
     }
     else if (quest.status === 'synthesizing' && status === 'complete') {
+        clearQuestTimeout();
+
         // Quest complete!
         quest.artifact = result.artifact;
         quest.status = 'complete';
@@ -277,11 +379,49 @@ async function handleTaskResult(result: any): Promise<void> {
 
         activeQuests.delete(questId);
     }
-    else if (status === 'error') {
+    else if (status === 'error' || (quest.status === 'verifying' && (status === 'failed' || status === 'attestation_rejected' || status === 'timeout'))) {
+        clearQuestTimeout();
+
         quest.status = 'failed';
-        console.error(`[Coordinator] Quest ${questId} FAILED:`, result.error);
+        const reason = result.error
+            ?? (status === 'timeout'
+                ? `verification timed out after checking ${result.verifiedChunks?.length ?? 0} chunks`
+                : `confidence ${result.attestation?.confidence_score} below floor ${result.confidenceFloor}`);
+        console.error(`[Coordinator] Quest ${questId} FAILED:`, reason);
         activeQuests.delete(questId);
     }
+    else {
+        // A resent result for a stage the quest has already left; it must
+        // not disturb the current stage or its timeout
+        console.log(`[Coordinator] Ignoring duplicate ${status} result for quest ${questId} (${quest.status})`);
+    }
+}
+
+// --- Task Rejection Handler ---
+
+// Pause before offering a rejected task again, so a busy agent can drain
+const REJECTED_TASK_RETRY_MS = 5000;
+
+function handleTaskRejected(rejection: any): void {
+    const { questId, agentId, reason, queueDepth } = rejection;
+    const quest = activeQuests.get(questId);
+
+    if (!quest || quest.status !== 'verifying' || quest.assignedAgents.verifier !== agentId) {
+        return;
+    }
+
+    console.warn(`[Coordinator] ${agentId} rejected quest ${questId} (${reason}, ${queueDepth} queued), rescheduling`);
+    if (quest.timeout) {
+        clearTimeout(quest.timeout);
+    }
+
+    setTimeout(() => {
+        // The quest may have moved on or been dropped in the meantime
+        if (activeQuests.get(questId) === quest && quest.status === 'verifying') {
+            dispatchVerificationTask(quest).catch(error =>
+                console.error(`[Coordinator] Rescheduling quest ${questId} failed:`, error));
+        }
+    }, REJECTED_TASK_RETRY_MS);
 }
 
 // --- Task Dispatchers ---
@@ -498,6 +638,15 @@ const healthServer = http.createServer((req, res) => {
                 synthesizers: Array.from(connectedAgents.values()).filter(a => a.role === 'synthesizer').length
             }
         }));
+    } else if (req.url === '/agents') {
+        // Live per-agent stats, as of each agent's last status report
+        res.writeHead(200, { 'Content-Type': 'application/json' });
+        res.end(JSON.stringify(Array.from(connectedAgents.values()).map(a => ({
+            agentId: a.agentId,
+            role: a.role,
+            version: a.version,
+            status: a.status ?? null
+        }))));
     } else {
         res.writeHead(404);
         res.end();